@@ -1,14 +1,481 @@
 use crate::split_tab::SplitTabState;
 use anyhow::{anyhow, Error};
 use dirs::config_dir;
-use ini::Ini;
+use ini::{Ini, Properties};
 use log::warn;
+use pulldown_cmark::Options;
 use rat_widget::text::{upos_type, Locale};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::create_dir_all;
-use std::path::PathBuf;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use sys_locale::get_locale;
 
+/// What to do with unsaved files on quit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OnQuit {
+    /// Save everything.
+    #[default]
+    Save,
+    /// Ask which files to save.
+    Prompt,
+    /// Throw away the changes.
+    Discard,
+}
+
+impl FromStr for OnQuit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "save" => Ok(OnQuit::Save),
+            "prompt" => Ok(OnQuit::Prompt),
+            "discard" => Ok(OnQuit::Discard),
+            _ => Err(anyhow!("invalid on_quit {}", s)),
+        }
+    }
+}
+
+impl Display for OnQuit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OnQuit::Save => write!(f, "save"),
+            OnQuit::Prompt => write!(f, "prompt"),
+            OnQuit::Discard => write!(f, "discard"),
+        }
+    }
+}
+
+/// What to do with unsaved files when the terminal looses focus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OnFocusLost {
+    /// Save everything.
+    #[default]
+    Save,
+    /// Only show that there are unsaved changes.
+    Mark,
+    /// Do nothing.
+    Nothing,
+}
+
+impl FromStr for OnFocusLost {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "save" => Ok(OnFocusLost::Save),
+            "mark" => Ok(OnFocusLost::Mark),
+            "nothing" => Ok(OnFocusLost::Nothing),
+            _ => Err(anyhow!("invalid on_focus_lost {}", s)),
+        }
+    }
+}
+
+impl Display for OnFocusLost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OnFocusLost::Save => write!(f, "save"),
+            OnFocusLost::Mark => write!(f, "mark"),
+            OnFocusLost::Nothing => write!(f, "nothing"),
+        }
+    }
+}
+
+/// Actions of the Tab key without a selection.
+/// The first one that applies is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabAction {
+    /// Accept the completion popup.
+    Completion,
+    /// Jump to the next table cell.
+    Table,
+    /// Indent the list item.
+    List,
+    /// Insert a tab.
+    Literal,
+}
+
+impl TabAction {
+    /// Default order.
+    pub fn all() -> Vec<TabAction> {
+        vec![
+            TabAction::Completion,
+            TabAction::Table,
+            TabAction::List,
+            TabAction::Literal,
+        ]
+    }
+}
+
+impl FromStr for TabAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "completion" => Ok(TabAction::Completion),
+            "table" => Ok(TabAction::Table),
+            "list" => Ok(TabAction::List),
+            "literal" => Ok(TabAction::Literal),
+            _ => Err(anyhow!("invalid tab action {}", s)),
+        }
+    }
+}
+
+impl Display for TabAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TabAction::Completion => write!(f, "completion"),
+            TabAction::Table => write!(f, "table"),
+            TabAction::List => write!(f, "list"),
+            TabAction::Literal => write!(f, "literal"),
+        }
+    }
+}
+
+/// Variants of the formatter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FormatMode {
+    /// Reflow paragraphs to the text width.
+    #[default]
+    Reflow,
+    /// Reflow and pad table columns to equal width.
+    ReflowEqWidth,
+    /// Keep the line breaks, only normalize spacing and tables.
+    KeepBreaks,
+}
+
+impl FromStr for FormatMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "reflow" => Ok(FormatMode::Reflow),
+            "eq_width" => Ok(FormatMode::ReflowEqWidth),
+            "keep_breaks" => Ok(FormatMode::KeepBreaks),
+            _ => Err(anyhow!("invalid format {}", s)),
+        }
+    }
+}
+
+impl Display for FormatMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatMode::Reflow => write!(f, "reflow"),
+            FormatMode::ReflowEqWidth => write!(f, "eq_width"),
+            FormatMode::KeepBreaks => write!(f, "keep_breaks"),
+        }
+    }
+}
+
+/// Column alignment of tables.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TableAlign {
+    /// No alignment marker.
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+impl FromStr for TableAlign {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "none" => Ok(TableAlign::None),
+            "left" => Ok(TableAlign::Left),
+            "center" => Ok(TableAlign::Center),
+            "right" => Ok(TableAlign::Right),
+            _ => Err(anyhow!("invalid table_align {}", s)),
+        }
+    }
+}
+
+impl Display for TableAlign {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TableAlign::None => write!(f, "none"),
+            TableAlign::Left => write!(f, "left"),
+            TableAlign::Center => write!(f, "center"),
+            TableAlign::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// What happens on launch without arguments.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Startup {
+    /// Restore the last session, or open the directory.
+    #[default]
+    Restore,
+    /// Open the directory.
+    OpenDir,
+    /// Start with nothing.
+    Empty,
+}
+
+impl FromStr for Startup {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "restore" => Ok(Startup::Restore),
+            "open_dir" => Ok(Startup::OpenDir),
+            "empty" => Ok(Startup::Empty),
+            _ => Err(anyhow!("invalid startup {}", s)),
+        }
+    }
+}
+
+impl Display for Startup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Startup::Restore => write!(f, "restore"),
+            Startup::OpenDir => write!(f, "open_dir"),
+            Startup::Empty => write!(f, "empty"),
+        }
+    }
+}
+
+/// How the mouse follows links.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LinkClick {
+    /// Double-click follows a link.
+    #[default]
+    DoubleClick,
+    /// Ctrl-click follows a link, double-click selects a word.
+    CtrlClick,
+}
+
+impl FromStr for LinkClick {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "double_click" => Ok(LinkClick::DoubleClick),
+            "ctrl_click" => Ok(LinkClick::CtrlClick),
+            _ => Err(anyhow!("invalid link_click {}", s)),
+        }
+    }
+}
+
+impl Display for LinkClick {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkClick::DoubleClick => write!(f, "double_click"),
+            LinkClick::CtrlClick => write!(f, "ctrl_click"),
+        }
+    }
+}
+
+/// Where copy/paste goes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardMode {
+    /// System clipboard. Falls back to OSC 52 and the
+    /// internal buffer if that fails.
+    #[default]
+    Native,
+    /// Write via OSC 52 escape sequences. Works over ssh,
+    /// pasting uses the internal buffer.
+    Osc52,
+    /// Internal buffer only.
+    Internal,
+}
+
+impl FromStr for ClipboardMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "native" => Ok(ClipboardMode::Native),
+            "osc52" => Ok(ClipboardMode::Osc52),
+            "internal" => Ok(ClipboardMode::Internal),
+            _ => Err(anyhow!("invalid clipboard {}", s)),
+        }
+    }
+}
+
+impl Display for ClipboardMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ClipboardMode::Native => write!(f, "native"),
+            ClipboardMode::Osc52 => write!(f, "osc52"),
+            ClipboardMode::Internal => write!(f, "internal"),
+        }
+    }
+}
+
+/// Markdown extensions known by name.
+/// Only extensions that change the parse results are listed.
+const MD_EXTENSIONS: [(&str, Options); 10] = [
+    ("tables", Options::ENABLE_TABLES),
+    ("footnotes", Options::ENABLE_FOOTNOTES),
+    ("strikethrough", Options::ENABLE_STRIKETHROUGH),
+    ("tasklists", Options::ENABLE_TASKLISTS),
+    ("yaml_metadata", Options::ENABLE_YAML_STYLE_METADATA_BLOCKS),
+    (
+        "toml_metadata",
+        Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS,
+    ),
+    ("math", Options::ENABLE_MATH),
+    ("gfm", Options::ENABLE_GFM),
+    ("definition_list", Options::ENABLE_DEFINITION_LIST),
+    ("wikilinks", Options::ENABLE_WIKILINKS),
+];
+
+/// Markdown flavor, the enabled parser extensions.
+///
+/// Written as a list of extension names, or one of the presets
+/// 'github', 'commonmark' and 'all'.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MdFlavor(pub Options);
+
+impl MdFlavor {
+    pub fn github() -> Self {
+        Self(
+            Options::ENABLE_TABLES
+                | Options::ENABLE_FOOTNOTES
+                | Options::ENABLE_STRIKETHROUGH
+                | Options::ENABLE_TASKLISTS
+                | Options::ENABLE_GFM,
+        )
+    }
+}
+
+impl Default for MdFlavor {
+    fn default() -> Self {
+        Self::github()
+    }
+}
+
+impl FromStr for MdFlavor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = Options::empty();
+        for name in s
+            .split([',', ' '])
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+        {
+            match name {
+                "github" => options |= Self::github().0,
+                "commonmark" => {}
+                "all" => {
+                    for (_, v) in MD_EXTENSIONS {
+                        options |= v;
+                    }
+                }
+                _ => {
+                    let Some((_, v)) = MD_EXTENSIONS.iter().find(|(n, _)| *n == name) else {
+                        return Err(anyhow!("invalid markdown extension {}", name));
+                    };
+                    options |= *v;
+                }
+            }
+        }
+        Ok(MdFlavor(options))
+    }
+}
+
+impl Display for MdFlavor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if *self == Self::github() {
+            return write!(f, "github");
+        } else if self.0.is_empty() {
+            return write!(f, "commonmark");
+        }
+        let names = MD_EXTENSIONS
+            .iter()
+            .filter(|(_, v)| self.0.contains(*v))
+            .map(|(n, _)| *n)
+            .collect::<Vec<_>>();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+/// View toggles of one file that override the global ones.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileView {
+    pub path: PathBuf,
+    pub wrap_text: Option<bool>,
+    pub show_linenr: Option<bool>,
+}
+
+/// Open files, splits and the selected tab.
+#[derive(Debug, Default, Clone)]
+pub struct EditorLayout {
+    /// Project root. Empty for the ad-hoc layout.
+    pub root: PathBuf,
+    pub edit_split_at: Vec<u16>,
+    pub tab_state: Vec<(usize, usize, PathBuf)>,
+    pub tab_cursor: Vec<(usize, usize, upos_type, upos_type)>,
+    /// Selection anchor, only for tabs with a selection.
+    pub tab_anchor: Vec<(usize, usize, upos_type, upos_type)>,
+    pub tab_offset: Vec<(usize, usize, upos_type, upos_type, upos_type)>,
+    pub tab_pinned: Vec<(usize, usize)>,
+    pub tab_selected: (usize, usize),
+}
+
+impl EditorLayout {
+    /// Layout without the files that are gone.
+    /// Splits and tabs move up to fill the gaps.
+    pub fn existing(&self) -> EditorLayout {
+        let mut files = self.tab_state.clone();
+        files.sort_by_key(|(s, t, _)| (*s, *t));
+
+        let mut map = HashMap::new();
+        let mut splits = Vec::new();
+        let mut tab_state = Vec::new();
+        for (s, t, path) in files {
+            if !path.exists() {
+                continue;
+            }
+            if splits.last() != Some(&s) {
+                splits.push(s);
+            }
+            let new_s = splits.len() - 1;
+            let new_t = tab_state.iter().filter(|(v, _, _)| *v == new_s).count();
+            map.insert((s, t), (new_s, new_t));
+            tab_state.push((new_s, new_t, path));
+        }
+
+        EditorLayout {
+            root: self.root.clone(),
+            edit_split_at: splits
+                .iter()
+                .filter_map(|s| self.edit_split_at.get(*s).copied())
+                .collect(),
+            tab_state,
+            tab_cursor: self
+                .tab_cursor
+                .iter()
+                .filter_map(|(s, t, x, y)| map.get(&(*s, *t)).map(|(s, t)| (*s, *t, *x, *y)))
+                .collect(),
+            tab_anchor: self
+                .tab_anchor
+                .iter()
+                .filter_map(|(s, t, x, y)| map.get(&(*s, *t)).map(|(s, t)| (*s, *t, *x, *y)))
+                .collect(),
+            tab_offset: self
+                .tab_offset
+                .iter()
+                .filter_map(|(s, t, x, y, z)| map.get(&(*s, *t)).map(|(s, t)| (*s, *t, *x, *y, *z)))
+                .collect(),
+            tab_pinned: self
+                .tab_pinned
+                .iter()
+                .filter_map(|v| map.get(v).copied())
+                .collect(),
+            tab_selected: map.get(&self.tab_selected).copied().unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct MDConfig {
     // system
@@ -19,10 +486,85 @@ pub struct MDConfig {
     pub text_width: u16,
     pub font: String,
     pub font_size: f64,
+    pub ligatures: bool,
+    pub line_spacing: f32,
+    pub tab_width: u16,
+
+    // editing
+    pub paste_wrap_url: bool,
+    /// Convert pasted html to markdown.
+    pub paste_html: bool,
+    pub paste_format_table: bool,
+    pub paste_indent: bool,
+    /// Ask before pasting more than this many KiB. 0 never asks.
+    pub paste_confirm_size: usize,
+    /// Inserted date. 'locale', 'iso', 'long' or a chrono format.
+    pub date_format: String,
+    /// Inserted date and time. Same as date_format.
+    pub datetime_format: String,
+    /// Format used by F8.
+    pub format_mode: FormatMode,
+    /// External formatter for F8. Gets the text via stdin.
+    pub format_command: String,
+    /// Initial text for new markdown files.
+    pub template_file: Option<PathBuf>,
+    /// Alignment for table columns without one.
+    pub table_align: TableAlign,
+    /// Typographic quotes, dashes and ellipsis while typing.
+    pub smart_punct: bool,
+    /// Markdown extensions for highlighting and links.
+    pub md_flavor: MdFlavor,
+    /// Enter keeps the leading whitespace of the line.
+    pub auto_indent: bool,
+    /// Clickable links if the terminal supports them.
+    pub hyperlinks: bool,
+    /// Mouse click that follows a link.
+    pub link_click: LinkClick,
+    /// Follow links into the next split.
+    pub link_split: bool,
+    /// Clipboard provider.
+    pub clipboard: ClipboardMode,
+    /// Selecting sets the primary selection, middle-click pastes it.
+    pub primary_selection: bool,
+    /// Tab in a list item indents by this many spaces.
+    pub list_indent: u16,
+    /// Order of the Tab key actions. Ends with Literal
+    /// if that is missing.
+    pub tab_order: Vec<TabAction>,
+    /// Lines kept between the cursor and the top/bottom edge.
+    pub scroll_off: u16,
+    pub backup: bool,
+    /// Timestamped backups to keep. 1 keeps a single .bak.
+    pub backup_count: usize,
+    pub on_quit: OnQuit,
+    /// 'mark' shows its message in the status line, which
+    /// is not visible with hide_menu.
+    pub on_focus_lost: OnFocusLost,
+    pub max_open_buffers: usize,
+    /// Files larger than this (MiB) are opened read-only.
+    pub large_file_threshold: u64,
+    /// Pause background timers while the terminal is unfocused.
+    pub pause_unfocused: bool,
 
     // startup
     pub load_file: Vec<PathBuf>,
+    pub sample: usize,
+    /// Start the ipc server.
+    pub ipc: bool,
     pub globs: Vec<String>,
+    pub use_trash: bool,
+    /// Rewrite the links to a renamed file.
+    pub rename_links: bool,
+    /// Launch without arguments.
+    pub startup: Startup,
+    /// Directory for launch without arguments. Defaults to
+    /// the current directory.
+    pub startup_dir: Option<PathBuf>,
+
+    // code blocks that may be run: language -> interpreter
+    pub run_code: Vec<(String, String)>,
+    // scripts: name -> file
+    pub scripts: Vec<(String, PathBuf)>,
 
     // auto/tmp
     pub file_split_at: u16,
@@ -30,17 +572,36 @@ pub struct MDConfig {
     pub show_break: bool,
     pub wrap_text: bool,
     pub show_linenr: bool,
+    /// Dim everything but the paragraph at the cursor.
+    pub focus_mode: bool,
+    /// Hide menu and status line. Esc still shows the menu.
+    pub hide_menu: bool,
+    /// No tab bar for a split with a single tab.
+    pub hide_single_tab: bool,
+    /// Highlight trailing whitespace.
+    pub show_trailing: bool,
+    /// Vertical line at text_width.
+    pub show_guide: bool,
     pub log_level: String,
 
-    pub edit_split_at: Vec<u16>,
-    pub tab_state: Vec<(usize, usize, PathBuf)>,
-    pub tab_cursor: Vec<(usize, usize, upos_type, upos_type)>,
-    pub tab_offset: Vec<(usize, usize, upos_type, upos_type, upos_type)>,
-    pub tab_selected: (usize, usize),
+    /// Layout for ad-hoc sessions.
+    pub layout: EditorLayout,
+    /// Layout per project root.
+    pub root_layouts: Vec<EditorLayout>,
+    /// Project root of this session. None for ad-hoc file opens.
+    pub session_root: Option<PathBuf>,
+    /// Per file view toggles.
+    pub file_views: Vec<FileView>,
 }
 
 const DEFAULT_FILE_SPLIT_AT: u16 = 15;
 const DEFAULT_TEXT_WIDTH: u16 = 65;
+pub const MIN_TEXT_WIDTH: u16 = 20;
+pub const MAX_TEXT_WIDTH: u16 = 400;
+const DEFAULT_TAB_WIDTH: u16 = 4;
+const DEFAULT_LIST_INDENT: u16 = 2;
+pub const MIN_LINE_SPACING: f32 = 0.8;
+pub const MAX_LINE_SPACING: f32 = 2.0;
 
 impl Default for MDConfig {
     fn default() -> Self {
@@ -57,174 +618,479 @@ impl Default for MDConfig {
             text_width: DEFAULT_TEXT_WIDTH,
             font: "".to_string(),
             font_size: 20.0,
+            ligatures: false,
+            line_spacing: 1.0,
+            tab_width: DEFAULT_TAB_WIDTH,
+            paste_wrap_url: true,
+            paste_html: true,
+            paste_format_table: false,
+            paste_indent: false,
+            paste_confirm_size: 256,
+            date_format: "locale".to_string(),
+            datetime_format: "locale".to_string(),
+            backup: false,
+            backup_count: 1,
+            on_quit: OnQuit::Save,
+            on_focus_lost: OnFocusLost::Save,
+            format_mode: FormatMode::Reflow,
+            format_command: Default::default(),
+            template_file: None,
+            table_align: TableAlign::None,
+            smart_punct: false,
+            auto_indent: false,
+            hyperlinks: true,
+            link_click: LinkClick::DoubleClick,
+            link_split: false,
+            clipboard: ClipboardMode::Native,
+            primary_selection: cfg!(target_os = "linux"),
+            md_flavor: Default::default(),
+            list_indent: DEFAULT_LIST_INDENT,
+            tab_order: TabAction::all(),
+            scroll_off: 0,
+            max_open_buffers: 0,
+            large_file_threshold: 64,
+            pause_unfocused: true,
             load_file: Default::default(),
+            sample: 0,
+            ipc: false,
             globs: vec!["*.md".to_string()],
+            use_trash: true,
+            rename_links: false,
+            startup: Startup::Restore,
+            startup_dir: None,
+            run_code: Default::default(),
+            scripts: Default::default(),
             log_level: "debug".to_string(),
             show_linenr: true,
-            tab_state: Default::default(),
-            tab_cursor: Default::default(),
-            tab_offset: Default::default(),
-            tab_selected: (0, 0),
-            edit_split_at: Default::default(),
+            focus_mode: false,
+            hide_menu: false,
+            hide_single_tab: false,
+            show_trailing: false,
+            show_guide: false,
+            layout: Default::default(),
+            root_layouts: Default::default(),
+            session_root: None,
+            file_views: Default::default(),
         }
     }
 }
 
 impl MDConfig {
+    /// Interpreter for the code block language, if allowed.
+    pub fn run_code_for(&self, lang: &str) -> Option<&str> {
+        self.run_code
+            .iter()
+            .find(|(v, _)| v == lang)
+            .map(|(_, cmd)| cmd.as_str())
+    }
+
+    /// Script file for the name.
+    #[cfg(feature = "scripting")]
+    pub fn script_for(&self, name: &str) -> Option<&Path> {
+        self.scripts
+            .iter()
+            .find(|(v, _)| v == name)
+            .map(|(_, file)| file.as_path())
+    }
+
     pub fn load() -> Result<MDConfig, Error> {
-        let cfg = if let Some(config) = config_dir() {
-            let config = config.join("mdedit").join("mdedit.ini");
-            if config.exists() {
-                let ini = Ini::load_from_file(config)?;
-                let sec = ini.general_section();
+        match config_dir() {
+            Some(v) => Self::load_from(&v.join("mdedit").join("mdedit.ini")),
+            None => Ok(MDConfig::default()),
+        }
+    }
 
-                let theme = sec.get("theme").unwrap_or("Imperial");
+    /// Load from this file, defaults if it doesn't exist.
+    fn load_from(config: &Path) -> Result<MDConfig, Error> {
+        let cfg = if config.exists() {
+            let ini = Ini::load_from_file(config)?;
+            let sec = ini.general_section();
 
-                let text_width = sec
-                    .get("text_width")
-                    .unwrap_or(DEFAULT_TEXT_WIDTH.to_string().as_str())
-                    .parse()
-                    .unwrap_or(DEFAULT_TEXT_WIDTH);
-
-                let font = sec.get("font")
-                    .unwrap_or("").trim().to_string();
-                let font_size = sec.get("font-size")
-                    .unwrap_or("20")
-                    .parse::<f64>()
-                    .unwrap_or(20.0);
-
-                let mut globs = sec
-                    .get("file_pattern")
-                    .unwrap_or("*.md")
-                    .split([' ', ','])
-                    .map(|v| v.to_string())
-                    .collect::<Vec<_>>();
-                globs.sort();
-                globs.dedup();
-
-                let show_ctrl = sec
-                    .get("show_ctrl")
-                    .unwrap_or("false")
-                    .parse()
-                    .unwrap_or(false);
-                let show_break = sec
-                    .get("show_break")
-                    .unwrap_or("false")
-                    .parse()
-                    .unwrap_or(false);
-                let wrap_text = sec
-                    .get("wrap_text")
-                    .unwrap_or("false")
-                    .parse()
-                    .unwrap_or(false);
-                let show_linenr = sec
-                    .get("show_linenr")
-                    .unwrap_or("true")
-                    .parse()
-                    .unwrap_or(true);
+            let theme = sec.get("theme").unwrap_or("Imperial");
+
+            let text_width = sec
+                .get("text_width")
+                .unwrap_or(DEFAULT_TEXT_WIDTH.to_string().as_str())
+                .parse()
+                .unwrap_or(DEFAULT_TEXT_WIDTH)
+                .clamp(MIN_TEXT_WIDTH, MAX_TEXT_WIDTH);
+
+            let font = sec.get("font").unwrap_or("").trim().to_string();
+            let font_size = sec
+                .get("font-size")
+                .unwrap_or("20")
+                .parse::<f64>()
+                .unwrap_or(20.0);
+            let ligatures = sec
+                .get("ligatures")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let line_spacing = sec
+                .get("line_spacing")
+                .unwrap_or("1.0")
+                .parse::<f32>()
+                .unwrap_or(1.0)
+                .clamp(MIN_LINE_SPACING, MAX_LINE_SPACING);
+            let tab_width = sec
+                .get("tab_width")
+                .unwrap_or(DEFAULT_TAB_WIDTH.to_string().as_str())
+                .parse::<u16>()
+                .unwrap_or(DEFAULT_TAB_WIDTH)
+                .clamp(1, 16);
+
+            let mut globs = sec
+                .get("file_pattern")
+                .unwrap_or("*.md")
+                .split([' ', ','])
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>();
+            globs.sort();
+            globs.dedup();
+
+            let show_ctrl = sec
+                .get("show_ctrl")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let show_break = sec
+                .get("show_break")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let wrap_text = sec
+                .get("wrap_text")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let show_linenr = sec
+                .get("show_linenr")
+                .unwrap_or("true")
+                .parse()
+                .unwrap_or(true);
+            let focus_mode = sec
+                .get("focus_mode")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let hide_menu = sec
+                .get("hide_menu")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let hide_single_tab = sec
+                .get("hide_single_tab")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let show_trailing = sec
+                .get("show_trailing")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let show_guide = sec
+                .get("show_guide")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
 
-                let log = sec.get("log").unwrap_or("warn").trim().to_string();
+            let use_trash = sec
+                .get("use_trash")
+                .unwrap_or("true")
+                .parse()
+                .unwrap_or(true);
+            let rename_links = sec
+                .get("rename_links")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let startup = sec
+                .get("startup")
+                .unwrap_or("restore")
+                .parse()
+                .unwrap_or_default();
+            let startup_dir = sec
+                .get("startup_dir")
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from);
 
-                let file_split_at = DEFAULT_FILE_SPLIT_AT;
-                if let Some(sec) = ini.section(Some("ui")) {
-                    sec.get("file_split_at")
-                        .unwrap_or(DEFAULT_FILE_SPLIT_AT.to_string().as_str())
-                        .parse()
-                        .unwrap_or(DEFAULT_FILE_SPLIT_AT);
+            let paste_wrap_url = sec
+                .get("paste_wrap_url")
+                .unwrap_or("true")
+                .parse()
+                .unwrap_or(true);
+
+            let paste_html = sec
+                .get("paste_html")
+                .unwrap_or("true")
+                .parse()
+                .unwrap_or(true);
+
+            let paste_format_table = sec
+                .get("paste_format_table")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+
+            let paste_indent = sec
+                .get("paste_indent")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let paste_confirm_size = sec
+                .get("paste_confirm_size")
+                .unwrap_or("256")
+                .parse()
+                .unwrap_or(256);
+            let date_format = sec.get("date_format").unwrap_or("locale").to_string();
+            let datetime_format = sec.get("datetime_format").unwrap_or("locale").to_string();
+
+            let backup = sec
+                .get("backup")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let mut backup_count = sec.get("backup_count").unwrap_or("1").parse().unwrap_or(1);
+            if backup_count == 0 {
+                warn!("backup_count must be at least 1");
+                backup_count = 1;
+            }
+
+            let on_quit = sec
+                .get("on_quit")
+                .unwrap_or("save")
+                .parse()
+                .unwrap_or_default();
+
+            let on_focus_lost = sec
+                .get("on_focus_lost")
+                .unwrap_or("save")
+                .parse()
+                .unwrap_or_default();
+
+            let format_mode = sec
+                .get("format")
+                .unwrap_or("reflow")
+                .parse()
+                .unwrap_or_default();
+
+            let format_command = sec.get("format_command").unwrap_or("").trim().to_string();
+            let template_file = sec
+                .get("template_file")
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from);
+
+            let table_align = sec
+                .get("table_align")
+                .unwrap_or("none")
+                .parse()
+                .unwrap_or_default();
+
+            let md_flavor = match sec.get("markdown").unwrap_or("github").parse() {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!("markdown: {}", e);
+                    MdFlavor::default()
                 }
+            };
+            let auto_indent = sec
+                .get("auto_indent")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let hyperlinks = sec
+                .get("hyperlinks")
+                .unwrap_or("true")
+                .parse()
+                .unwrap_or(true);
+            let link_click = sec
+                .get("link_click")
+                .unwrap_or("double_click")
+                .parse()
+                .unwrap_or_default();
+            let link_split = sec
+                .get("link_split")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+            let clipboard = sec
+                .get("clipboard")
+                .unwrap_or("native")
+                .parse()
+                .unwrap_or_default();
+            let primary_selection = sec
+                .get("primary_selection")
+                .unwrap_or(if cfg!(target_os = "linux") {
+                    "true"
+                } else {
+                    "false"
+                })
+                .parse()
+                .unwrap_or(cfg!(target_os = "linux"));
+            let smart_punct = sec
+                .get("smart_punct")
+                .unwrap_or("false")
+                .parse()
+                .unwrap_or(false);
+
+            let list_indent = sec
+                .get("list_indent")
+                .unwrap_or("2")
+                .parse()
+                .unwrap_or(DEFAULT_LIST_INDENT)
+                .clamp(1, 8);
 
-                let mut tab_state = Vec::new();
-                let mut tab_cursor = Vec::new();
-                let mut tab_offset = Vec::new();
-                let mut tab_selected = (0, 0);
-                let mut edit_split_at = Vec::new();
-                if let Some(sec) = ini.section(Some("editor")) {
-                    'f: {
-                        for (k, v) in sec.iter() {
-                            if k.starts_with("file.") {
-                                let Some((s, t)) = Self::split_tab(k, v) else {
-                                    break 'f;
-                                };
-                                let path = PathBuf::from(v);
-                                if !path.exists() {
-                                    warn!("file not found {}", path.to_string_lossy());
-                                    break 'f;
-                                }
-                                tab_state.push((s, t, PathBuf::from(v)));
-                            } else if k.starts_with("cursor.") {
-                                let Some((s, t)) = Self::split_tab(k, v) else {
-                                    break 'f;
-                                };
-                                let Some((x, y)) = Self::split_cursor(k, v) else {
-                                    break 'f;
-                                };
-                                tab_cursor.push((s, t, x, y));
-                            } else if k.starts_with("offset.") {
-                                let Some((s, t)) = Self::split_tab(k, v) else {
-                                    break 'f;
-                                };
-                                let Some((x, y, z)) = Self::split_offset(k, v) else {
-                                    break 'f;
-                                };
-                                tab_offset.push((s, t, x, y, z));
-                            }
-                        }
-
-                        if let Some(sel) = sec.get("selected") {
-                            let mut sit = sel.split('.');
-                            let Some(s) = sit.next() else {
-                                warn!("no selected split in {}", sel);
-                                break 'f;
-                            };
-                            let Ok(s) = s.parse::<usize>() else {
-                                warn!("invalid split {} in {}", s, sel);
-                                break 'f;
-                            };
-                            let Some(t) = sit.next() else {
-                                warn!("no selected tab in {}", sel);
-                                break 'f;
-                            };
-                            let Ok(t) = t.parse::<usize>() else {
-                                warn!("invalid split {} in {}", t, sel);
-                                break 'f;
-                            };
-                            tab_selected = (s, t);
-                        }
-
-                        if let Some(split) = sec.get("editor_widths") {
-                            for s in split.split(',') {
-                                let Ok(s) = s.trim().parse::<u16>() else {
-                                    warn!("invalid split {} in {}", s, split);
-                                    break 'f;
-                                };
-                                edit_split_at.push(s);
-                            }
-                        }
+            let mut tab_order = Vec::new();
+            if let Some(v) = sec.get("tab_order") {
+                for v in v.split([' ', ',']).filter(|v| !v.is_empty()) {
+                    match v.parse() {
+                        Ok(v) => tab_order.push(v),
+                        Err(e) => warn!("tab_order: {}", e),
                     }
                 }
+            }
+            if tab_order.is_empty() {
+                tab_order = TabAction::all();
+            } else if !tab_order.contains(&TabAction::Literal) {
+                // Tab must do something.
+                tab_order.push(TabAction::Literal);
+            }
 
-                Some(MDConfig {
-                    theme: theme.into(),
-                    file_split_at,
-                    text_width,
-                    font,
-                    font_size,
-                    globs,
-                    show_ctrl,
-                    show_break,
-                    wrap_text,
-                    show_linenr,
-                    log_level: log,
-                    tab_state,
-                    tab_cursor,
-                    tab_offset,
-                    tab_selected,
-                    edit_split_at,
-                    ..Default::default()
-                })
+            let scroll_off = sec.get("scroll_off").unwrap_or("0").parse().unwrap_or(0);
+
+            let max_open_buffers = sec
+                .get("max_open_buffers")
+                .unwrap_or("0")
+                .parse()
+                .unwrap_or(0);
+
+            let large_file_threshold = sec
+                .get("large_file_threshold")
+                .unwrap_or("64")
+                .parse()
+                .unwrap_or(64);
+
+            let pause_unfocused = sec
+                .get("pause_unfocused")
+                .unwrap_or("true")
+                .parse()
+                .unwrap_or(true);
+
+            let log = sec.get("log").unwrap_or("warn").trim().to_string();
+
+            let mut file_split_at = DEFAULT_FILE_SPLIT_AT;
+            if let Some(sec) = ini.section(Some("ui")) {
+                file_split_at = sec
+                    .get("file_split_at")
+                    .unwrap_or(DEFAULT_FILE_SPLIT_AT.to_string().as_str())
+                    .parse()
+                    .unwrap_or(DEFAULT_FILE_SPLIT_AT);
+            }
+
+            let mut run_code = Vec::new();
+            if let Some(sec) = ini.section(Some("run")) {
+                for (k, v) in sec.iter() {
+                    let k = k.trim();
+                    let v = v.trim();
+                    if k.is_empty() || v.is_empty() {
+                        warn!("invalid run entry {} = {}", k, v);
+                        continue;
+                    }
+                    run_code.push((k.to_string(), v.to_string()));
+                }
+            }
+
+            let mut scripts = Vec::new();
+            if let Some(sec) = ini.section(Some("scripts")) {
+                for (k, v) in sec.iter() {
+                    let k = k.trim();
+                    let v = v.trim();
+                    if k.is_empty() || v.is_empty() {
+                        warn!("invalid script entry {} = {}", k, v);
+                        continue;
+                    }
+                    scripts.push((k.to_string(), PathBuf::from(v)));
+                }
+            }
+
+            let (layout, file_views) = if let Some(sec) = ini.section(Some("editor")) {
+                (Self::load_layout(sec), Self::load_file_views(sec))
             } else {
-                None
+                (EditorLayout::default(), Vec::new())
+            };
+
+            let mut root_layouts = Vec::new();
+            for (name, sec) in ini.iter() {
+                if name.is_some_and(|v| v.starts_with("editor.")) {
+                    let layout = Self::load_layout(sec);
+                    if !layout.root.as_os_str().is_empty() {
+                        root_layouts.push(layout);
+                    }
+                }
             }
+
+            Some(MDConfig {
+                theme: theme.into(),
+                file_split_at,
+                text_width,
+                font,
+                font_size,
+                ligatures,
+                line_spacing,
+                tab_width,
+                paste_wrap_url,
+                paste_html,
+                paste_format_table,
+                paste_indent,
+                paste_confirm_size,
+                date_format,
+                datetime_format,
+                backup,
+                backup_count,
+                on_quit,
+                on_focus_lost,
+                format_mode,
+                format_command,
+                template_file,
+                table_align,
+                smart_punct,
+                auto_indent,
+                hyperlinks,
+                link_click,
+                link_split,
+                clipboard,
+                primary_selection,
+                md_flavor,
+                list_indent,
+                tab_order,
+                scroll_off,
+                max_open_buffers,
+                large_file_threshold,
+                pause_unfocused,
+                globs,
+                use_trash,
+                rename_links,
+                startup,
+                startup_dir,
+                run_code,
+                scripts,
+                show_ctrl,
+                show_break,
+                wrap_text,
+                show_linenr,
+                focus_mode,
+                hide_menu,
+                hide_single_tab,
+                show_trailing,
+                show_guide,
+                log_level: log,
+                layout,
+                root_layouts,
+                file_views,
+                ..Default::default()
+            })
         } else {
             None
         };
@@ -282,6 +1148,135 @@ impl MDConfig {
         Some((x, y))
     }
 
+    /// Parse an editor section.
+    fn load_layout(sec: &Properties) -> EditorLayout {
+        let mut layout = EditorLayout {
+            root: sec.get("root").map(PathBuf::from).unwrap_or_default(),
+            ..Default::default()
+        };
+
+        'f: {
+            for (k, v) in sec.iter() {
+                if k.starts_with("file.") {
+                    let Some((s, t)) = Self::split_tab(k, v) else {
+                        break 'f;
+                    };
+                    let path = PathBuf::from(v);
+                    if !path.exists() {
+                        warn!("file not found {}", path.to_string_lossy());
+                        break 'f;
+                    }
+                    layout.tab_state.push((s, t, PathBuf::from(v)));
+                } else if k.starts_with("cursor.") {
+                    let Some((s, t)) = Self::split_tab(k, v) else {
+                        break 'f;
+                    };
+                    let Some((x, y)) = Self::split_cursor(k, v) else {
+                        break 'f;
+                    };
+                    layout.tab_cursor.push((s, t, x, y));
+                } else if k.starts_with("anchor.") {
+                    let Some((s, t)) = Self::split_tab(k, v) else {
+                        break 'f;
+                    };
+                    let Some((x, y)) = Self::split_cursor(k, v) else {
+                        break 'f;
+                    };
+                    layout.tab_anchor.push((s, t, x, y));
+                } else if k.starts_with("pinned.") {
+                    let Some((s, t)) = Self::split_tab(k, v) else {
+                        break 'f;
+                    };
+                    if v.trim().parse().unwrap_or(false) {
+                        layout.tab_pinned.push((s, t));
+                    }
+                } else if k.starts_with("offset.") {
+                    let Some((s, t)) = Self::split_tab(k, v) else {
+                        break 'f;
+                    };
+                    let Some((x, y, z)) = Self::split_offset(k, v) else {
+                        break 'f;
+                    };
+                    layout.tab_offset.push((s, t, x, y, z));
+                }
+            }
+
+            if let Some(sel) = sec.get("selected") {
+                let mut sit = sel.split('.');
+                let Some(s) = sit.next() else {
+                    warn!("no selected split in {}", sel);
+                    break 'f;
+                };
+                let Ok(s) = s.parse::<usize>() else {
+                    warn!("invalid split {} in {}", s, sel);
+                    break 'f;
+                };
+                let Some(t) = sit.next() else {
+                    warn!("no selected tab in {}", sel);
+                    break 'f;
+                };
+                let Ok(t) = t.parse::<usize>() else {
+                    warn!("invalid split {} in {}", t, sel);
+                    break 'f;
+                };
+                layout.tab_selected = (s, t);
+            }
+
+            if let Some(split) = sec.get("editor_widths") {
+                for s in split.split(',') {
+                    let Ok(s) = s.trim().parse::<u16>() else {
+                        warn!("invalid split {} in {}", s, split);
+                        break 'f;
+                    };
+                    layout.edit_split_at.push(s);
+                }
+            }
+        }
+
+        layout
+    }
+
+    /// Entries are stored as `view.n = wrap,linenr,path`.
+    /// A '-' means the global setting.
+    fn load_file_views(sec: &Properties) -> Vec<FileView> {
+        let mut views = Vec::new();
+        for (k, v) in sec.iter() {
+            if !k.starts_with("view.") {
+                continue;
+            }
+            let mut v_it = v.splitn(3, ',');
+            let (Some(wrap), Some(linenr), Some(path)) = (v_it.next(), v_it.next(), v_it.next())
+            else {
+                warn!("invalid view {} = {}", k, v);
+                continue;
+            };
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                continue;
+            }
+            views.push(FileView {
+                path,
+                wrap_text: wrap.trim().parse().ok(),
+                show_linenr: linenr.trim().parse().ok(),
+            });
+        }
+        views
+    }
+
+    /// View toggles for the file.
+    pub fn file_view(&self, path: &Path) -> Option<&FileView> {
+        self.file_views.iter().find(|v| v.path == path)
+    }
+
+    /// Remember the view toggles of a file.
+    /// Views without overrides are removed.
+    pub fn set_file_view(&mut self, view: FileView) {
+        self.file_views.retain(|v| v.path != view.path);
+        if view.wrap_text.is_some() || view.show_linenr.is_some() {
+            self.file_views.push(view);
+        }
+    }
+
     fn split_tab(k: &str, v: &str) -> Option<(usize, usize)> {
         let mut k_it = k.split('.');
         k_it.next();
@@ -304,18 +1299,40 @@ impl MDConfig {
         Some((s, t))
     }
 
+    /// Layout for the project root.
+    pub fn layout_for(&self, root: &Path) -> Option<&EditorLayout> {
+        self.root_layouts.iter().find(|v| v.root == root)
+    }
+
     pub fn store_file_state(&mut self, split_tab: &SplitTabState) {
-        if let Some(pos) = split_tab.selected_pos() {
-            self.tab_selected = pos;
+        let layout = self.current_layout(split_tab);
+
+        if self.session_root.is_some() {
+            if let Some(v) = self.root_layouts.iter_mut().find(|v| v.root == layout.root) {
+                *v = layout;
+            } else {
+                self.root_layouts.push(layout);
+            }
         } else {
-            self.tab_selected = (0, 0);
+            self.layout = layout;
+        }
+    }
+
+    /// Layout of the open files.
+    pub fn current_layout(&self, split_tab: &SplitTabState) -> EditorLayout {
+        let mut layout = EditorLayout {
+            root: self.session_root.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        if let Some(pos) = split_tab.selected_pos() {
+            layout.tab_selected = pos;
         }
 
-        self.edit_split_at.clear();
-        self.edit_split_at
+        layout
+            .edit_split_at
             .extend_from_slice(split_tab.split.area_lengths());
 
-        self.tab_state.clear();
         for (sidx, s) in split_tab.split_tab_file.iter().enumerate() {
             for (tidx, t) in s.iter().enumerate() {
                 let edit = &split_tab.split_tab_file[sidx][tidx].edit;
@@ -323,9 +1340,16 @@ impl MDConfig {
                 let offset = edit.offset();
                 let sub_offset = edit.sub_row_offset();
 
-                self.tab_state.push((sidx, tidx, t.path.clone()));
-                self.tab_cursor.push((sidx, tidx, cursor.x, cursor.y));
-                self.tab_offset.push((
+                layout.tab_state.push((sidx, tidx, t.path.clone()));
+                layout.tab_cursor.push((sidx, tidx, cursor.x, cursor.y));
+                if edit.has_selection() {
+                    let anchor = edit.anchor();
+                    layout.tab_anchor.push((sidx, tidx, anchor.x, anchor.y));
+                }
+                if t.pinned {
+                    layout.tab_pinned.push((sidx, tidx));
+                }
+                layout.tab_offset.push((
                     sidx,
                     tidx,
                     offset.0 as upos_type,
@@ -334,78 +1358,217 @@ impl MDConfig {
                 ));
             }
         }
+
+        layout
+    }
+
+    fn store_layout(ini: &mut Ini, name: String, layout: &EditorLayout) {
+        let mut sec = ini.with_section(Some(name));
+        if !layout.root.as_os_str().is_empty() {
+            sec.set("root", layout.root.to_string_lossy());
+        }
+        sec.set(
+            "selected",
+            format!("{}.{}", layout.tab_selected.0, layout.tab_selected.1),
+        );
+        for (s, t, f) in &layout.tab_state {
+            sec.set(
+                format!("file.{}.{}", *s, *t),
+                format!("{}", f.to_string_lossy()),
+            );
+        }
+        for (s, t, x, y) in &layout.tab_cursor {
+            sec.set(format!("cursor.{}.{}", *s, *t), format!("{},{}", *x, *y));
+        }
+        for (s, t, x, y) in &layout.tab_anchor {
+            sec.set(format!("anchor.{}.{}", *s, *t), format!("{},{}", *x, *y));
+        }
+        for (s, t) in &layout.tab_pinned {
+            sec.set(format!("pinned.{}.{}", *s, *t), "true");
+        }
+        for (s, t, ox, oy, os) in &layout.tab_offset {
+            sec.set(
+                format!("offset.{}.{}", *s, *t),
+                format!("{},{},{}", *ox, *oy, *os),
+            );
+        }
+        let mut file_split = String::new();
+        for s in &layout.edit_split_at {
+            if !file_split.is_empty() {
+                file_split.push_str(",");
+            }
+            file_split.push_str(format!("{}", *s).as_str());
+        }
+        sec.set("editor_widths", file_split);
     }
 
     pub fn store(&self) -> Result<(), Error> {
-        if let Some(config_root) = config_dir() {
-            let config_dir = config_root.join("mdedit");
+        match config_dir() {
+            Some(v) => self.store_to(&v.join("mdedit").join("mdedit.ini")),
+            None => Err(anyhow!("Can't save cfg.")),
+        }
+    }
+
+    /// Store to this file, creates the directory if needed.
+    fn store_to(&self, config: &Path) -> Result<(), Error> {
+        if let Some(config_dir) = config.parent() {
             if !config_dir.exists() {
-                create_dir_all(&config_dir)?;
+                create_dir_all(config_dir)?;
             }
+        }
 
-            let config = config_dir.join("mdedit.ini");
-            let mut ini = Ini::new();
-            let mut sec = ini.with_general_section();
-            sec.set("theme", self.theme.clone());
-            sec.set("text_width", self.text_width.to_string());
-            sec.set("font", self.font.clone());
-            sec.set("font-size", self.font_size.to_string());
-            sec.set(
-                "file_pattern",
-                self.globs
-                    .iter()
-                    .cloned()
-                    .reduce(|mut v, w| {
-                        v.push(',');
-                        v.push(' ');
-                        v.push_str(&w);
-                        v
-                    })
-                    .unwrap_or("*.md".to_string()),
-            );
-            sec.set("log", self.log_level.clone());
-            sec.set("show_ctrl", self.show_ctrl.to_string());
-            sec.set("show_break", self.show_break.to_string());
-            sec.set("wrap_text", self.wrap_text.to_string());
-            sec.set("show_linenr", self.show_linenr.to_string());
+        let mut ini = Ini::new();
+        let mut sec = ini.with_general_section();
+        sec.set("theme", self.theme.clone());
+        sec.set("text_width", self.text_width.to_string());
+        sec.set("font", self.font.clone());
+        sec.set("font-size", self.font_size.to_string());
+        sec.set("ligatures", self.ligatures.to_string());
+        sec.set("line_spacing", self.line_spacing.to_string());
+        sec.set("tab_width", self.tab_width.to_string());
+        sec.set(
+            "file_pattern",
+            self.globs
+                .iter()
+                .cloned()
+                .reduce(|mut v, w| {
+                    v.push(',');
+                    v.push(' ');
+                    v.push_str(&w);
+                    v
+                })
+                .unwrap_or("*.md".to_string()),
+        );
+        sec.set("use_trash", self.use_trash.to_string());
+        sec.set("rename_links", self.rename_links.to_string());
+        sec.set("startup", self.startup.to_string());
+        sec.set(
+            "startup_dir",
+            self.startup_dir
+                .as_ref()
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+        sec.set("log", self.log_level.clone());
+        sec.set("show_ctrl", self.show_ctrl.to_string());
+        sec.set("show_break", self.show_break.to_string());
+        sec.set("wrap_text", self.wrap_text.to_string());
+        sec.set("show_linenr", self.show_linenr.to_string());
+        sec.set("focus_mode", self.focus_mode.to_string());
+        sec.set("hide_menu", self.hide_menu.to_string());
+        sec.set("hide_single_tab", self.hide_single_tab.to_string());
+        sec.set("show_trailing", self.show_trailing.to_string());
+        sec.set("show_guide", self.show_guide.to_string());
+        sec.set("paste_wrap_url", self.paste_wrap_url.to_string());
+        sec.set("paste_html", self.paste_html.to_string());
+        sec.set("paste_format_table", self.paste_format_table.to_string());
+        sec.set("paste_indent", self.paste_indent.to_string());
+        sec.set("paste_confirm_size", self.paste_confirm_size.to_string());
+        sec.set("date_format", self.date_format.clone());
+        sec.set("datetime_format", self.datetime_format.clone());
+        sec.set("backup", self.backup.to_string());
+        sec.set("backup_count", self.backup_count.to_string());
+        sec.set("on_quit", self.on_quit.to_string());
+        sec.set("on_focus_lost", self.on_focus_lost.to_string());
+        sec.set("format", self.format_mode.to_string());
+        sec.set("format_command", self.format_command.clone());
+        sec.set(
+            "template_file",
+            self.template_file
+                .as_ref()
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+        sec.set("table_align", self.table_align.to_string());
+        sec.set("smart_punct", self.smart_punct.to_string());
+        sec.set("auto_indent", self.auto_indent.to_string());
+        sec.set("hyperlinks", self.hyperlinks.to_string());
+        sec.set("link_click", self.link_click.to_string());
+        sec.set("link_split", self.link_split.to_string());
+        sec.set("clipboard", self.clipboard.to_string());
+        sec.set("primary_selection", self.primary_selection.to_string());
+        sec.set("markdown", self.md_flavor.to_string());
+        sec.set("list_indent", self.list_indent.to_string());
+        sec.set(
+            "tab_order",
+            self.tab_order
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        sec.set("scroll_off", self.scroll_off.to_string());
+        sec.set("max_open_buffers", self.max_open_buffers.to_string());
+        sec.set(
+            "large_file_threshold",
+            self.large_file_threshold.to_string(),
+        );
+        sec.set("pause_unfocused", self.pause_unfocused.to_string());
+
+        let mut sec = ini.with_section(Some("ui"));
+        sec.set("file_split_at", self.file_split_at.to_string());
 
-            let mut sec = ini.with_section(Some("ui"));
-            sec.set("file_split_at", self.file_split_at.to_string());
+        let mut sec = ini.with_section(Some("run"));
+        for (lang, cmd) in &self.run_code {
+            sec.set(lang.as_str(), cmd.as_str());
+        }
+
+        let mut sec = ini.with_section(Some("scripts"));
+        for (name, file) in &self.scripts {
+            sec.set(name.as_str(), file.to_string_lossy());
+        }
 
-            let mut sec = ini.with_section(Some("editor"));
+        Self::store_layout(&mut ini, "editor".into(), &self.layout);
+        let mut sec = ini.with_section(Some("editor"));
+        for (n, view) in self.file_views.iter().enumerate() {
+            let flag = |v: Option<bool>| v.map(|v| v.to_string()).unwrap_or("-".into());
             sec.set(
-                "selected",
-                format!("{}.{}", self.tab_selected.0, self.tab_selected.1),
+                format!("view.{}", n),
+                format!(
+                    "{},{},{}",
+                    flag(view.wrap_text),
+                    flag(view.show_linenr),
+                    view.path.to_string_lossy()
+                ),
             );
-            for (s, t, f) in &self.tab_state {
-                sec.set(
-                    format!("file.{}.{}", *s, *t),
-                    format!("{}", f.to_string_lossy()),
-                );
-            }
-            for (s, t, x, y) in &self.tab_cursor {
-                sec.set(format!("cursor.{}.{}", *s, *t), format!("{},{}", *x, *y));
-            }
-            for (s, t, ox, oy, os) in &self.tab_offset {
-                sec.set(
-                    format!("offset.{}.{}", *s, *t),
-                    format!("{},{},{}", *ox, *oy, *os),
-                );
-            }
-            let mut file_split = String::new();
-            for s in &self.edit_split_at {
-                if !file_split.is_empty() {
-                    file_split.push_str(",");
-                }
-                file_split.push_str(format!("{}", *s).as_str());
-            }
-            sec.set("editor_widths", file_split);
+        }
+        for layout in &self.root_layouts {
+            let mut hasher = DefaultHasher::new();
+            layout.root.hash(&mut hasher);
+            let name = format!("editor.{:016x}", hasher.finish());
+            Self::store_layout(&mut ini, name, layout);
+        }
 
-            ini.write_to_file(config)?;
+        ini.write_to_file(config)?;
 
-            Ok(())
-        } else {
-            Err(anyhow!("Can't save cfg."))
-        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MDConfig, DEFAULT_FILE_SPLIT_AT};
+    use std::{env, fs, process};
+
+    #[test]
+    fn test_file_split_at() {
+        let dir = env::temp_dir().join(format!("mdedit-cfg-{}", process::id()));
+        _ = fs::remove_dir_all(&dir);
+        let file = dir.join("mdedit").join("mdedit.ini");
+
+        let cfg = MDConfig {
+            file_split_at: 42,
+            ..Default::default()
+        };
+        cfg.store_to(&file).expect("store");
+        let cfg = MDConfig::load_from(&file).expect("load");
+        assert_eq!(cfg.file_split_at, 42);
+
+        // older files have no [ui] section.
+        fs::write(&file, "theme = Imperial\n").expect("write");
+        let cfg = MDConfig::load_from(&file).expect("load");
+        assert_eq!(cfg.file_split_at, DEFAULT_FILE_SPLIT_AT);
+
+        fs::remove_dir_all(&dir).expect("cleanup");
     }
 }
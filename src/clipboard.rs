@@ -0,0 +1,136 @@
+//! Clipboard providers.
+
+use crate::cfg::ClipboardMode;
+use log::warn;
+use rat_widget::text::clipboard::{Clipboard, ClipboardError};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Clipboard shared by all editors.
+///
+/// Everything copied is kept in an internal buffer too,
+/// that's the last resort if nothing else works.
+#[derive(Debug, Default, Clone)]
+pub struct MDClipboard {
+    mode: ClipboardMode,
+    clip: Rc<RefCell<String>>,
+    primary: Rc<RefCell<String>>,
+}
+
+impl MDClipboard {
+    pub fn new(mode: ClipboardMode) -> Self {
+        Self {
+            mode,
+            clip: Default::default(),
+            primary: Default::default(),
+        }
+    }
+
+    /// Primary selection. Falls back to the internal buffer.
+    pub fn primary(&self) -> String {
+        match get_primary() {
+            Some(v) => v,
+            None => self.primary.borrow().clone(),
+        }
+    }
+
+    /// Set the primary selection.
+    pub fn set_primary(&self, s: &str) {
+        *self.primary.borrow_mut() = s.to_string();
+        set_primary(s);
+    }
+}
+
+impl Clipboard for MDClipboard {
+    fn get_string(&self) -> Result<String, ClipboardError> {
+        match self.mode {
+            ClipboardMode::Native => match cli_clipboard::get_contents() {
+                Ok(v) => Ok(v),
+                Err(e) => {
+                    warn!("{:?}", e);
+                    Ok(self.clip.borrow().clone())
+                }
+            },
+            // reading via osc52 is rarely allowed.
+            ClipboardMode::Osc52 | ClipboardMode::Internal => Ok(self.clip.borrow().clone()),
+        }
+    }
+
+    fn set_string(&self, s: &str) -> Result<(), ClipboardError> {
+        *self.clip.borrow_mut() = s.to_string();
+
+        match self.mode {
+            ClipboardMode::Native => match cli_clipboard::set_contents(s.to_string()) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    warn!("{:?}", e);
+                    // still in the internal buffer.
+                    _ = osc52_copy(s);
+                    Ok(())
+                }
+            },
+            ClipboardMode::Osc52 => osc52_copy(s).map_err(|e| {
+                warn!("{:?}", e);
+                ClipboardError
+            }),
+            ClipboardMode::Internal => Ok(()),
+        }
+    }
+}
+
+/// Copy to the terminal's clipboard.
+#[cfg(feature = "term")]
+fn osc52_copy(s: &str) -> Result<(), std::io::Error> {
+    use crate::image_preview::base64;
+    use std::io::{stdout, Write};
+
+    let mut out = stdout().lock();
+    write!(out, "\x1b]52;c;{}\x07", base64(s.as_bytes()))?;
+    out.flush()
+}
+
+/// There is no terminal.
+#[cfg(not(feature = "term"))]
+fn osc52_copy(_: &str) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+/// Primary selection from X11.
+#[cfg(target_os = "linux")]
+fn get_primary() -> Option<String> {
+    use cli_clipboard::x11_clipboard::{Primary, X11ClipboardContext};
+    use cli_clipboard::ClipboardProvider;
+
+    let mut clip = X11ClipboardContext::<Primary>::new().ok()?;
+    match clip.get_contents() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!("{:?}", e);
+            None
+        }
+    }
+}
+
+/// No primary selection.
+#[cfg(not(target_os = "linux"))]
+fn get_primary() -> Option<String> {
+    None
+}
+
+/// Set the X11 primary selection.
+#[cfg(target_os = "linux")]
+fn set_primary(s: &str) {
+    use cli_clipboard::x11_clipboard::{Primary, X11ClipboardContext};
+    use cli_clipboard::ClipboardProvider;
+
+    let Ok(mut clip) = X11ClipboardContext::<Primary>::new() else {
+        return;
+    };
+    if let Err(e) = clip.set_contents(s.to_string()) {
+        warn!("{:?}", e);
+    }
+}
+
+/// No primary selection.
+#[cfg(not(target_os = "linux"))]
+fn set_primary(_: &str) {}
@@ -0,0 +1,84 @@
+//! Run external commands with the text as stdin.
+
+use anyhow::{anyhow, Error};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Max runtime of a command.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Split the command line into program and arguments.
+/// Quotes and backslash escapes work like in a posix shell.
+pub fn split(cmd: &str) -> Result<(String, Vec<String>), Error> {
+    let mut args = shell_words::split(cmd).map_err(|e| anyhow!("{}: {}", cmd, e))?;
+    if args.is_empty() {
+        return Err(anyhow!("empty command"));
+    }
+    let program = args.remove(0);
+    Ok((program, args))
+}
+
+/// Run the command with input as stdin.
+///
+/// stdin, stdout and stderr each get their own thread, so big
+/// input or output can't block the command. The command is
+/// killed after the timeout.
+pub fn run(program: &str, args: &[String], dir: &Path, input: String) -> Result<Output, Error> {
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("{}: {}", program, e))?;
+
+    let stdin = child.stdin.take();
+    let writer = thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            // the command may exit without reading everything.
+            _ = stdin.write_all(input.as_bytes());
+        }
+    });
+    let stdout = child.stdout.take();
+    let read_out = thread::spawn(move || read_all(stdout));
+    let stderr = child.stderr.take();
+    let read_err = thread::spawn(move || read_all(stderr));
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            _ = child.kill();
+            _ = child.wait();
+            return Err(anyhow!(
+                "{} timed out after {}s",
+                program,
+                TIMEOUT.as_secs()
+            ));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    _ = writer.join();
+    let stdout = read_out.join().unwrap_or_default();
+    let stderr = read_err.join().unwrap_or_default();
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+fn read_all(r: Option<impl Read>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if let Some(mut r) = r {
+        _ = r.read_to_end(&mut buf);
+    }
+    buf
+}
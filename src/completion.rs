@@ -0,0 +1,184 @@
+use crate::global::GlobalState;
+use rat_theme4::StyleName;
+use rat_widget::util::reset_buf_area;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Widget};
+use std::path::{Component, Path, PathBuf};
+
+/// Max number of files collected for link completion.
+const MAX_LINK_TARGETS: usize = 5000;
+/// Max visible rows of the popup.
+const MAX_ROWS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// After `](`
+    Link,
+    /// After `[^`
+    Footnote,
+}
+
+/// Completion popup in the editor.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub kind: CompletionKind,
+    /// Byte position of the completed text. Directly after the trigger.
+    pub start: usize,
+    /// All candidates.
+    candidates: Vec<String>,
+    /// Candidates matching the typed prefix.
+    pub items: Vec<String>,
+    pub cursor: usize,
+    offset: usize,
+}
+
+impl Completion {
+    pub fn new(kind: CompletionKind, start: usize, candidates: Vec<String>) -> Self {
+        Self {
+            kind,
+            start,
+            items: candidates.clone(),
+            candidates,
+            cursor: 0,
+            offset: 0,
+        }
+    }
+
+    /// Filter with the typed prefix.
+    pub fn filter(&mut self, prefix: &str) {
+        let prefix = prefix.to_lowercase();
+        self.items = self
+            .candidates
+            .iter()
+            .filter(|v| v.to_lowercase().starts_with(prefix.as_str()))
+            .cloned()
+            .collect();
+        self.cursor = self.cursor.min(self.items.len().saturating_sub(1));
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.items.get(self.cursor).map(|v| v.as_str())
+    }
+
+    pub fn prev(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn next(&mut self) {
+        if self.cursor + 1 < self.items.len() {
+            self.cursor += 1;
+        }
+    }
+}
+
+/// Render the popup below the screen position, or above
+/// if there is not enough space. Stays inside bounds.
+pub fn render(
+    pos: (u16, u16),
+    bounds: Rect,
+    buf: &mut Buffer,
+    state: &mut Completion,
+    ctx: &GlobalState,
+) {
+    if state.items.is_empty() {
+        return;
+    }
+
+    let rows = state.items.len().min(MAX_ROWS);
+    let width = state
+        .items
+        .iter()
+        .map(|v| v.chars().count())
+        .max()
+        .unwrap_or_default()
+        .min(bounds.width.saturating_sub(2) as usize) as u16
+        + 2;
+    let height = rows as u16 + 2;
+
+    let (x, y) = pos;
+    let y = if y + 1 + height <= bounds.bottom() {
+        y + 1
+    } else {
+        y.saturating_sub(height).max(bounds.y)
+    };
+    let x = x.min(bounds.right().saturating_sub(width)).max(bounds.x);
+    let area = Rect::new(x, y, width, height).intersection(bounds);
+
+    // keep the cursor visible
+    if state.cursor < state.offset {
+        state.offset = state.cursor;
+    } else if state.cursor >= state.offset + rows {
+        state.offset = state.cursor + 1 - rows;
+    }
+
+    let block = Block::bordered()
+        .style(ctx.theme.style_style(Style::CONTAINER_BASE))
+        .border_style(ctx.theme.style_style(Style::CONTAINER_BORDER_FG));
+    let inner = block.inner(area);
+    reset_buf_area(area, buf);
+    block.render(area, buf);
+
+    for (row, idx) in (state.offset..state.items.len())
+        .take(inner.height as usize)
+        .enumerate()
+    {
+        let line = Line::from(state.items[idx].as_str());
+        let line = if idx == state.cursor {
+            line.style(ctx.theme.style_style(Style::FOCUS))
+        } else {
+            line
+        };
+        line.render(
+            Rect::new(inner.x, inner.y + row as u16, inner.width, 1),
+            buf,
+        );
+    }
+}
+
+/// Walk the root for link targets.
+/// Uses the standard filters for ignored and hidden files.
+pub fn link_targets(root: &Path) -> Vec<PathBuf> {
+    let mut targets = Vec::new();
+
+    let walk = ignore::WalkBuilder::new(root)
+        .standard_filters(true)
+        .build();
+    for w in walk {
+        let Ok(w) = w else {
+            continue;
+        };
+        if w.file_type().is_some_and(|v| v.is_file()) {
+            targets.push(w.into_path());
+        }
+        if targets.len() >= MAX_LINK_TARGETS {
+            break;
+        }
+    }
+    targets.sort();
+    targets
+}
+
+/// Path of `path` relative to the directory `base`.
+/// Both must be absolute.
+pub fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let base = base.components().collect::<Vec<_>>();
+    let path = path.components().collect::<Vec<_>>();
+
+    let common = base
+        .iter()
+        .zip(path.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut rel = PathBuf::new();
+    for _ in common..base.len() {
+        rel.push(Component::ParentDir);
+    }
+    for c in &path[common..] {
+        rel.push(c);
+    }
+    rel
+}
@@ -0,0 +1,57 @@
+//! Current date for inserting into the text.
+
+use anyhow::{anyhow, Error};
+use chrono::Local;
+use rat_widget::text::Locale;
+use std::fmt::Write;
+
+/// Format for the date, or date and time.
+///
+/// Presets are 'locale', 'iso' and 'long', anything else
+/// is a chrono format string like '%d.%m.%Y'.
+pub fn pattern(format: &str, with_time: bool) -> &str {
+    match (format, with_time) {
+        ("locale", false) => "%x",
+        ("locale", true) => "%x %X",
+        ("iso", false) => "%Y-%m-%d",
+        ("iso", true) => "%Y-%m-%d %H:%M",
+        ("long", false) => "%A, %-d %B %Y",
+        ("long", true) => "%A, %-d %B %Y %H:%M",
+        (format, _) => format,
+    }
+}
+
+/// Current local date with the format from [pattern].
+/// Day and month names follow the locale.
+pub fn now(format: &str, with_time: bool, loc: Locale) -> Result<String, Error> {
+    let pattern = pattern(format, with_time);
+    let mut res = String::new();
+    write!(res, "{}", Local::now().format_localized(pattern, loc))
+        .map_err(|_| anyhow!("Invalid date format '{}'", pattern))?;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{now, pattern};
+    use rat_widget::text::Locale;
+
+    #[test]
+    fn test_pattern() {
+        assert_eq!(pattern("locale", false), "%x");
+        assert_eq!(pattern("locale", true), "%x %X");
+        assert_eq!(pattern("iso", false), "%Y-%m-%d");
+        assert_eq!(pattern("iso", true), "%Y-%m-%d %H:%M");
+        assert_eq!(pattern("long", false), "%A, %-d %B %Y");
+        assert_eq!(pattern("long", true), "%A, %-d %B %Y %H:%M");
+        // anything else is used as is
+        assert_eq!(pattern("%d.%m.%Y", true), "%d.%m.%Y");
+    }
+
+    #[test]
+    fn test_now() {
+        let date = now("iso", false, Locale::POSIX).expect("date");
+        assert_eq!(date.len(), 10);
+        assert!(now("%Q", false, Locale::POSIX).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::global::event::MDEvent;
+use crate::global::GlobalState;
+use crate::rat_salsa::Control;
+use crate::rat_salsa::SalsaContext;
+use anyhow::Error;
+use rat_theme4::{StyleName, WidgetStyle};
+use rat_widget::button::{Button, ButtonState};
+use rat_widget::event::{ct_event, try_flow, ButtonOutcome, HandleEvent, Regular};
+use rat_widget::focus::{FocusBuilder, FocusFlag, HasFocus};
+use rat_widget::layout::layout_middle;
+use rat_widget::util::reset_buf_area;
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::Style;
+use ratatui::widgets::{Block, Padding, Paragraph, StatefulWidget, Widget, Wrap};
+use std::any::Any;
+
+/// Message with a row of buttons.
+/// Each button closes the dialog with its event.
+/// The first letter of a button is its hotkey.
+pub struct ChoiceDialogState {
+    title: String,
+    msg: String,
+    choices: Vec<(String, MDEvent, ButtonState)>,
+}
+
+impl ChoiceDialogState {
+    pub fn new(title: &str, msg: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            msg: msg.to_string(),
+            choices: Default::default(),
+        }
+    }
+
+    pub fn choice(mut self, label: &str, event: MDEvent) -> Self {
+        self.choices
+            .push((label.to_string(), event, ButtonState::default()));
+        if self.choices.len() == 1 {
+            self.choices[0].2.focus.set(true);
+        }
+        self
+    }
+}
+
+impl HasFocus for ChoiceDialogState {
+    fn build(&self, builder: &mut FocusBuilder) {
+        for (_, _, button) in &self.choices {
+            builder.widget(button);
+        }
+    }
+
+    fn focus(&self) -> FocusFlag {
+        unimplemented!("not defined")
+    }
+
+    fn area(&self) -> Rect {
+        unimplemented!("not defined")
+    }
+}
+
+pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut GlobalState) {
+    let state = state.downcast_mut::<ChoiceDialogState>().expect("state");
+
+    let dlg_area = layout_middle(
+        area,
+        Constraint::Percentage(24),
+        Constraint::Percentage(24),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+    );
+    let dlg_area = Rect::new(
+        dlg_area.x,
+        dlg_area.y,
+        dlg_area.width,
+        // grow for longer messages
+        dlg_area
+            .height
+            .min(10.max(state.msg.lines().count() as u16 + 4)),
+    );
+
+    let block = Block::bordered()
+        .title(state.title.as_str())
+        .padding(Padding::new(1, 1, 1, 0))
+        .style(ctx.theme.style_style(Style::DIALOG_BASE))
+        .border_style(ctx.theme.style_style(Style::DIALOG_BORDER_FG));
+    let inner = block.inner(dlg_area);
+
+    reset_buf_area(dlg_area, buf);
+    block.render(dlg_area, buf);
+
+    let l = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).split(inner);
+
+    Paragraph::new(state.msg.as_str())
+        .wrap(Wrap { trim: false })
+        .render(l[0], buf);
+
+    let l2 = Layout::horizontal(
+        state
+            .choices
+            .iter()
+            .map(|(label, _, _)| Constraint::Length(label.len() as u16 + 4)),
+    )
+    .spacing(1)
+    .flex(Flex::End)
+    .split(l[1]);
+
+    for (idx, (label, _, button)) in state.choices.iter_mut().enumerate() {
+        Button::new(label.as_str())
+            .styles(ctx.theme.style(WidgetStyle::BUTTON))
+            .render(l2[idx], buf, button);
+    }
+}
+
+pub fn event(
+    event: &MDEvent,
+    state: &mut dyn Any,
+    ctx: &mut GlobalState,
+) -> Result<Control<MDEvent>, Error> {
+    let state = state.downcast_mut::<ChoiceDialogState>().expect("state");
+
+    match event {
+        MDEvent::Event(event) => {
+            let mut focus = FocusBuilder::build_for(state);
+            let f = focus.handle(event, Regular);
+            ctx.queue(f);
+
+            try_flow!(match event {
+                ct_event!(keycode press Esc) => Control::Close(MDEvent::NoOp),
+                ct_event!(keycode press Left) => {
+                    focus.prev();
+                    Control::Changed
+                }
+                ct_event!(keycode press Right) => {
+                    focus.next();
+                    Control::Changed
+                }
+                _ => Control::Continue,
+            });
+
+            let mut pressed = None;
+            for (idx, (label, _, button)) in state.choices.iter_mut().enumerate() {
+                let hotkey = label
+                    .chars()
+                    .next()
+                    .map(|c| c.to_ascii_lowercase())
+                    .unwrap_or(' ');
+                match button.handle(
+                    event,
+                    KeyEvent::new(KeyCode::Char(hotkey), KeyModifiers::NONE),
+                ) {
+                    ButtonOutcome::Pressed => {
+                        pressed = Some(idx);
+                        break;
+                    }
+                    ButtonOutcome::Continue => {}
+                    r => return Ok(r.into()),
+                }
+            }
+            if let Some(idx) = pressed {
+                let (_, event, _) = state.choices.swap_remove(idx);
+                return Ok(Control::Close(event));
+            }
+
+            Ok(Control::Unchanged)
+        }
+        _ => Ok(Control::Continue),
+    }
+}
@@ -1,17 +1,24 @@
+use crate::cfg::{MdFlavor, MAX_TEXT_WIDTH, MIN_TEXT_WIDTH};
+#[cfg(all(feature = "wgpu", not(feature = "term")))]
+use crate::cfg::{MAX_LINE_SPACING, MIN_LINE_SPACING};
 use crate::global::event::MDEvent;
 use crate::global::theme::create_mdedit_theme;
 use crate::global::GlobalState;
 use crate::rat_salsa::Control;
 use crate::rat_salsa::SalsaContext;
 use anyhow::Error;
+use log::warn;
 #[cfg(all(feature = "wgpu", not(feature = "term")))]
 use rat_salsa_wgpu::font::FontData;
 use rat_theme4::{salsa_themes, StyleName, WidgetStyle};
 use rat_widget::button::{Button, ButtonState};
+use rat_widget::checkbox::{Checkbox, CheckboxState};
 use rat_widget::choice::{Choice, ChoiceState};
 #[cfg(all(feature = "wgpu", not(feature = "term")))]
 use rat_widget::event::SliderOutcome;
-use rat_widget::event::{try_flow, ButtonOutcome, ChoiceOutcome, HandleEvent, Popup, Regular};
+use rat_widget::event::{
+    try_flow, ButtonOutcome, ChoiceOutcome, HandleEvent, Popup, Regular, TextOutcome,
+};
 use rat_widget::focus::{FocusBuilder, FocusFlag, HasFocus};
 use rat_widget::form::{Form, FormState};
 use rat_widget::layout::{layout_middle, FormLabel, FormWidget, LayoutForm};
@@ -34,10 +41,19 @@ pub struct ConfigDialogState {
     theme: ChoiceState<String>,
     text_width: NumberInputState,
     globs: TextInputState,
+    markdown: TextInputState,
+    trailing: CheckboxState,
+    guide: CheckboxState,
     #[cfg(all(feature = "wgpu", not(feature = "term")))]
     font: ChoiceState<String>,
     #[cfg(all(feature = "wgpu", not(feature = "term")))]
     font_size: SliderState<f64>,
+    #[cfg(all(feature = "wgpu", not(feature = "term")))]
+    line_spacing: SliderState<f32>,
+    #[cfg(all(feature = "wgpu", not(feature = "term")))]
+    ligatures: CheckboxState,
+    #[cfg(all(feature = "wgpu", not(feature = "term")))]
+    tab_width: NumberInputState,
 
     ok_button: ButtonState,
     cancel_button: ButtonState,
@@ -97,6 +113,21 @@ pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut Globa
             FormLabel::Str("Files glob"),
             FormWidget::Width(35),
         );
+        layout.widget(
+            state.markdown.id(),
+            FormLabel::Str("Markdown"),
+            FormWidget::Width(35),
+        );
+        layout.widget(
+            state.trailing.id(),
+            FormLabel::Str("Trailing spaces"),
+            FormWidget::Width(10),
+        );
+        layout.widget(
+            state.guide.id(),
+            FormLabel::Str("Width guide"),
+            FormWidget::Width(10),
+        );
         #[cfg(all(feature = "wgpu", not(feature = "term")))]
         {
             layout.widget(
@@ -109,6 +140,21 @@ pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut Globa
                 FormLabel::Str("Size"),
                 FormWidget::Width(10),
             );
+            layout.widget(
+                state.line_spacing.id(),
+                FormLabel::Str("Line spacing"),
+                FormWidget::Width(10),
+            );
+            layout.widget(
+                state.ligatures.id(),
+                FormLabel::Str("Ligatures"),
+                FormWidget::Width(10),
+            );
+            layout.widget(
+                state.tab_width.id(),
+                FormLabel::Str("Tab width"),
+                FormWidget::Width(15),
+            );
         }
         form = form.layout(layout.build_endless(layout_size.width));
     }
@@ -138,6 +184,21 @@ pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut Globa
         || TextInput::new().styles(ctx.theme.style(WidgetStyle::TEXT)),
         &mut state.globs,
     );
+    form.render(
+        state.markdown.id(),
+        || TextInput::new().styles(ctx.theme.style(WidgetStyle::TEXT)),
+        &mut state.markdown,
+    );
+    form.render(
+        state.trailing.id(),
+        || Checkbox::new().styles(ctx.theme.style(WidgetStyle::CHECKBOX)),
+        &mut state.trailing,
+    );
+    form.render(
+        state.guide.id(),
+        || Checkbox::new().styles(ctx.theme.style(WidgetStyle::CHECKBOX)),
+        &mut state.guide,
+    );
     #[cfg(all(feature = "wgpu", not(feature = "term")))]
     let font_popup = {
         let font_popup = form.render2(
@@ -169,6 +230,29 @@ pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut Globa
             },
             &mut state.font_size,
         );
+        let ls = state.line_spacing.value();
+        form.render(
+            state.line_spacing.id(),
+            || {
+                Slider::<f32>::new()
+                    .range((MIN_LINE_SPACING, MAX_LINE_SPACING))
+                    .step(0.1)
+                    .long_step(0.5)
+                    .upper_bound(format!(" {:.1}", ls))
+                    .styles(ctx.theme.style(WidgetStyle::SLIDER))
+            },
+            &mut state.line_spacing,
+        );
+        form.render(
+            state.ligatures.id(),
+            || Checkbox::new().styles(ctx.theme.style(WidgetStyle::CHECKBOX)),
+            &mut state.ligatures,
+        );
+        form.render(
+            state.tab_width.id(),
+            || NumberInput::new().styles(ctx.theme.style(WidgetStyle::TEXT)),
+            &mut state.tab_width,
+        );
 
         font_popup
     };
@@ -182,8 +266,13 @@ pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut Globa
         state
             .text_width
             .screen_cursor()
-            .or(state.globs.screen_cursor()),
+            .or(state.globs.screen_cursor())
+            .or(state.markdown.screen_cursor()),
     );
+    #[cfg(all(feature = "wgpu", not(feature = "term")))]
+    if let Some(cursor) = state.tab_width.screen_cursor() {
+        ctx.set_screen_cursor(Some(cursor));
+    }
 
     // buttons
     let l2 = Layout::horizontal([Constraint::Length(15), Constraint::Length(15)])
@@ -204,10 +293,16 @@ impl HasFocus for ConfigDialogState {
         builder.widget(&self.theme);
         builder.widget(&self.text_width);
         builder.widget(&self.globs);
+        builder.widget(&self.markdown);
+        builder.widget(&self.trailing);
+        builder.widget(&self.guide);
         #[cfg(all(feature = "wgpu", not(feature = "term")))]
         {
             builder.widget(&self.font);
             builder.widget(&self.font_size);
+            builder.widget(&self.line_spacing);
+            builder.widget(&self.ligatures);
+            builder.widget(&self.tab_width);
         }
         builder.widget(&self.ok_button);
         builder.widget(&self.cancel_button);
@@ -250,7 +345,13 @@ pub fn event(
                 }
                 r => r.into(),
             });
-            try_flow!(state.text_width.handle(event, Regular));
+            try_flow!(match state.text_width.handle(event, Regular) {
+                TextOutcome::TextChanged => {
+                    state.validate_text_width();
+                    Control::Changed
+                }
+                r => r.into(),
+            });
             #[cfg(all(feature = "wgpu", not(feature = "term")))]
             {
                 try_flow!(match state.font.handle(event, Popup) {
@@ -267,8 +368,14 @@ pub fn event(
                     }
                     r => r.into(),
                 });
+                try_flow!(state.line_spacing.handle(event, Regular));
+                try_flow!(state.ligatures.handle(event, Regular));
+                try_flow!(state.tab_width.handle(event, Regular));
             }
             try_flow!(state.globs.handle(event, Regular));
+            try_flow!(state.markdown.handle(event, Regular));
+            try_flow!(state.trailing.handle(event, Regular));
+            try_flow!(state.guide.handle(event, Regular));
 
             try_flow!(match state
                 .ok_button
@@ -295,6 +402,8 @@ impl ConfigDialogState {
     pub fn new(ctx: &mut GlobalState) -> Result<Self, Error> {
         let mut s = Self::default();
         s.text_width.set_format_loc("###0", ctx.cfg.loc)?;
+        #[cfg(all(feature = "wgpu", not(feature = "term")))]
+        s.tab_width.set_format_loc("#0", ctx.cfg.loc)?;
 
         let cfg = &ctx.cfg;
         s.theme.set_value(cfg.theme.clone());
@@ -303,6 +412,9 @@ impl ConfigDialogState {
         {
             s.font_size.set_value(ctx.font_size());
             s.font.set_value(cfg.font.clone());
+            s.line_spacing.set_value(cfg.line_spacing);
+            s.ligatures.set_value(cfg.ligatures);
+            s.tab_width.set_value(cfg.tab_width)?;
         }
         s.globs
             .set_value(cfg.globs.iter().fold(String::new(), |mut v, w| {
@@ -313,12 +425,26 @@ impl ConfigDialogState {
                 v
             }));
 
+        s.markdown.set_value(cfg.md_flavor.to_string());
+        s.trailing.set_value(cfg.show_trailing);
+        s.guide.set_value(cfg.show_guide);
+
         let focus = FocusBuilder::build_for(&s);
         focus.first();
 
         Ok(s)
     }
 
+    /// Mark the text width invalid if out of range.
+    /// It's clamped when saving.
+    fn validate_text_width(&mut self) {
+        let valid = self
+            .text_width
+            .value::<u16>()
+            .is_ok_and(|v| (MIN_TEXT_WIDTH..=MAX_TEXT_WIDTH).contains(&v));
+        self.text_width.set_invalid(!valid);
+    }
+
     fn cancel(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
         let theme = salsa_themes()
             .iter()
@@ -333,11 +459,21 @@ impl ConfigDialogState {
     fn save(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
         let cfg = &mut ctx.cfg;
         cfg.theme = self.theme.value();
-        cfg.text_width = self.text_width.value()?;
+        cfg.text_width = self
+            .text_width
+            .value::<u16>()?
+            .clamp(MIN_TEXT_WIDTH, MAX_TEXT_WIDTH);
         #[cfg(all(feature = "wgpu", not(feature = "term")))]
         {
             cfg.font = self.font.value();
             cfg.font_size = self.font_size.value();
+            cfg.line_spacing = self
+                .line_spacing
+                .value()
+                .clamp(MIN_LINE_SPACING, MAX_LINE_SPACING);
+            cfg.ligatures = self.ligatures.value();
+            cfg.tab_width = self.tab_width.value::<u16>()?.clamp(1, 16);
+            ctx.queue_event(MDEvent::CfgTabWidth);
         }
         cfg.globs = self
             .globs
@@ -351,6 +487,17 @@ impl ConfigDialogState {
                 }
             })
             .collect();
+        match self.markdown.value::<String>().parse::<MdFlavor>() {
+            Ok(md_flavor) if md_flavor != cfg.md_flavor => {
+                cfg.md_flavor = md_flavor;
+                ctx.queue_event(MDEvent::CfgMdFlavor);
+            }
+            Ok(_) => {}
+            // keep the current flavor
+            Err(e) => warn!("markdown: {}", e),
+        }
+        cfg.show_trailing = self.trailing.value();
+        cfg.show_guide = self.guide.value();
 
         ctx.queue_event(MDEvent::StoreConfig);
         Ok(Control::Close(MDEvent::NoOp))
@@ -0,0 +1,159 @@
+use crate::global::event::MDEvent;
+use crate::global::GlobalState;
+use crate::rat_salsa::Control;
+use anyhow::Error;
+use rat_theme4::StyleName;
+use rat_widget::event::{ct_event, try_flow};
+use rat_widget::layout::layout_middle;
+use rat_widget::util::reset_buf_area;
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Padding, Widget};
+use std::any::Any;
+use std::path::PathBuf;
+
+/// List of unsaved files. Save a selection of them.
+#[derive(Debug)]
+pub struct DirtyDialogState {
+    files: Vec<(PathBuf, bool)>,
+    cursor: usize,
+    offset: usize,
+    // quit afterwards
+    quit: bool,
+}
+
+impl DirtyDialogState {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self {
+            files: files.into_iter().map(|v| (v, true)).collect(),
+            cursor: 0,
+            offset: 0,
+            quit: false,
+        }
+    }
+
+    /// Ask before quitting.
+    pub fn new_quit(files: Vec<PathBuf>) -> Self {
+        Self {
+            quit: true,
+            ..Self::new(files)
+        }
+    }
+
+    fn selected(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, sel)| *sel)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut GlobalState) {
+    let state = state.downcast_mut::<DirtyDialogState>().expect("state");
+
+    let dlg_area = layout_middle(
+        area,
+        Constraint::Percentage(19),
+        Constraint::Percentage(19),
+        Constraint::Percentage(19),
+        Constraint::Percentage(19),
+    );
+
+    let block = Block::bordered()
+        .title(if state.quit {
+            "Unsaved files - Quit"
+        } else {
+            "Unsaved files"
+        })
+        .padding(Padding::new(1, 1, 1, 0))
+        .style(ctx.theme.style_style(Style::DIALOG_BASE))
+        .border_style(ctx.theme.style_style(Style::DIALOG_BORDER_FG));
+    let inner = block.inner(dlg_area);
+
+    reset_buf_area(dlg_area, buf);
+    block.render(dlg_area, buf);
+
+    let l = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).split(inner);
+
+    // keep the cursor visible
+    let height = l[0].height as usize;
+    if state.cursor < state.offset {
+        state.offset = state.cursor;
+    } else if height > 0 && state.cursor >= state.offset + height {
+        state.offset = state.cursor + 1 - height;
+    }
+
+    for (row, idx) in (state.offset..state.files.len()).take(height).enumerate() {
+        let (path, sel) = &state.files[idx];
+        let mark = if *sel { "\u{2611}" } else { "\u{2610}" };
+        let line = Line::from(format!("{} {}", mark, path.display()));
+        let line = if idx == state.cursor {
+            line.style(ctx.theme.style_style(Style::FOCUS))
+        } else {
+            line
+        };
+        line.render(Rect::new(l[0].x, l[0].y + row as u16, l[0].width, 1), buf);
+    }
+
+    let hint = if state.quit {
+        "Space toggle | A all | Enter save+quit | D discard+quit | Esc cancel"
+    } else {
+        "Space toggle | A all | Enter save | Esc cancel"
+    };
+    Line::from(hint).dim().render(l[1], buf);
+}
+
+pub fn event(
+    event: &MDEvent,
+    state: &mut dyn Any,
+    _ctx: &mut GlobalState,
+) -> Result<Control<MDEvent>, Error> {
+    let state = state.downcast_mut::<DirtyDialogState>().expect("state");
+
+    match event {
+        MDEvent::Event(event) => {
+            try_flow!(match event {
+                ct_event!(keycode press Esc) => Control::Close(MDEvent::NoOp),
+                ct_event!(keycode press Enter) => {
+                    if state.quit {
+                        Control::Close(MDEvent::SaveAndQuit(state.selected()))
+                    } else {
+                        Control::Close(MDEvent::SaveFiles(state.selected()))
+                    }
+                }
+                ct_event!(key press 'd') if state.quit => {
+                    Control::Close(MDEvent::SaveAndQuit(Vec::new()))
+                }
+                ct_event!(keycode press Up) => {
+                    state.cursor = state.cursor.saturating_sub(1);
+                    Control::Changed
+                }
+                ct_event!(keycode press Down) => {
+                    if state.cursor + 1 < state.files.len() {
+                        state.cursor += 1;
+                    }
+                    Control::Changed
+                }
+                ct_event!(key press ' ') => {
+                    if let Some((_, sel)) = state.files.get_mut(state.cursor) {
+                        *sel = !*sel;
+                    }
+                    Control::Changed
+                }
+                ct_event!(key press 'a') => {
+                    let all = state.files.iter().all(|(_, sel)| *sel);
+                    for (_, sel) in state.files.iter_mut() {
+                        *sel = !all;
+                    }
+                    Control::Changed
+                }
+                _ => Control::Continue,
+            });
+            Ok(Control::Unchanged)
+        }
+        _ => Ok(Control::Continue),
+    }
+}
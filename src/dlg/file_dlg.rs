@@ -98,3 +98,45 @@ pub fn event_save_as(
         _ => Ok(Control::Continue),
     }
 }
+
+pub fn event_export_session(
+    event: &MDEvent,
+    state: &mut dyn Any,
+    ctx: &mut GlobalState,
+) -> Result<Control<MDEvent>, Error> {
+    let state = state
+        .downcast_mut::<FileDialogState>()
+        .expect("dialog-state");
+    match event {
+        MDEvent::Event(event) => match state.handle(event, Dialog)? {
+            FileOutcome::Cancel => Ok(Control::Close(MDEvent::NoOp)),
+            FileOutcome::Ok(p) => {
+                ctx.queue_event(MDEvent::ExportSession(p));
+                Ok(Control::Close(MDEvent::NoOp))
+            }
+            r => Ok(Outcome::from(r).into()),
+        },
+        _ => Ok(Control::Continue),
+    }
+}
+
+pub fn event_import_session(
+    event: &MDEvent,
+    state: &mut dyn Any,
+    ctx: &mut GlobalState,
+) -> Result<Control<MDEvent>, Error> {
+    let state = state
+        .downcast_mut::<FileDialogState>()
+        .expect("dialog-state");
+    match event {
+        MDEvent::Event(event) => match state.handle(event, Dialog)? {
+            FileOutcome::Cancel => Ok(Control::Close(MDEvent::NoOp)),
+            FileOutcome::Ok(p) => {
+                ctx.queue_event(MDEvent::ImportSession(p));
+                Ok(Control::Close(MDEvent::NoOp))
+            }
+            r => Ok(Outcome::from(r).into()),
+        },
+        _ => Ok(Control::Continue),
+    }
+}
@@ -0,0 +1,151 @@
+use crate::global::event::MDEvent;
+use crate::global::GlobalState;
+use crate::rat_salsa::Control;
+use crate::rat_salsa::SalsaContext;
+use anyhow::Error;
+use rat_theme4::{StyleName, WidgetStyle};
+use rat_widget::event::{ct_event, try_flow, HandleEvent, Regular};
+use rat_widget::layout::layout_middle;
+use rat_widget::text::HasScreenCursor;
+use rat_widget::text_input::{TextInput, TextInputState};
+use rat_widget::util::reset_buf_area;
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Padding, StatefulWidget, Widget};
+use std::any::Any;
+
+/// Small one-line input.
+#[derive(Debug)]
+pub struct InputDialogState {
+    title: String,
+    label: String,
+    input: TextInputState,
+    on_ok: fn(String) -> MDEvent,
+    /// Older values, newest last. Up/Down cycles through them.
+    history: Vec<String>,
+    history_idx: Option<usize>,
+}
+
+impl InputDialogState {
+    pub fn new(title: &str, label: &str, on_ok: fn(String) -> MDEvent) -> Self {
+        let mut s = Self {
+            title: title.to_string(),
+            label: label.to_string(),
+            input: TextInputState::named("input"),
+            on_ok,
+            history: Default::default(),
+            history_idx: None,
+        };
+        s.input.focus.set(true);
+        s
+    }
+
+    pub fn with_value(mut self, value: &str) -> Self {
+        self.input.set_value(value);
+        self
+    }
+
+    pub fn with_history(mut self, history: &[String]) -> Self {
+        self.history = history.to_vec();
+        self
+    }
+
+    fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let idx = match self.history_idx {
+            Some(idx) => idx.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_idx = Some(idx);
+        self.input.set_value(self.history[idx].as_str());
+    }
+
+    fn history_next(&mut self) {
+        let Some(idx) = self.history_idx else {
+            return;
+        };
+        if idx + 1 < self.history.len() {
+            self.history_idx = Some(idx + 1);
+            self.input.set_value(self.history[idx + 1].as_str());
+        } else {
+            self.history_idx = None;
+            self.input.set_value("");
+        }
+    }
+}
+
+pub fn render(area: Rect, buf: &mut Buffer, state: &mut dyn Any, ctx: &mut GlobalState) {
+    let state = state.downcast_mut::<InputDialogState>().expect("state");
+
+    let dlg_area = layout_middle(
+        area,
+        Constraint::Percentage(29),
+        Constraint::Percentage(29),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+    );
+    let dlg_area = Rect::new(
+        dlg_area.x,
+        dlg_area.y,
+        dlg_area.width,
+        dlg_area.height.min(6),
+    );
+
+    let block = Block::bordered()
+        .title(state.title.as_str())
+        .padding(Padding::new(1, 1, 1, 1))
+        .style(ctx.theme.style_style(Style::DIALOG_BASE))
+        .border_style(ctx.theme.style_style(Style::DIALOG_BORDER_FG));
+    let inner = block.inner(dlg_area);
+
+    reset_buf_area(dlg_area, buf);
+    block.render(dlg_area, buf);
+
+    let l = Layout::horizontal([
+        Constraint::Length(state.label.len() as u16 + 1),
+        Constraint::Fill(1),
+    ])
+    .split(Rect::new(inner.x, inner.y, inner.width, 1));
+
+    Line::from(state.label.as_str()).render(l[0], buf);
+    TextInput::new()
+        .styles(ctx.theme.style(WidgetStyle::TEXT))
+        .render(l[1], buf, &mut state.input);
+
+    ctx.set_screen_cursor(state.input.screen_cursor());
+}
+
+pub fn event(
+    event: &MDEvent,
+    state: &mut dyn Any,
+    _ctx: &mut GlobalState,
+) -> Result<Control<MDEvent>, Error> {
+    let state = state.downcast_mut::<InputDialogState>().expect("state");
+
+    match event {
+        MDEvent::Event(event) => {
+            try_flow!(match event {
+                ct_event!(keycode press Enter) => {
+                    Control::Close((state.on_ok)(state.input.value()))
+                }
+                ct_event!(keycode press Esc) => Control::Close(MDEvent::NoOp),
+                ct_event!(keycode press Up) => {
+                    state.history_prev();
+                    Control::Changed
+                }
+                ct_event!(keycode press Down) => {
+                    state.history_next();
+                    Control::Changed
+                }
+                _ => Control::Continue,
+            });
+            try_flow!(state.input.handle(event, Regular));
+            Ok(Control::Unchanged)
+        }
+        _ => Ok(Control::Continue),
+    }
+}
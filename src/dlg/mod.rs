@@ -1,3 +1,6 @@
+pub mod choice_dlg;
 pub mod config_dlg;
+pub mod dirty_dlg;
 pub mod file_dlg;
+pub mod input_dlg;
 pub mod msg_dialog;
@@ -1,19 +1,74 @@
+use crate::cfg::{FormatMode, TableAlign};
+use crate::completion;
+use crate::highlight;
+use pulldown_cmark::{Event, HeadingLevel, LinkType, Options, Parser, Tag};
 use rat_markdown::dump::md_dump;
 use rat_markdown::op::md_format;
-use rat_markdown::styles::parse_md_styles;
+use rat_markdown::styles::MDStyle;
 use rat_widget::event::TextOutcome;
+use rat_widget::text::TextRange;
 use rat_widget::textarea::TextAreaState;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+/// Target style for link normalization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStyle {
+    /// `[text](url)`
+    Inline,
+    /// `[text][label]` + `[label]: url`
+    Reference,
+}
+
+/// Target form for the paths of local links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPaths {
+    /// `../img/a.png`
+    Relative,
+    /// `/img/a.png` from the project root.
+    RootRelative,
+}
+
+/// Fenced or indented code block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Byte range of the complete block.
+    pub range: Range<usize>,
+    /// Byte range of the content without the fences.
+    pub content: Range<usize>,
+    /// Info-string language. Empty for indented blocks.
+    pub lang: String,
+}
 
 /// Do some doc-type variation of the editors behaviour.
 pub trait DocType {
-    /// Parse document.
-    fn parse(&self, txt: &mut TextAreaState);
+    /// Parse document. Styles of extensions not in options are dropped.
+    fn parse(&self, txt: &mut TextAreaState, options: Options);
+
+    /// Format document. Table columns without alignment get table_align.
+    fn format(
+        &self,
+        txt: &mut TextAreaState,
+        width: u16,
+        mode: FormatMode,
+        table_align: TableAlign,
+    ) -> TextOutcome;
 
-    /// Format document
-    fn format(&self, txt: &mut TextAreaState, width: u16, table_eq_width: bool) -> TextOutcome;
+    /// Set the alignment of all columns of the table at the cursor.
+    fn align_table(&self, txt: &mut TextAreaState, align: TableAlign) -> TextOutcome;
 
     /// Dump parser debug info to log.
     fn log_parser(&self, txt: &TextAreaState);
+
+    /// Code block at the given byte position.
+    fn code_block_at(&self, txt: &TextAreaState, byte_pos: usize) -> Option<CodeBlock>;
+
+    /// All code blocks.
+    fn code_blocks(&self, txt: &TextAreaState) -> Vec<CodeBlock>;
+
+    /// Convert all links to the given style.
+    fn normalize_links(&self, txt: &mut TextAreaState, style: LinkStyle) -> TextOutcome;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -22,20 +77,44 @@ pub enum DocTypes {
     TXT,
 }
 
+impl DocTypes {
+    /// Default file extension.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocTypes::MD => "md",
+            DocTypes::TXT => "txt",
+        }
+    }
+}
+
 impl DocType for DocTypes {
     #[inline]
-    fn parse(&self, txt: &mut TextAreaState) {
+    fn parse(&self, txt: &mut TextAreaState, options: Options) {
         match self {
-            DocTypes::MD => DocTypeMD.parse(txt),
-            DocTypes::TXT => DocTypeTXT.parse(txt),
+            DocTypes::MD => DocTypeMD.parse(txt, options),
+            DocTypes::TXT => DocTypeTXT.parse(txt, options),
         }
     }
 
     #[inline]
-    fn format(&self, txt: &mut TextAreaState, width: u16, table_eq_width: bool) -> TextOutcome {
+    fn format(
+        &self,
+        txt: &mut TextAreaState,
+        width: u16,
+        mode: FormatMode,
+        table_align: TableAlign,
+    ) -> TextOutcome {
         match self {
-            DocTypes::MD => DocTypeMD.format(txt, width, table_eq_width),
-            DocTypes::TXT => DocTypeTXT.format(txt, width, table_eq_width),
+            DocTypes::MD => DocTypeMD.format(txt, width, mode, table_align),
+            DocTypes::TXT => DocTypeTXT.format(txt, width, mode, table_align),
+        }
+    }
+
+    #[inline]
+    fn align_table(&self, txt: &mut TextAreaState, align: TableAlign) -> TextOutcome {
+        match self {
+            DocTypes::MD => DocTypeMD.align_table(txt, align),
+            DocTypes::TXT => DocTypeTXT.align_table(txt, align),
         }
     }
 
@@ -46,33 +125,774 @@ impl DocType for DocTypes {
             DocTypes::TXT => DocTypeTXT.log_parser(txt),
         }
     }
+
+    #[inline]
+    fn code_block_at(&self, txt: &TextAreaState, byte_pos: usize) -> Option<CodeBlock> {
+        match self {
+            DocTypes::MD => DocTypeMD.code_block_at(txt, byte_pos),
+            DocTypes::TXT => DocTypeTXT.code_block_at(txt, byte_pos),
+        }
+    }
+
+    #[inline]
+    fn code_blocks(&self, txt: &TextAreaState) -> Vec<CodeBlock> {
+        match self {
+            DocTypes::MD => DocTypeMD.code_blocks(txt),
+            DocTypes::TXT => DocTypeTXT.code_blocks(txt),
+        }
+    }
+
+    #[inline]
+    fn normalize_links(&self, txt: &mut TextAreaState, style: LinkStyle) -> TextOutcome {
+        match self {
+            DocTypes::MD => DocTypeMD.normalize_links(txt, style),
+            DocTypes::TXT => DocTypeTXT.normalize_links(txt, style),
+        }
+    }
 }
 
 struct DocTypeMD;
 
 impl DocType for DocTypeMD {
-    fn parse(&self, txt: &mut TextAreaState) {
-        let styles = parse_md_styles(&txt.text());
-        txt.set_styles(styles);
+    fn parse(&self, txt: &mut TextAreaState, options: Options) {
+        txt.set_styles(md_styles(&txt.text(), options));
+
+        // highlighting as extra layer over the code blocks.
+        for block in self.code_blocks(txt) {
+            let code = txt.str_slice_byte(block.content.clone()).to_string();
+            for (range, style) in highlight::highlight(&block.lang, &code) {
+                txt.add_style(
+                    block.content.start + range.start..block.content.start + range.end,
+                    style,
+                );
+            }
+        }
     }
 
-    fn format(&self, txt: &mut TextAreaState, width: u16, table_eq_width: bool) -> TextOutcome {
-        md_format(txt, width as usize, table_eq_width)
+    fn format(
+        &self,
+        txt: &mut TextAreaState,
+        width: u16,
+        mode: FormatMode,
+        table_align: TableAlign,
+    ) -> TextOutcome {
+        // aligning and formatting are one undo step.
+        txt.begin_undo_seq();
+        let mut aligned = false;
+        if table_align != TableAlign::None {
+            let pos = txt.byte_at(txt.cursor());
+            if let Some(range) = txt.styles_at_match(pos.start, MDStyle::Table.into()) {
+                if let Some(replace) = align_row(txt, range, table_align, true) {
+                    replace_byte_ranges(txt, vec![replace]);
+                    aligned = true;
+                }
+            }
+        }
+
+        let r = match mode {
+            FormatMode::Reflow => md_format(txt, width as usize, false),
+            FormatMode::ReflowEqWidth => md_format(txt, width as usize, true),
+            FormatMode::KeepBreaks => {
+                let pos = txt.byte_at(txt.cursor());
+                if txt
+                    .styles_at_match(pos.start, MDStyle::Table.into())
+                    .is_some()
+                {
+                    md_format(txt, width as usize, false)
+                } else {
+                    normalize_spacing(txt)
+                }
+            }
+        };
+        txt.end_undo_seq();
+
+        if aligned {
+            TextOutcome::TextChanged
+        } else {
+            r
+        }
+    }
+
+    fn align_table(&self, txt: &mut TextAreaState, align: TableAlign) -> TextOutcome {
+        let pos = txt.byte_at(txt.cursor());
+        let Some(range) = txt.styles_at_match(pos.start, MDStyle::Table.into()) else {
+            return TextOutcome::Continue;
+        };
+        let Some(replace) = align_row(txt, range, align, false) else {
+            return TextOutcome::Unchanged;
+        };
+        replace_byte_ranges(txt, vec![replace]);
+        TextOutcome::TextChanged
     }
 
     fn log_parser(&self, txt: &TextAreaState) {
         md_dump(txt);
     }
+
+    fn code_block_at(&self, txt: &TextAreaState, byte_pos: usize) -> Option<CodeBlock> {
+        let range = txt.styles_at_match(byte_pos, MDStyle::CodeBlock.into())?;
+        Some(code_block(txt, range))
+    }
+
+    fn code_blocks(&self, txt: &TextAreaState) -> Vec<CodeBlock> {
+        let code_style: usize = MDStyle::CodeBlock.into();
+        let mut blocks = txt
+            .styles()
+            .filter(|(_, style)| *style == code_style)
+            .map(|(range, _)| code_block(txt, range))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|v| v.range.start);
+        blocks
+    }
+
+    fn normalize_links(&self, txt: &mut TextAreaState, style: LinkStyle) -> TextOutcome {
+        let text = txt.text();
+        let replace = match style {
+            LinkStyle::Inline => links_to_inline(&text),
+            LinkStyle::Reference => links_to_reference(&text, txt.newline()),
+        };
+        if replace.is_empty() {
+            return TextOutcome::Unchanged;
+        }
+        replace_byte_ranges(txt, replace);
+        TextOutcome::TextChanged
+    }
+}
+
+/// Rewrite the delimiter row of the table. With only_default
+/// columns that already have an alignment are kept.
+fn align_row(
+    txt: &TextAreaState,
+    table: Range<usize>,
+    align: TableAlign,
+    only_default: bool,
+) -> Option<(Range<usize>, String)> {
+    let text = txt.str_slice_byte(table.clone());
+
+    // second line
+    let start = text.find('\n')? + 1;
+    let end = text[start..]
+        .find('\n')
+        .map(|v| start + v)
+        .unwrap_or(text.len());
+    let row = text[start..end].trim_end_matches('\r');
+
+    let trimmed = row.trim();
+    let lead = trimmed.starts_with('|');
+    let trail = trimmed.len() > 1 && trimmed.ends_with('|');
+    let inner = trimmed.trim_start_matches('|').trim_end_matches('|');
+
+    let mut cells = Vec::new();
+    for cell in inner.split('|') {
+        let cell = cell.trim();
+        if only_default && cell.contains(':') {
+            cells.push(cell.to_string());
+            continue;
+        }
+        let dashes = cell.chars().filter(|c| *c == '-').count().max(3);
+        cells.push(match align {
+            TableAlign::None => "-".repeat(dashes),
+            TableAlign::Left => format!(":{}", "-".repeat(dashes)),
+            TableAlign::Center => format!(":{}:", "-".repeat(dashes)),
+            TableAlign::Right => format!("{}:", "-".repeat(dashes)),
+        });
+    }
+
+    let mut new = row[..row.len() - row.trim_start().len()].to_string();
+    if lead {
+        new.push('|');
+    }
+    new.push_str(cells.join("|").as_str());
+    if trail {
+        new.push('|');
+    }
+
+    if new == row {
+        None
+    } else {
+        Some((table.start + start..table.start + start + row.len(), new))
+    }
+}
+
+/// Normalize the spacing of the selection or the paragraph
+/// at the cursor. Line breaks stay where they are.
+/// Code blocks and tables are left alone, code spans keep
+/// their spacing.
+fn normalize_spacing(txt: &mut TextAreaState) -> TextOutcome {
+    let range = if txt.has_selection() {
+        txt.bytes_at_range(txt.selection())
+    } else {
+        let pos = txt.byte_at(txt.cursor());
+        match txt.styles_at_match(pos.start, MDStyle::Paragraph.into()) {
+            Some(range) => range,
+            None => return TextOutcome::Continue,
+        }
+    };
+
+    let block_style: [usize; 2] = [MDStyle::CodeBlock.into(), MDStyle::Table.into()];
+    let code_style: usize = MDStyle::CodeInline.into();
+    let mut blocks = Vec::new();
+    let mut code = Vec::new();
+    for (r, style) in txt.styles() {
+        if r.start >= range.end || r.end <= range.start {
+            continue;
+        }
+        if block_style.contains(&style) {
+            blocks.push(r);
+        } else if style == code_style {
+            code.push(r);
+        }
+    }
+
+    let text = txt.str_slice_byte(range.clone()).to_string();
+    let n_lines = text.lines().count();
+
+    let mut replace = Vec::new();
+    let mut line_start = range.start;
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let line_range = line_start..line_start + content.len();
+        if !blocks
+            .iter()
+            .any(|r| r.start < line_range.end && r.end > line_range.start)
+        {
+            let line_code = code
+                .iter()
+                .filter(|r| r.start < line_range.end && r.end > line_range.start)
+                .map(|r| r.start.saturating_sub(line_start)..r.end - line_start)
+                .collect::<Vec<_>>();
+            let new = normalize_line(content, idx + 1 < n_lines, &line_code);
+            if new != content {
+                replace.push((line_range, new));
+            }
+        }
+        line_start += line.len();
+    }
+
+    if replace.is_empty() {
+        return TextOutcome::Unchanged;
+    }
+    replace_byte_ranges(txt, replace);
+    TextOutcome::TextChanged
+}
+
+/// Collapse inner whitespace, keep the indent and a
+/// trailing hard break. Whitespace inside the code
+/// ranges is kept as is.
+fn normalize_line(line: &str, inner: bool, code: &[Range<usize>]) -> String {
+    let in_code = |pos: usize| code.iter().any(|r| r.contains(&pos));
+
+    let body_start = line.len() - line.trim_start().len();
+    let mut body_end = line.trim_end().len().max(body_start);
+    if (body_end..line.len()).any(in_code) {
+        body_end = line.len();
+    }
+    let body = &line[body_start..body_end];
+    let indent = &line[..body_start];
+
+    let hard_break = inner && body_end < line.len() && line.ends_with("  ");
+
+    let mut new = indent.to_string();
+    let mut space = false;
+    for (idx, c) in body.char_indices() {
+        if c.is_whitespace() && !in_code(body_start + idx) {
+            space = true;
+            continue;
+        }
+        if space {
+            new.push(' ');
+            space = false;
+        }
+        new.push(c);
+    }
+    if hard_break {
+        new.push_str("  ");
+    }
+    new
+}
+
+/// Replace byte-ranges in the text as one undo step.
+/// The ranges must not overlap.
+pub fn replace_byte_ranges(txt: &mut TextAreaState, mut replace: Vec<(Range<usize>, String)>) {
+    let cursor = txt.cursor();
+
+    replace.sort_by_key(|(range, _)| range.start);
+    txt.begin_undo_seq();
+    for (range, new) in replace.into_iter().rev() {
+        let start = txt.byte_pos(range.start);
+        let end = txt.byte_pos(range.end);
+        if start != end {
+            txt.delete_range(TextRange::new(start, end));
+        }
+        txt.set_cursor(start, false);
+        txt.insert_str(new);
+    }
+    txt.end_undo_seq();
+
+    txt.set_cursor(cursor, false);
+}
+
+/// Same as [replace_byte_ranges] for a plain string.
+pub fn replace_str_ranges(text: &str, mut replace: Vec<(Range<usize>, String)>) -> String {
+    replace.sort_by_key(|(range, _)| range.start);
+    let mut text = text.to_string();
+    for (range, new) in replace.into_iter().rev() {
+        text.replace_range(range, new.as_str());
+    }
+    text
+}
+
+/// Markdown styles for the text, parsed with the enabled
+/// extensions. Text of a disabled extension gets the style
+/// the parser gives it without, e.g. paragraph.
+fn md_styles(text: &str, options: Options) -> Vec<(Range<usize>, usize)> {
+    let mut styles: Vec<(Range<usize>, usize)> = Vec::new();
+    let mut push = |range: Range<usize>, style: MDStyle| styles.push((range, style.into()));
+
+    let parser = Parser::new_ext(text, options).into_offset_iter();
+    let link_defs = parser
+        .reference_definitions()
+        .iter()
+        .map(|(_, v)| v.span.clone())
+        .collect::<Vec<_>>();
+    for (event, range) in parser {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Paragraph => push(range, MDStyle::Paragraph),
+                Tag::Heading { level, .. } => push(
+                    range,
+                    match level {
+                        HeadingLevel::H1 => MDStyle::Heading1,
+                        HeadingLevel::H2 => MDStyle::Heading2,
+                        HeadingLevel::H3 => MDStyle::Heading3,
+                        HeadingLevel::H4 => MDStyle::Heading4,
+                        HeadingLevel::H5 => MDStyle::Heading5,
+                        HeadingLevel::H6 => MDStyle::Heading6,
+                    },
+                ),
+                Tag::BlockQuote(_) => push(range, MDStyle::BlockQuote),
+                Tag::CodeBlock(_) => push(range, MDStyle::CodeBlock),
+                Tag::HtmlBlock => push(range, MDStyle::Html),
+                Tag::List(_) => push(range, MDStyle::List),
+                Tag::Item => {
+                    let tag = item_tag(text, range.clone());
+                    push(range, MDStyle::Item);
+                    push(tag, MDStyle::ItemTag);
+                }
+                Tag::FootnoteDefinition(_) => push(range, MDStyle::FootnoteDefinition),
+                Tag::DefinitionList => push(range, MDStyle::DefinitionList),
+                Tag::DefinitionListTitle => push(range, MDStyle::DefinitionListTitle),
+                Tag::DefinitionListDefinition => push(range, MDStyle::DefinitionListDefinition),
+                Tag::Table(_) => push(range, MDStyle::Table),
+                Tag::TableHead => push(range, MDStyle::TableHead),
+                Tag::TableRow => push(range, MDStyle::TableRow),
+                Tag::TableCell => push(range, MDStyle::TableCell),
+                Tag::Emphasis => push(range, MDStyle::Emphasis),
+                Tag::Strong => push(range, MDStyle::Strong),
+                Tag::Strikethrough => push(range, MDStyle::Strikethrough),
+                Tag::Link { .. } => push(range, MDStyle::Link),
+                Tag::Image { .. } => push(range, MDStyle::Image),
+                Tag::MetadataBlock(_) => push(range, MDStyle::MetadataBlock),
+                _ => {}
+            },
+            Event::Code(_) => push(range, MDStyle::CodeInline),
+            Event::InlineMath(_) => push(range, MDStyle::MathInline),
+            Event::DisplayMath(_) => push(range, MDStyle::MathDisplay),
+            Event::Html(_) | Event::InlineHtml(_) => push(range, MDStyle::Html),
+            Event::FootnoteReference(_) => push(range, MDStyle::FootnoteReference),
+            Event::Rule => push(range, MDStyle::Rule),
+            Event::TaskListMarker(_) => push(range, MDStyle::TaskListMarker),
+            _ => {}
+        }
+    }
+    for range in link_defs {
+        push(range, MDStyle::LinkDef);
+    }
+    styles
+}
+
+/// Bullet or number of the list item.
+fn item_tag(text: &str, item: Range<usize>) -> Range<usize> {
+    let src = &text[item.clone()];
+    let start = src.len() - src.trim_start().len();
+    let end = src[start..]
+        .find(char::is_whitespace)
+        .map(|v| start + v)
+        .unwrap_or(src.len());
+    item.start + start..item.start + end
+}
+
+/// Link destination + optional title.
+fn link_target(dest: &str, title: &str) -> String {
+    let mut target = if dest.contains([' ', '(', ')']) {
+        format!("<{}>", dest)
+    } else {
+        dest.to_string()
+    };
+    if !title.is_empty() {
+        target.push_str(format!(" \"{}\"", title.replace('"', "\\\"")).as_str());
+    }
+    target
+}
+
+/// Text part of the link source.
+fn link_text(src: &str, link_type: LinkType) -> &str {
+    let end = match link_type {
+        LinkType::Inline => src.rfind("]("),
+        LinkType::Reference | LinkType::ReferenceUnknown => src.rfind("]["),
+        LinkType::Collapsed | LinkType::CollapsedUnknown => src.rfind("[]"),
+        _ => src.rfind(']'),
+    };
+    src.get(1..end.unwrap_or(1)).unwrap_or_default()
+}
+
+/// Rewrite inline links as reference links. Duplicate urls share
+/// one definition, existing definitions are reused.
+fn links_to_reference(text: &str, newline: &str) -> Vec<(Range<usize>, String)> {
+    let parser = Parser::new_ext(text, Options::all());
+
+    // existing definitions
+    let mut labels = HashMap::new();
+    let mut next_label = 1;
+    for (label, def) in parser.reference_definitions().iter() {
+        let key = (
+            def.dest.to_string(),
+            def.title.as_deref().unwrap_or("").to_string(),
+        );
+        labels.entry(key).or_insert(label.to_string());
+        if let Ok(n) = label.parse::<u32>() {
+            next_label = next_label.max(n + 1);
+        }
+    }
+
+    let mut replace = Vec::new();
+    let mut defs = String::new();
+    for (e, range) in parser.into_offset_iter() {
+        if let Event::Start(Tag::Link {
+            link_type: LinkType::Inline,
+            dest_url,
+            title,
+            ..
+        }) = e
+        {
+            let key = (dest_url.to_string(), title.to_string());
+            let label = if let Some(label) = labels.get(&key) {
+                label.clone()
+            } else {
+                let label = next_label.to_string();
+                next_label += 1;
+                defs.push_str(format!("[{}]: {}", label, link_target(&key.0, &key.1)).as_str());
+                defs.push_str(newline);
+                labels.insert(key, label.clone());
+                label
+            };
+
+            let link_txt = link_text(&text[range.clone()], LinkType::Inline);
+            replace.push((range, format!("[{}][{}]", link_txt, label)));
+        }
+    }
+
+    if !defs.is_empty() {
+        let mut append = String::new();
+        if !text.ends_with('\n') {
+            append.push_str(newline);
+        }
+        append.push_str(newline);
+        append.push_str(&defs);
+        replace.push((text.len()..text.len(), append));
+    }
+
+    replace
+}
+
+/// Rewrite reference links as inline links. Removes the
+/// definitions that have been used, unless an image still
+/// refers to them.
+fn links_to_inline(text: &str) -> Vec<(Range<usize>, String)> {
+    let parser = Parser::new_ext(text, Options::all());
+
+    let defs = parser
+        .reference_definitions()
+        .iter()
+        .map(|(_, def)| (def.dest.to_string(), def.span.clone()))
+        .collect::<Vec<_>>();
+
+    let mut replace = Vec::new();
+    let mut used = Vec::new();
+    let mut images = Vec::new();
+    for (e, range) in parser.into_offset_iter() {
+        match e {
+            Event::Start(Tag::Link {
+                link_type:
+                    link_type @ (LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut),
+                dest_url,
+                title,
+                ..
+            }) => {
+                let link_txt = link_text(&text[range.clone()], link_type);
+                replace.push((
+                    range,
+                    format!("[{}]({})", link_txt, link_target(&dest_url, &title)),
+                ));
+                used.push(dest_url.to_string());
+            }
+            Event::Start(Tag::Image {
+                link_type: LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut,
+                dest_url,
+                ..
+            }) => {
+                images.push(dest_url.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    for (dest, span) in defs {
+        if used.contains(&dest) && !images.contains(&dest) {
+            // remove with line break
+            let mut end = span.end;
+            if text[end..].starts_with("\r\n") {
+                end += 2;
+            } else if text[end..].starts_with('\n') {
+                end += 1;
+            }
+            replace.push((span.start..end, String::new()));
+        }
+    }
+
+    replace
+}
+
+/// Rewrite the paths of local links and images.
+///
+/// `dir` is the directory of the document, `root` the project
+/// root, both absolute. External links and anchors stay as they are.
+pub fn convert_link_paths(
+    text: &str,
+    options: Options,
+    dir: &Path,
+    root: &Path,
+    mode: LinkPaths,
+) -> Vec<(Range<usize>, String)> {
+    let mut replace = Vec::new();
+    for (range, dest) in link_dests(text, options) {
+        let Some((target, fragment)) = resolve_link(&dest, dir, root) else {
+            continue;
+        };
+        let Some(new) = link_path(&target, dir, root, mode) else {
+            continue;
+        };
+        let new = format!("{}{}", new, fragment);
+        if new != dest {
+            replace.push((range, new));
+        }
+    }
+    replace
+}
+
+/// Rewrite the local links and images that point to `old`
+/// so they point to `new`. Root-relative links stay root-relative.
+///
+/// All paths are absolute, see [convert_link_paths].
+pub fn retarget_links(
+    text: &str,
+    options: Options,
+    dir: &Path,
+    root: &Path,
+    old: &Path,
+    new: &Path,
+) -> Vec<(Range<usize>, String)> {
+    let mut replace = Vec::new();
+    for (range, dest) in link_dests(text, options) {
+        let Some((target, fragment)) = resolve_link(&dest, dir, root) else {
+            continue;
+        };
+        if target != old {
+            continue;
+        }
+        let mode = if dest.starts_with('/') {
+            LinkPaths::RootRelative
+        } else {
+            LinkPaths::Relative
+        };
+        let Some(new) = link_path(new, dir, root, mode) else {
+            continue;
+        };
+        replace.push((range, format!("{}{}", new, fragment)));
+    }
+    replace
+}
+
+/// Byte ranges and text of the destinations of all inline
+/// links, images and reference definitions.
+fn link_dests(text: &str, options: Options) -> Vec<(Range<usize>, String)> {
+    let parser = Parser::new_ext(text, options);
+
+    // span, destination, is a definition
+    let mut dests = parser
+        .reference_definitions()
+        .iter()
+        .map(|(_, def)| (def.span.clone(), def.dest.to_string(), true))
+        .collect::<Vec<_>>();
+    for (e, range) in parser.into_offset_iter() {
+        match e {
+            Event::Start(Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            })
+            | Event::Start(Tag::Image {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            }) => {
+                dests.push((range, dest_url.to_string(), false));
+            }
+            _ => {}
+        }
+    }
+
+    let mut res = Vec::new();
+    for (range, dest, is_def) in dests {
+        if dest.is_empty() {
+            continue;
+        }
+        // the destination follows the link text or label.
+        let src = &text[range.clone()];
+        let after = if is_def {
+            src.find("]:")
+        } else {
+            src.rfind("](")
+        };
+        let after = after.map(|v| v + 2).unwrap_or(0);
+        let Some(pos) = src[after..].find(dest.as_str()) else {
+            continue;
+        };
+        let start = range.start + after + pos;
+        res.push((start..start + dest.len(), dest));
+    }
+    res
+}
+
+/// Absolute target and '#fragment' of a local link.
+/// None for external links and anchors.
+fn resolve_link<'a>(dest: &'a str, dir: &Path, root: &Path) -> Option<(PathBuf, &'a str)> {
+    if dest.is_empty()
+        || dest.starts_with('#')
+        || dest.starts_with("//")
+        || dest.contains("://")
+        || dest.starts_with("mailto:")
+    {
+        return None;
+    }
+    let (path, fragment) = dest.split_at(dest.find('#').unwrap_or(dest.len()));
+
+    let target = if let Some(rel) = path.strip_prefix('/') {
+        // could be an absolute path too
+        if Path::new(path).starts_with(root) {
+            PathBuf::from(path)
+        } else {
+            root.join(rel)
+        }
+    } else {
+        dir.join(path)
+    };
+    Some((normalize_path(&target), fragment))
+}
+
+/// Link path for the target.
+fn link_path(target: &Path, dir: &Path, root: &Path, mode: LinkPaths) -> Option<String> {
+    let new = match mode {
+        LinkPaths::Relative => completion::relative_path(dir, target)
+            .to_string_lossy()
+            .replace('\\', "/"),
+        LinkPaths::RootRelative => format!(
+            "/{}",
+            target
+                .strip_prefix(root)
+                .ok()?
+                .to_string_lossy()
+                .replace('\\', "/")
+        ),
+    };
+    if new.is_empty() || new == "/" {
+        return None;
+    }
+    Some(new)
+}
+
+/// Resolve '.' and '..' without touching the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Split a code block into fences and content.
+fn code_block(txt: &TextAreaState, range: Range<usize>) -> CodeBlock {
+    let block = txt.str_slice_byte(range.clone());
+
+    let first_len = block.find('\n').map(|v| v + 1).unwrap_or(block.len());
+    let first = block[..first_len].trim();
+    let fence = if first.starts_with("```") {
+        Some('`')
+    } else if first.starts_with("~~~") {
+        Some('~')
+    } else {
+        None
+    };
+
+    if let Some(fence) = fence {
+        let lang = first
+            .trim_start_matches(fence)
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        let body = block[first_len..].trim_end_matches(['\r', '\n']);
+        let last_start = body.rfind('\n').map(|v| v + 1).unwrap_or(0);
+        let content_end = if body[last_start..].trim().starts_with(fence) {
+            first_len + last_start
+        } else {
+            block.len()
+        };
+
+        CodeBlock {
+            range: range.clone(),
+            content: range.start + first_len..range.start + content_end.max(first_len),
+            lang,
+        }
+    } else {
+        CodeBlock {
+            range: range.clone(),
+            content: range,
+            lang: Default::default(),
+        }
+    }
 }
 
 struct DocTypeTXT;
 
 impl DocType for DocTypeTXT {
-    fn parse(&self, _: &mut TextAreaState) {
+    fn parse(&self, _: &mut TextAreaState, _: Options) {
+        // noop
+    }
+
+    fn format(&self, _: &mut TextAreaState, _: u16, _: FormatMode, _: TableAlign) -> TextOutcome {
         // noop
+        TextOutcome::Continue
     }
 
-    fn format(&self, _: &mut TextAreaState, _: u16, _: bool) -> TextOutcome {
+    fn align_table(&self, _: &mut TextAreaState, _: TableAlign) -> TextOutcome {
         // noop
         TextOutcome::Continue
     }
@@ -80,4 +900,17 @@ impl DocType for DocTypeTXT {
     fn log_parser(&self, _: &TextAreaState) {
         // noop
     }
+
+    fn code_block_at(&self, _: &TextAreaState, _: usize) -> Option<CodeBlock> {
+        None
+    }
+
+    fn code_blocks(&self, _: &TextAreaState) -> Vec<CodeBlock> {
+        Vec::new()
+    }
+
+    fn normalize_links(&self, _: &mut TextAreaState, _: LinkStyle) -> TextOutcome {
+        // noop
+        TextOutcome::Continue
+    }
 }
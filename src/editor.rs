@@ -1,9 +1,20 @@
+use crate::completion;
+use crate::dlg::choice_dlg;
+use crate::dlg::choice_dlg::ChoiceDialogState;
+use crate::dlg::dirty_dlg;
+use crate::dlg::dirty_dlg::DirtyDialogState;
+use crate::dlg::input_dlg;
+use crate::dlg::input_dlg::InputDialogState;
+use crate::doc_type;
+use crate::editor_file;
 use crate::editor_file::MDFileState;
 use crate::file_list::FileListState;
 use crate::fsys::FileSysStructure;
 use crate::global::event::{MDEvent, MDImmediate};
 use crate::global::GlobalState;
 use crate::rat_salsa::{Control, SalsaContext};
+use crate::sample;
+use crate::search;
 use crate::split_tab::SplitTabState;
 use crate::{file_list, split_tab};
 use anyhow::Error;
@@ -14,8 +25,15 @@ use rat_widget::splitter::{ResizeConstraint, Split, SplitState, SplitType};
 use ratatui::buffer::Buffer;
 use ratatui::layout::{Constraint, Rect};
 use ratatui::widgets::StatefulWidget;
+use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Ask before opening more files for a glob.
+const MAX_GLOB_FILES: usize = 20;
+
+/// Max files listed when asking to rewrite links.
+const MAX_LISTED_FILES: usize = 12;
+
 #[derive(Debug, Default)]
 pub struct MDEditState {
     pub window_cmd: bool,
@@ -24,6 +42,9 @@ pub struct MDEditState {
     pub split_files: SplitState,
     pub file_list: FileListState,
     pub split_tab: SplitTabState,
+
+    /// File waiting for its new name.
+    pub rename_from: Option<PathBuf>,
 }
 
 pub fn render(
@@ -98,23 +119,92 @@ pub fn event(
                 }
             }
             MDEvent::New(p) => state.new(p, ctx)?,
+            MDEvent::GenerateSample(n) => state.generate_sample(*n, ctx)?,
             MDEvent::SelectOrOpen(p) => state.select_or_open(p, ctx)?,
             MDEvent::SelectOrOpenSplit(p) => state.select_or_open_split(p, ctx)?,
             MDEvent::Open(p) => state.open(p, ctx)?,
+            MDEvent::OpenLarge(p) => state.open_confirmed(p, ctx)?,
+            MDEvent::OpenLargeSplit(p) => state.open_split_confirmed(p, ctx)?,
+            MDEvent::OpenGlob(p) => state.open_glob(p, ctx)?,
+            MDEvent::OpenFiles(p) => state.open_files(p, ctx)?,
             MDEvent::Save => {
                 sync_files = true;
                 state.save(ctx)?
             }
             MDEvent::SaveAs(p) => state.save_as(p, ctx)?,
+            MDEvent::SaveFile(p) => {
+                sync_files = true;
+                state.save_file(p, ctx)?
+            }
+            MDEvent::SaveFiles(p) => {
+                sync_files = true;
+                for p in p {
+                    state.save_file(p, ctx)?;
+                }
+                Control::Changed
+            }
+            MDEvent::MenuSaveAll => state.show_dirty(ctx)?,
+            MDEvent::SaveCreateDir(p) => {
+                sync_files = true;
+                if let Some(parent) = editor_file::missing_parent(p) {
+                    fs::create_dir_all(parent)?;
+                }
+                state.save_file(p, ctx)?
+            }
+            MDEvent::SaveFailed(p, msg) => state.save_failed(p, msg, ctx)?,
+            MDEvent::DeleteFile(p) => {
+                sync_files = true;
+                state.delete_file(p, false, ctx)?
+            }
+            MDEvent::DeleteFilePermanently(p) => {
+                sync_files = true;
+                state.delete_file(p, true, ctx)?
+            }
+            MDEvent::MenuRenameFile(p) => state.show_rename(p, ctx)?,
+            MDEvent::RenameFile(name) => {
+                sync_files = true;
+                state.rename_file(name, ctx)?
+            }
+            MDEvent::RenamedLinks(old, new, files) => {
+                state.confirm_rewrite_links(old, new, files, ctx)?
+            }
+            MDEvent::RewriteLinks(old, new, files) => state.rewrite_links(old, new, files, ctx)?,
             MDEvent::Close => state.close_selected_tab(ctx)?,
             MDEvent::CloseAll => state.close_all(ctx)?,
+            MDEvent::CloseOutsideRoot => state.close_outside_root(ctx)?,
+            MDEvent::TogglePin => state.toggle_pin(ctx)?,
+            MDEvent::PinFileList => {
+                sync_files = true;
+                state.toggle_split_dir(ctx)?
+            }
             MDEvent::CloseAt(idx_split, idx_tab) => {
                 state.close_tab_at(*idx_split, *idx_tab, ctx)?
             }
             MDEvent::SelectAt(idx_split, idx_tab) => {
                 state.select_tab_at(*idx_split, *idx_tab, ctx)?
             }
+            MDEvent::SelectLastTab => state.select_last_tab(ctx)?,
             MDEvent::Split => state.split(ctx)?,
+            MDEvent::SwapSplit => {
+                if state.split_tab.swap_split(ctx) {
+                    ctx.queue(Control::Event(MDEvent::StoreConfig));
+                    Control::Changed
+                } else {
+                    Control::Continue
+                }
+            }
+            MDEvent::ScrollSync => {
+                state.split_tab.scroll_sync = !state.split_tab.scroll_sync;
+                ctx.queue(Control::Event(MDEvent::Info(
+                    if state.split_tab.scroll_sync {
+                        "scroll sync on"
+                    } else {
+                        "scroll sync off"
+                    }
+                    .into(),
+                )));
+                Control::Changed
+            }
             MDEvent::JumpToTree => state.jump_to_tree(ctx)?,
             MDEvent::JumpToFiles => state.jump_to_file(ctx)?,
             MDEvent::JumpToTabs => state.jump_to_tabs(ctx)?,
@@ -134,6 +224,7 @@ pub fn event(
                 state.jump_to_file(ctx)?
             }
             MDEvent::FileSysReloaded(fs) => {
+                ctx.link_targets = None;
                 state.file_list.replace_fs(fs.take());
                 file_list::init(&mut state.file_list, ctx)?;
                 if !state.split_files.is_hidden(0) {
@@ -142,6 +233,16 @@ pub fn event(
                     Control::Continue
                 }
             }
+            MDEvent::Search(term) => {
+                // the editors do the search.
+                search::push_history(&mut ctx.search_history, term);
+                Control::Continue
+            }
+            MDEvent::LinkTargets(root, targets) => {
+                // cache and pass on to the waiting editor.
+                ctx.link_targets = Some((root.clone(), targets.take()));
+                Control::Continue
+            }
             _ => Control::Continue,
         };
         break_flow!('f: rr);
@@ -186,27 +287,199 @@ impl MDEditState {
         let new = MDFileState::new_file(&path, ctx);
         self.split_tab.open(pos, new, ctx);
         self.split_tab.select(pos, ctx);
+        self.split_tab.evict(ctx)?;
         self.split_tab.focus_selected(ctx);
 
         Ok(Control::Changed)
     }
 
-    // Open path.
+    // New file with a generated test document. It starts
+    // unchanged, so it is only written after an edit. An
+    // existing file is never replaced.
+    pub fn generate_sample(
+        &mut self,
+        sections: usize,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let path = self
+            .file_list
+            .current_dir()
+            .join(format!("sample-{}.md", sections));
+        if path.exists() {
+            return Ok(Control::Event(MDEvent::Message(format!(
+                "{} exists already.",
+                path.display()
+            ))));
+        }
+        _ = self.new(&path, ctx)?;
+
+        if let Some((_, md)) = self.split_tab.selected_mut() {
+            md.edit.set_text(sample::generate(sections).as_str());
+            _ = md.text_changed(ctx);
+            md.changed = false;
+        }
+        Ok(Control::Changed)
+    }
+
+    // Open path. Asks first for large files.
     pub fn open(&mut self, path: &Path, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        if self.confirm_large(path, false, ctx) {
+            return Ok(Control::Changed);
+        }
+        self.open_confirmed(path, ctx)
+    }
+
+    // Open path without asking.
+    pub fn open_confirmed(
+        &mut self,
+        path: &Path,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
         let pos = if let Some(pos) = self.split_tab.selected_pos() {
             (pos.0, pos.1 + 1)
         } else {
             (0, 0)
         };
 
-        self.open_in(pos, path, ctx)
+        let r = self.open_in(pos, path, ctx)?;
+        self.split_tab.evict(ctx)?;
+        self.split_tab.focus_selected(ctx);
+        Ok(r)
     }
 
-    // Open path as new split.
+    // Open all files matching the glob in the current split.
+    // Asks first if there are too many.
+    pub fn open_glob(
+        &mut self,
+        pattern: &str,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let mut paths = Vec::new();
+        for path in glob::glob(pattern)? {
+            let mut path = path?;
+            if !path.is_file() {
+                continue;
+            }
+            // need __some__ parent directory
+            if path.parent().is_none() || path.parent() == Some(&PathBuf::from("")) {
+                path = PathBuf::from(".").join(path);
+            }
+            paths.push(path);
+        }
+
+        if paths.is_empty() {
+            Ok(Control::Event(MDEvent::Info(format!(
+                "no files for {}",
+                pattern
+            ))))
+        } else if paths.len() > MAX_GLOB_FILES {
+            let state = ChoiceDialogState::new(
+                "Open files",
+                format!("Open {} files for {}?", paths.len(), pattern).as_str(),
+            )
+            .choice("Open", MDEvent::OpenFiles(paths))
+            .choice("Cancel", MDEvent::NoOp);
+            ctx.dialogs
+                .push(choice_dlg::render, choice_dlg::event, state);
+            Ok(Control::Changed)
+        } else {
+            self.open_files(&paths, ctx)
+        }
+    }
+
+    // Open the files as tabs after the selected one.
+    // The first of them is selected. Files that fail
+    // are reported together and skipped.
+    pub fn open_files(
+        &mut self,
+        paths: &[PathBuf],
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let first = if let Some(pos) = self.split_tab.selected_pos() {
+            (pos.0, pos.1 + 1)
+        } else {
+            (0, 0)
+        };
+
+        let mut n = 0;
+        let mut failed = Vec::new();
+        for path in paths {
+            if self.confirm_large(path, false, ctx) {
+                continue;
+            }
+            match self.open_in((first.0, first.1 + n), path, ctx) {
+                Ok(_) => n += 1,
+                Err(e) => failed.push(format!("{}: {:#}", path.display(), e)),
+            }
+        }
+        if n > 0 {
+            self.split_tab.select(first, ctx);
+            self.split_tab.evict(ctx)?;
+            self.split_tab.focus_selected(ctx);
+        }
+        if !failed.is_empty() {
+            ctx.queue_event(MDEvent::Message(format!(
+                "Can't open:\n{}",
+                failed.join("\n")
+            )));
+        }
+        Ok(Control::Changed)
+    }
+
+    // Asks before loading a file larger than large_file_threshold,
+    // unless it is already open. Returns true if it asks.
+    // The answer opens as tab or as new split.
+    fn confirm_large(&mut self, path: &Path, split: bool, ctx: &mut GlobalState) -> bool {
+        if self.split_tab.for_path(path).is_some() {
+            return false;
+        }
+        let Ok(meta) = fs::metadata(path) else {
+            return false;
+        };
+        if meta.len() <= ctx.cfg.large_file_threshold * 1024 * 1024 {
+            return false;
+        }
+
+        let state = ChoiceDialogState::new(
+            "Open file",
+            format!(
+                "{} has {} MiB and will be read-only. Open anyway?",
+                path.display(),
+                meta.len() / (1024 * 1024)
+            )
+            .as_str(),
+        )
+        .choice(
+            "Open",
+            if split {
+                MDEvent::OpenLargeSplit(path.into())
+            } else {
+                MDEvent::OpenLarge(path.into())
+            },
+        )
+        .choice("Cancel", MDEvent::NoOp);
+        ctx.dialogs
+            .push(choice_dlg::render, choice_dlg::event, state);
+        true
+    }
+
+    // Open path as new split. Asks first for large files.
     fn _open_split(
         &mut self,
         path: &Path,
         ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if self.confirm_large(path, true, ctx) {
+            return Ok(Control::Changed);
+        }
+        self.open_split_confirmed(path, ctx)
+    }
+
+    // Open path as new split without asking.
+    pub fn open_split_confirmed(
+        &mut self,
+        path: &Path,
+        ctx: &mut GlobalState,
     ) -> Result<Control<MDEvent>, Error> {
         let pos = if let Some(pos) = self.split_tab.selected_pos() {
             if pos.0 + 1 >= self.split_tab.split_tab_file.len() {
@@ -222,7 +495,10 @@ impl MDEditState {
             (0, 0)
         };
 
-        self.open_in(pos, path, ctx)
+        let r = self.open_in(pos, path, ctx)?;
+        self.split_tab.evict(ctx)?;
+        self.split_tab.focus_selected(ctx);
+        Ok(r)
     }
 
     /// Open in split/tab.
@@ -259,9 +535,14 @@ impl MDEditState {
         } else {
             None
         };
+        let pinned = self
+            .split_tab
+            .sel_split
+            .and_then(|v| self.split_tab.split_dir.get(v).cloned())
+            .flatten();
 
         Ok(if let Some(path) = path {
-            if let Some(parent) = path.parent() {
+            if let Some(parent) = pinned.as_deref().or(path.parent()) {
                 let root = FileSysStructure::find_root(parent);
                 let root = root.as_deref();
 
@@ -333,8 +614,74 @@ impl MDEditState {
     }
 
     // Save all.
-    pub fn save(&mut self, _ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
-        self.split_tab.save()?;
+    pub fn save(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        for (path, e) in self.split_tab.save(&ctx.cfg) {
+            ctx.queue_event(MDEvent::SaveFailed(path, format!("{:#}", e)));
+        }
+        Ok(Control::Changed)
+    }
+
+    // Save one file.
+    pub fn save_file(
+        &mut self,
+        path: &Path,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        match self.split_tab.save_file(path, &ctx.cfg) {
+            Some(Ok(())) => {
+                ctx.queue_event(MDEvent::Info(format!("Saved {}", path.display())));
+                Ok(Control::Changed)
+            }
+            Some(Err(e)) => {
+                ctx.queue_event(MDEvent::SaveFailed(path.into(), format!("{:#}", e)));
+                Ok(Control::Changed)
+            }
+            None => Ok(Control::Continue),
+        }
+    }
+
+    // Show the list of unsaved files.
+    pub fn show_dirty(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let dirty = self.split_tab.changed_files();
+        if dirty.is_empty() {
+            ctx.queue_event(MDEvent::Info("Nothing to save".into()));
+            return Ok(Control::Changed);
+        }
+        ctx.dialogs.push(
+            dirty_dlg::render,
+            dirty_dlg::event,
+            DirtyDialogState::new(dirty),
+        );
+        Ok(Control::Changed)
+    }
+
+    // Ask what to do with a file that couldn't be saved.
+    pub fn save_failed(
+        &mut self,
+        path: &Path,
+        msg: &str,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        // save-as works on the selected tab
+        if let Some((pos, _md)) = self.split_tab.for_path(path) {
+            self.split_tab.select(pos, ctx);
+            self.split_tab.focus_selected(ctx);
+        }
+
+        let state = ChoiceDialogState::new(
+            "Save failed",
+            format!("{}\n\n{}", path.display(), msg).as_str(),
+        );
+        let state = if editor_file::missing_parent(path).is_some() {
+            state.choice("Make directory", MDEvent::SaveCreateDir(path.into()))
+        } else {
+            state.choice("Retry", MDEvent::SaveFile(path.into()))
+        };
+        let state = state
+            .choice("Save as", MDEvent::MenuSaveAs)
+            .choice("Cancel", MDEvent::NoOp);
+        ctx.dialogs
+            .push(choice_dlg::render, choice_dlg::event, state);
         Ok(Control::Changed)
     }
 
@@ -360,6 +707,17 @@ impl MDEditState {
         Ok(Control::Changed)
     }
 
+    // Jump to the previously selected tab.
+    pub fn select_last_tab(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        if let Some(pos) = self.split_tab.last_selected() {
+            self.split_tab.select(pos, ctx);
+            self.split_tab.focus_selected(ctx);
+            Ok(Control::Changed)
+        } else {
+            Ok(Control::Continue)
+        }
+    }
+
     // Close tab
     pub fn close_tab_at(
         &mut self,
@@ -391,16 +749,113 @@ impl MDEditState {
         }
     }
 
+    // Pin or unpin the selected tab.
+    pub fn toggle_pin(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        if let Some((_, md)) = self.split_tab.selected_mut() {
+            md.pinned = !md.pinned;
+            ctx.queue(Control::Event(MDEvent::Info(
+                if md.pinned { "pinned" } else { "unpinned" }.into(),
+            )));
+            ctx.queue(Control::Event(MDEvent::StoreConfig));
+            Ok(Control::Changed)
+        } else {
+            Ok(Control::Continue)
+        }
+    }
+
+    // Pin the file list to its current directory while
+    // the selected split is active.
+    pub fn toggle_split_dir(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let Some(idx_split) = self.split_tab.sel_split else {
+            return Ok(Control::Continue);
+        };
+        let dir = &mut self.split_tab.split_dir[idx_split];
+        if dir.is_some() {
+            *dir = None;
+            ctx.queue(Control::Event(MDEvent::Info("file list unpinned".into())));
+        } else {
+            let current = self.file_list.current_dir().to_path_buf();
+            ctx.queue(Control::Event(MDEvent::Info(format!(
+                "file list pinned to {}",
+                current.display()
+            ))));
+            *dir = Some(current);
+        }
+        Ok(Control::Changed)
+    }
+
+    // Close the files that are not below the root of the file list.
+    pub fn close_outside_root(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let root = self.file_list.root().to_path_buf();
+        let root = fs::canonicalize(&root).unwrap_or(root);
+
+        let open = self
+            .split_tab
+            .split_tab_file
+            .iter()
+            .map(|v| v.len())
+            .sum::<usize>();
+        for s in (0..self.split_tab.split_tab_file.len()).rev() {
+            for t in (0..self.split_tab.split_tab_file[s].len()).rev() {
+                let md = &self.split_tab.split_tab_file[s][t];
+                let path = fs::canonicalize(&md.path).unwrap_or(md.path.clone());
+                if md.pinned || path.starts_with(&root) {
+                    continue;
+                }
+                // changed files are saved, or stay open.
+                self.split_tab.close((s, t), ctx)?;
+            }
+        }
+        let closed = open
+            - self
+                .split_tab
+                .split_tab_file
+                .iter()
+                .map(|v| v.len())
+                .sum::<usize>();
+        if self.split_tab.sel_split.is_none() {
+            self.file_list.focus_files(ctx);
+        } else {
+            self.split_tab.focus_selected(ctx);
+        }
+
+        ctx.queue(Control::Event(MDEvent::Info(format!(
+            "closed {} files outside {}",
+            closed,
+            root.display()
+        ))));
+        Ok(Control::Changed)
+    }
+
+    // Close the tabs of all splits.
+    // Returns false if some file couldn't be saved and stays open.
+    pub fn close_all_splits(&mut self, ctx: &mut GlobalState) -> Result<bool, Error> {
+        for s in (0..self.split_tab.split_tab_file.len()).rev() {
+            for t in (0..self.split_tab.split_tab_file[s].len()).rev() {
+                self.split_tab.close((s, t), ctx)?;
+            }
+        }
+        if self.split_tab.sel_split.is_none() {
+            self.file_list.focus_files(ctx);
+        } else {
+            self.split_tab.focus_selected(ctx);
+        }
+        Ok(self.split_tab.split_tab_file.is_empty())
+    }
+
     // Close all
     pub fn close_all(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
         if let Some(pos) = self.split_tab.selected_pos() {
             for i in (0..self.split_tab.split_tab_file[pos.0].len()).rev() {
+                if self.split_tab.split_tab_file[pos.0][i].pinned {
+                    continue;
+                }
                 self.split_tab.close((pos.0, i), ctx)?;
             }
             if self.split_tab.sel_split.is_none() {
                 self.file_list.focus_files(ctx);
             } else {
-                // noop
+                self.split_tab.focus_selected(ctx);
             }
             Ok(Control::Changed)
         } else {
@@ -408,18 +863,317 @@ impl MDEditState {
         }
     }
 
+    // Move the file to the trash, or ask before deleting it
+    // permanently. Only after that succeeded its tabs are closed.
+    // Tabs with unsaved changes stay open.
+    pub fn delete_file(
+        &mut self,
+        path: &Path,
+        permanent: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let msg = if permanent {
+            if let Err(e) = fs::remove_file(path) {
+                return Ok(Control::Event(MDEvent::Message(format!(
+                    "Can't delete {}: {}",
+                    name, e
+                ))));
+            }
+            format!("{} deleted permanently", name)
+        } else if ctx.cfg.use_trash && self.file_list.trash(path) {
+            format!("{} moved to trash", name)
+        } else {
+            let state =
+                ChoiceDialogState::new("Delete", format!("Delete {} permanently?", name).as_str())
+                    .choice("Delete", MDEvent::DeleteFilePermanently(path.into()))
+                    .choice("Cancel", MDEvent::NoOp);
+            ctx.dialogs
+                .push(choice_dlg::render, choice_dlg::event, state);
+            return Ok(Control::Changed);
+        };
+
+        // close from the back, the positions stay valid.
+        let mut close = Vec::new();
+        let mut kept = false;
+        for (idx_split, split) in self.split_tab.split_tab_file.iter().enumerate() {
+            for (idx_tab, md) in split.iter().enumerate() {
+                if md.path == path {
+                    if md.changed {
+                        kept = true;
+                    } else {
+                        close.push((idx_split, idx_tab));
+                    }
+                }
+            }
+        }
+        for pos in close.into_iter().rev() {
+            self.split_tab.close(pos, ctx)?;
+        }
+        if self.split_tab.sel_split.is_none() {
+            self.file_list.focus_files(ctx);
+        } else {
+            self.split_tab.focus_selected(ctx);
+        }
+
+        ctx.queue(Control::Event(MDEvent::Info(if kept {
+            format!("{}, unsaved changes are still open", msg)
+        } else {
+            msg
+        })));
+        Ok(Control::Changed)
+    }
+
+    // Ask for the new name of the file.
+    pub fn show_rename(
+        &mut self,
+        path: &Path,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.rename_from = Some(path.to_path_buf());
+        ctx.dialogs.push(
+            input_dlg::render,
+            input_dlg::event,
+            InputDialogState::new("Rename", "Name", MDEvent::RenameFile).with_value(&name),
+        );
+        Ok(Control::Changed)
+    }
+
+    // Rename the file in its directory. Open tabs follow
+    // and with 'rename_links' the links to it are rewritten.
+    pub fn rename_file(
+        &mut self,
+        name: &str,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let Some(old) = self.rename_from.take() else {
+            return Ok(Control::Continue);
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains(['/', '\\']) {
+            return Ok(Control::Event(MDEvent::Message(format!(
+                "Invalid file name '{}'.",
+                name
+            ))));
+        }
+        let new = old.with_file_name(name);
+        if new == old {
+            return Ok(Control::Continue);
+        }
+        if new.exists() {
+            return Ok(Control::Event(MDEvent::Message(format!(
+                "{} already exists.",
+                new.display()
+            ))));
+        }
+        if let Err(e) = fs::rename(&old, &new) {
+            return Ok(Control::Event(MDEvent::Message(format!(
+                "Can't rename {}: {}",
+                old.display(),
+                e
+            ))));
+        }
+
+        for md in self.split_tab.split_tab_file.iter_mut().flatten() {
+            if md.path == old {
+                md.renamed(&new);
+            }
+        }
+        let dir = self.file_list.current_dir().to_path_buf();
+        self.file_list.load_current(&dir, &ctx.cfg.globs)?;
+        self.file_list.select(&new)?;
+
+        if ctx.cfg.rename_links {
+            self.find_links(&old, &new, ctx)?;
+        } else {
+            ctx.queue(Control::Event(MDEvent::Info(format!(
+                "renamed to {}",
+                name
+            ))));
+        }
+        Ok(Control::Changed)
+    }
+
+    // Find the files below the root that link to the renamed file.
+    // Open files are checked here, the rest in the background.
+    fn find_links(&mut self, old: &Path, new: &Path, ctx: &mut GlobalState) -> Result<(), Error> {
+        let new = fs::canonicalize(new)?;
+        let Some(dir) = new.parent() else {
+            return Ok(());
+        };
+        let old = dir.join(old.file_name().unwrap_or_default());
+        let root = FileSysStructure::find_root(dir).unwrap_or(dir.to_path_buf());
+
+        let mut open = Vec::new();
+        let mut found = Vec::new();
+        for md in self.split_tab.split_tab_file.iter().flatten() {
+            let path = fs::canonicalize(&md.path).unwrap_or(md.path.clone());
+            if open.contains(&path) {
+                continue;
+            }
+            let Some(md_dir) = path.parent() else {
+                continue;
+            };
+            // the buffer may be ahead of the file.
+            let text = md.edit.text();
+            if !doc_type::retarget_links(&text, md.md_options, md_dir, &root, &old, &new).is_empty()
+            {
+                found.push(md.path.clone());
+            }
+            open.push(path);
+        }
+
+        let globs = ctx.cfg.globs.clone();
+        let options = ctx.cfg.md_flavor.0;
+        ctx.spawn(move || {
+            for path in search::files(&root, &globs) {
+                if open.contains(&path) {
+                    continue;
+                }
+                let Some(dir) = path.parent() else {
+                    continue;
+                };
+                let Ok(text) = fs::read_to_string(&path) else {
+                    continue;
+                };
+                if !doc_type::retarget_links(&text, options, dir, &root, &old, &new).is_empty() {
+                    found.push(path);
+                }
+            }
+            Ok(Control::Event(MDEvent::RenamedLinks(old, new, found)))
+        })?;
+
+        ctx.queue(Control::Event(MDEvent::Info(format!(
+            "searching links in {} ...",
+            root.display()
+        ))));
+        Ok(())
+    }
+
+    // List the files with links to the renamed file and ask.
+    pub fn confirm_rewrite_links(
+        &mut self,
+        old: &Path,
+        new: &Path,
+        files: &[PathBuf],
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let name = new.file_name().unwrap_or_default().to_string_lossy();
+        if files.is_empty() {
+            return Ok(Control::Event(MDEvent::Info(format!(
+                "renamed to {}, no links to rewrite",
+                name
+            ))));
+        }
+
+        let root =
+            FileSysStructure::find_root(new.parent().unwrap_or(new)).unwrap_or(new.to_path_buf());
+        let mut msg = format!(
+            "Rewrite the links to {} in {} files?\n\n",
+            name,
+            files.len()
+        );
+        for p in files.iter().take(MAX_LISTED_FILES) {
+            let p = fs::canonicalize(p).unwrap_or(p.clone());
+            let rel = completion::relative_path(&root, &p);
+            msg.push_str(format!("  {}\n", rel.display()).as_str());
+        }
+        if files.len() > MAX_LISTED_FILES {
+            msg.push_str(format!("  ... and {} more\n", files.len() - MAX_LISTED_FILES).as_str());
+        }
+
+        let state = ChoiceDialogState::new("Renamed", msg.as_str())
+            .choice(
+                "Rewrite",
+                MDEvent::RewriteLinks(old.to_path_buf(), new.to_path_buf(), files.to_vec()),
+            )
+            .choice("Cancel", MDEvent::NoOp);
+        ctx.dialogs
+            .push(choice_dlg::render, choice_dlg::event, state);
+        Ok(Control::Changed)
+    }
+
+    // Rewrite the links to the renamed file. Open files are
+    // changed in the editor, all others on disk.
+    pub fn rewrite_links(
+        &mut self,
+        old: &Path,
+        new: &Path,
+        files: &[PathBuf],
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let root =
+            FileSysStructure::find_root(new.parent().unwrap_or(new)).unwrap_or(new.to_path_buf());
+        let options = ctx.cfg.md_flavor.0;
+
+        let mut count = 0;
+        let mut failed = Vec::new();
+        for path in files {
+            if let Some((pos, md)) = self.split_tab.for_path_mut(path) {
+                if md.retarget_links(&root, old, new, ctx) > 0 {
+                    count += 1;
+                }
+                let replay = md.edit.recent_replay_log();
+                if !replay.is_empty() {
+                    self.split_tab.replay(pos, path, &replay, ctx);
+                }
+            } else {
+                let mut rewrite = || -> Result<(), Error> {
+                    let text = fs::read_to_string(path)?;
+                    let dir = path.parent().unwrap_or(Path::new(""));
+                    let replace = doc_type::retarget_links(&text, options, dir, &root, old, new);
+                    if replace.is_empty() {
+                        return Ok(());
+                    }
+                    if ctx.cfg.backup {
+                        editor_file::backup(path, ctx.cfg.backup_count)?;
+                    }
+                    let text = doc_type::replace_str_ranges(&text, replace);
+                    editor_file::write_atomic(path, text.as_bytes())?;
+                    count += 1;
+                    Ok(())
+                };
+                if let Err(e) = rewrite() {
+                    failed.push(format!("{}: {}", path.display(), e));
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            ctx.queue(Control::Event(MDEvent::Info(format!(
+                "rewrote links in {} files",
+                count
+            ))));
+            Ok(Control::Changed)
+        } else {
+            Ok(Control::Event(MDEvent::Message(format!(
+                "Rewrote links in {} files, failed for\n\n{}",
+                count,
+                failed.join("\n")
+            ))))
+        }
+    }
+
     // Save selected as.
     pub fn save_as(
         &mut self,
         path: &Path,
-        _ctx: &mut GlobalState,
+        ctx: &mut GlobalState,
     ) -> Result<Control<MDEvent>, Error> {
         let mut path = path.to_path_buf();
-        if path.extension().is_none() {
-            path.set_extension("md");
-        }
         if let Some((_pos, t)) = self.split_tab.selected_mut() {
-            t.save_as(&path)?;
+            // hidden files have no extension, leave them alone.
+            let hidden = path
+                .file_name()
+                .is_some_and(|v| v.to_string_lossy().starts_with('.'));
+            if path.extension().is_none() && !hidden {
+                path.set_extension(t.extension());
+            }
+            if let Err(e) = t.save_as(&path, &ctx.cfg) {
+                let old_path = t.path.clone();
+                ctx.queue_event(MDEvent::SaveFailed(old_path, format!("{:#}", e)));
+            }
         }
         Ok(Control::Changed)
     }
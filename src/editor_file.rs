@@ -1,46 +1,152 @@
-use crate::doc_type::{DocType, DocTypes};
+use crate::cfg::{FileView, FormatMode, LinkClick, MDConfig, TabAction, TableAlign};
+use crate::command;
+use crate::completion;
+use crate::completion::{Completion, CompletionKind};
+use crate::date;
+use crate::dlg::choice_dlg;
+use crate::dlg::choice_dlg::ChoiceDialogState;
+use crate::dlg::input_dlg;
+use crate::dlg::input_dlg::InputDialogState;
+use crate::doc_type::{
+    convert_link_paths, replace_byte_ranges, retarget_links, CodeBlock, DocType, DocTypes,
+    LinkPaths,
+};
+use crate::fsys::FileSysStructure;
 use crate::global::event::MDEvent;
-use crate::global::theme::MDWidgets;
+use crate::global::theme::{MDStyles, MDWidgets};
 use crate::global::GlobalState;
+use crate::highlight;
+use crate::hyperlink;
+use crate::image_info::ImageInfo;
+use crate::image_preview::Graphics;
+use crate::md_list;
+use crate::paste;
+use crate::rat_salsa::timer::{TimerDef, TimerHandle};
+use crate::rat_salsa::{Control, SalsaContext};
+use crate::search;
+use crate::smart_punct;
+use crate::text_case;
+use crate::text_case::CaseMode;
 use anyhow::{anyhow, Error};
+use crossbeam::atomic::AtomicCell;
 use log::warn;
 use pulldown_cmark::{Event, Options, Parser, Tag};
 use rat_markdown::styles::MDStyle;
 use rat_markdown::MarkDown;
-use crate::rat_salsa::timer::{TimerDef, TimerHandle};
-use crate::rat_salsa::{Control, SalsaContext};
+use rat_theme4::palette::Colors;
 use rat_theme4::{StyleName, WidgetStyle};
 use rat_widget::event::util::MouseFlags;
-use rat_widget::event::{ct_event, try_flow, ConsumedEvent, HandleEvent, TextOutcome};
+use rat_widget::event::{ct_event, try_flow, ConsumedEvent, HandleEvent, ReadOnly, TextOutcome};
 use rat_widget::focus::{FocusBuilder, FocusFlag, HasFocus, Navigation};
 use rat_widget::line_number::{LineNumberState, LineNumbers};
 use rat_widget::scrolled::Scroll;
-use rat_widget::text::clipboard::{Clipboard, ClipboardError};
-use rat_widget::text::{HasScreenCursor, TextStyle};
+use rat_widget::text::clipboard::Clipboard;
+use rat_widget::text::{upos_type, HasScreenCursor, TextPosition, TextRange, TextStyle};
 use rat_widget::textarea::{TextArea, TextAreaState, TextWrap};
 use rat_widget::util::fill_buf_area;
 use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::layout::Rect;
-use ratatui::style::Style;
-use ratatui::widgets::{Block, BorderType, Borders, StatefulWidget};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, BorderType, Borders, StatefulWidget, Widget};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::io::{BufWriter, Read, Seek, Write};
+use std::mem;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::{Duration, Instant, SystemTime};
+use unicode_width::UnicodeWidthChar;
 
 #[derive(Debug)]
 pub struct MDFileState {
     pub path: PathBuf,
     pub changed: bool,
     pub doc_type: DocTypes,
+    /// Markdown extensions for parsing.
+    pub md_options: Options,
+    /// View toggles that override the global ones.
+    pub view: FileView,
     pub edit: TextAreaState,
     pub edit_mouse: MouseFlags,
+    /// Time and position of the last double-click.
+    pub last_doubleclick: Option<(Instant, (u16, u16))>,
     pub show_linenr: bool,
     pub linenr: LineNumberState,
     pub parse_timer: Option<TimerHandle>,
+    /// Code block waiting for its output.
+    pub run_block: Option<RunBlock>,
+    /// Metadata for linked images. None if the image is missing.
+    pub images: HashMap<PathBuf, Option<ImageInfo>>,
+    /// Debounce the image preview.
+    pub preview_timer: Option<TimerHandle>,
+    /// Scrolls while a selection is dragged to the top/bottom
+    /// edge. With the last mouse position.
+    pub drag_scroll: Option<(TimerHandle, (u16, u16))>,
+    /// The left button went down inside the text. Only
+    /// then a drag can scroll.
+    pub drag_inside: bool,
+    /// Image under the cursor.
+    pub preview: Option<PathBuf>,
+    /// Parsed styles keyed by a hash of the text.
+    /// Shared with the clones in other splits.
+    pub style_cache: Rc<RefCell<Option<(u64, Vec<(Range<usize>, usize)>)>>>,
+    /// Last time this tab was selected.
+    pub last_focus: Instant,
+    /// Large file, no editing.
+    pub read_only: bool,
+    /// Pinned tabs are not closed by eviction or the close-many commands.
+    pub pinned: bool,
+    /// Selections before expand_selection() for shrink_selection().
+    /// The last entry is the expanded selection.
+    pub selection_stack: Vec<Range<usize>>,
+    /// Text hash while the external formatter runs.
+    pub run_format: Option<u64>,
+    /// Open completion popup.
+    pub completion: Option<Completion>,
+    /// Words of the text for complete_word(). Refreshed by the parse timer.
+    pub words: BTreeSet<String>,
+    /// Links and their urls for the OSC 8 hyperlinks.
+    /// Refreshed by the parse timer.
+    pub links: Vec<(Range<usize>, String)>,
+    /// Table and list styles for cursor_context(), sorted.
+    /// Refreshed by the parse timer.
+    pub structure: Vec<(Range<usize>, usize)>,
+    /// Last complete_word(): prefix, start and the inserted word.
+    pub dabbrev: Option<(String, usize, String)>,
+    /// Cursor at the last update_cursor_pos().
+    pub last_cursor: TextPosition,
+    /// Highlighted search matches.
+    pub search_hl: Vec<Range<usize>>,
+    /// Last recenter() and where the cursor was.
+    pub recenter: Option<(TextPosition, ViewAlign)>,
+}
+
+/// Code block waiting for its output. The text may change
+/// while it runs, so the block is found again by its fence
+/// and content.
+#[derive(Debug, Clone)]
+pub struct RunBlock {
+    /// Byte position of the block when it started.
+    pub start: usize,
+    /// Opening fence line.
+    pub fence: String,
+    /// Content of the block.
+    pub code: String,
+}
+
+/// Where recenter() puts the cursor line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewAlign {
+    Center,
+    Top,
+    Bottom,
 }
 
 pub fn render(
@@ -66,6 +172,8 @@ pub fn render(
     );
 
     let style = theme.style::<TextStyle>(WidgetStyle::TEXT_DOCUMENT);
+    let base_bg = style.style.bg;
+    let text_styles = theme.style::<HashMap<usize, Style>>(WidgetStyle::TEXT_STYLES);
     TextArea::new()
         .block(
             Block::new()
@@ -74,9 +182,242 @@ pub fn render(
         )
         .vscroll(Scroll::new().start_margin(start_margin))
         .styles(style)
-        .text_style_map(theme.style::<HashMap<usize, Style>>(WidgetStyle::TEXT_STYLES))
+        .text_style_map(text_styles.clone())
         .render(text_area, buf, &mut state.edit);
 
+    // code block background over the full width. only cells with
+    // the plain background, selection and cursor line stay.
+    let code_blocks = state.doc_type.code_blocks(&state.edit);
+    if !code_blocks.is_empty() {
+        let code_bg = theme
+            .style_style(Style::CODE_BLOCK_BG)
+            .bg
+            .unwrap_or_default();
+        let inner = state.edit.inner;
+        let first = state.edit.vertical_offset() as upos_type;
+        let last = (first + inner.height as upos_type).min(state.edit.len_lines());
+        // first screen row of each visible line
+        let rows = (first..last)
+            .map(|y| {
+                let start = TextPosition::new(0, y);
+                let row = state.edit.pos_to_screen(start).map(|v| v.1);
+                (state.edit.byte_at(start).start, row)
+            })
+            .collect::<Vec<_>>();
+        for (idx, (byte, row)) in rows.iter().enumerate() {
+            let Some(row) = row else {
+                continue;
+            };
+            if !code_blocks.iter().any(|v| v.range.contains(byte)) {
+                continue;
+            }
+            // wrapped lines cover more rows
+            let next = rows
+                .get(idx + 1)
+                .and_then(|(_, v)| *v)
+                .unwrap_or(inner.bottom());
+            for y in *row..next.min(inner.bottom()) {
+                for x in inner.left()..inner.right() {
+                    match buf.cell_mut((x, y)) {
+                        Some(cell) if Some(cell.bg) == base_bg => cell.bg = code_bg,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    // focus mode dims everything outside the current paragraph
+    if ctx.cfg.focus_mode {
+        let (y0, y1) = state.paragraph_at(state.edit.cursor().y);
+        let inner = state.edit.inner;
+        for row in inner.top()..inner.bottom() {
+            let in_paragraph = state
+                .edit
+                .screen_to_pos((inner.x, row))
+                .filter(|v| state.edit.pos_to_screen(*v).map(|v| v.1) == Some(row))
+                .is_some_and(|v| (y0..=y1).contains(&v.y));
+            if !in_paragraph {
+                buf.set_style(
+                    Rect::new(inner.x, row, inner.width, 1),
+                    Style::new().add_modifier(Modifier::DIM),
+                );
+            }
+        }
+    }
+
+    // trailing whitespace of the visible lines
+    if ctx.cfg.show_trailing {
+        let ws_style = theme.style_style(Style::TRAILING_WS);
+        let first = state.edit.vertical_offset() as upos_type;
+        let last = (first + state.edit.inner.height as upos_type).min(state.edit.len_lines());
+        for y in first..last {
+            let line = state.line_str(y);
+            let trimmed = line.trim_end().len();
+            if trimmed == line.len() {
+                continue;
+            }
+            let start = state.edit.byte_at(TextPosition::new(0, y)).start;
+            for ws_area in state.screen_spans(start + trimmed..start + line.len()) {
+                buf.set_style(ws_area, ws_style);
+            }
+        }
+    }
+
+    // guide at the text width, only on empty cells.
+    if ctx.cfg.show_guide {
+        let inner = state.edit.inner;
+        let col = (ctx.cfg.text_width as usize).checked_sub(state.edit.horizontal_offset());
+        if let Some(col) = col.filter(|v| *v < inner.width as usize) {
+            let guide_style = theme.style_style(Style::TEXT_GUIDE);
+            let x = inner.x + col as u16;
+            for y in inner.top()..inner.bottom() {
+                if let Some(cell) = buf.cell_mut((x, y)) {
+                    if cell.symbol() == " " {
+                        cell.set_symbol("\u{2502}");
+                        cell.set_style(guide_style);
+                    }
+                }
+            }
+        }
+    }
+
+    // sticky heading
+    if let Some((heading, txt)) = state.sticky_heading() {
+        let base = theme.style_style(Style::TEXT_BASE);
+        let heading_style = text_styles.get(&heading).copied().unwrap_or_default();
+        let sticky_area = Rect::new(
+            state.edit.inner.x,
+            state.edit.inner.y,
+            state.edit.inner.width,
+            1,
+        );
+        fill_buf_area(buf, sticky_area, " ", base);
+        Line::from(txt)
+            .style(base.patch(heading_style).add_modifier(Modifier::DIM))
+            .render(sticky_area, buf);
+    }
+
+    // code block labels
+    let code_style = text_styles
+        .get(&MDStyle::CodeBlock.into())
+        .copied()
+        .unwrap_or_default();
+    for block in code_blocks {
+        if block.lang.is_empty() {
+            continue;
+        }
+        let pos = state.edit.byte_pos(block.range.start);
+        let Some((_, y)) = state.edit.pos_to_screen(pos) else {
+            continue;
+        };
+        let label = format!(" {} ", block.lang);
+        let width = (label.len() as u16).min(state.edit.inner.width);
+        let label_area = Rect::new(state.edit.inner.right().saturating_sub(width), y, width, 1);
+        Line::from(label)
+            .style(code_style.add_modifier(Modifier::REVERSED))
+            .render(label_area, buf);
+    }
+
+    // image annotations
+    let base = theme.style_style(Style::TEXT_BASE);
+    let top = state.edit.vertical_offset() as upos_type;
+    let bottom = top + state.edit.inner.height as upos_type;
+    for (range, alt, path) in state.image_links() {
+        let start = state.edit.byte_pos(range.start);
+        let end = state.edit.byte_pos(range.end);
+        if end.y < top || start.y > bottom {
+            continue;
+        }
+        let Some((x, y)) = state.edit.pos_to_screen(end) else {
+            continue;
+        };
+
+        let (info, info_style) = match path.as_ref().and_then(|v| state.images.get(v)) {
+            Some(Some(info)) => (
+                format!(" {} \u{00B7} {}", alt, info.display()),
+                base.add_modifier(Modifier::DIM),
+            ),
+            Some(None) => {
+                let warn_style = theme.p.fg_style(Colors::Red, 2);
+                if let Some((sx, sy)) = state.edit.pos_to_screen(start) {
+                    if sy == y && sx < x {
+                        buf.set_style(Rect::new(sx, sy, x - sx, 1), warn_style);
+                    }
+                }
+                (format!(" {} \u{00B7} missing", alt), warn_style)
+            }
+            None => (format!(" {}", alt), base.add_modifier(Modifier::DIM)),
+        };
+
+        // only where the rest of the row is empty.
+        let info_area = Rect::new(x, y, state.edit.inner.right().saturating_sub(x), 1);
+        let free = info_area
+            .positions()
+            .all(|v| buf.cell(v).is_none_or(|c| c.symbol() == " "));
+        if free {
+            Line::from(info).style(info_style).render(info_area, buf);
+        }
+    }
+
+    // image preview. the image itself is written after rendering.
+    if let Some(path) = &state.preview {
+        if state.edit.is_focused() {
+            let inner = state.edit.inner;
+            let width = (inner.width / 2).min(32);
+            let height = (inner.height / 2).min(12);
+            let preview_area = Rect::new(
+                inner.right().saturating_sub(width),
+                inner.bottom().saturating_sub(height),
+                width,
+                height,
+            );
+            fill_buf_area(buf, preview_area, " ", base);
+            ctx.preview = Some((path.clone(), preview_area));
+        }
+    }
+
+    // clickable links. after everything that writes to the
+    // text cells, before the popups.
+    if ctx.hyperlinks && ctx.cfg.hyperlinks {
+        let sticky = state.sticky_heading().is_some();
+        let len = state.edit.text().len();
+        let top_byte = state.edit.byte_at(TextPosition::new(0, top)).start;
+        let bottom_byte = if bottom < state.edit.len_lines() {
+            state.edit.byte_at(TextPosition::new(0, bottom)).start
+        } else {
+            len
+        };
+        for (range, url) in &state.links {
+            // the ranges may be stale until the next parse.
+            if range.end > len || range.end < top_byte || range.start > bottom_byte {
+                continue;
+            }
+            for link_area in state.screen_spans(range.clone()) {
+                if sticky && link_area.y == state.edit.inner.y {
+                    continue;
+                }
+                if ctx
+                    .preview
+                    .as_ref()
+                    .is_some_and(|(_, v)| v.intersects(link_area))
+                {
+                    continue;
+                }
+                hyperlink::render(link_area, url, buf);
+            }
+        }
+    }
+
+    // completion popup
+    if let Some(completion) = &mut state.completion {
+        if state.edit.is_focused() {
+            if let Some(pos) = state.edit.screen_cursor() {
+                completion::render(pos, state.edit.inner, buf, completion, ctx);
+            }
+        }
+    }
+
     if state.show_linenr {
         let line_nr_area = Rect::new(area.x, area.y, ln_width, area.height);
         LineNumbers::new()
@@ -99,17 +440,45 @@ pub fn render(
     Ok(())
 }
 
+// Views of the same file in different splits are full clones.
+// TextAreaState owns its text-store and undo-buffer, and rat-widget
+// has no way to share them between two widget states. Changes are
+// synced with the undo replay-log instead. Only the parsed styles
+// are shared.
 impl Clone for MDFileState {
     fn clone(&self) -> Self {
         let mut s = Self {
             path: self.path.clone(),
             changed: self.changed,
             doc_type: self.doc_type,
+            md_options: self.md_options,
+            view: self.view.clone(),
             edit: self.edit.clone(),
             edit_mouse: self.edit_mouse.clone(),
+            last_doubleclick: self.last_doubleclick,
             show_linenr: self.show_linenr,
             linenr: self.linenr.clone(),
             parse_timer: None,
+            run_block: None,
+            images: self.images.clone(),
+            preview_timer: None,
+            drag_scroll: None,
+            drag_inside: false,
+            preview: None,
+            style_cache: self.style_cache.clone(),
+            last_focus: Instant::now(),
+            read_only: self.read_only,
+            pinned: false,
+            selection_stack: Default::default(),
+            run_format: None,
+            completion: None,
+            words: self.words.clone(),
+            links: self.links.clone(),
+            structure: self.structure.clone(),
+            dabbrev: None,
+            last_cursor: Default::default(),
+            recenter: None,
+            search_hl: Default::default(),
         };
 
         let nnn = SystemTime::now()
@@ -142,36 +511,6 @@ impl HasFocus for MDFileState {
     }
 }
 
-#[derive(Debug, Default, Clone)]
-struct CliClipboard {
-    clip: RefCell<String>,
-}
-
-impl Clipboard for CliClipboard {
-    fn get_string(&self) -> Result<String, ClipboardError> {
-        match cli_clipboard::get_contents() {
-            Ok(v) => Ok(v),
-            Err(e) => {
-                warn!("{:?}", e);
-                Ok(self.clip.borrow().clone())
-            }
-        }
-    }
-
-    fn set_string(&self, s: &str) -> Result<(), ClipboardError> {
-        let mut clip = self.clip.borrow_mut();
-        *clip = s.to_string();
-
-        match cli_clipboard::set_contents(s.to_string()) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                warn!("{:?}", e);
-                Err(ClipboardError)
-            }
-        }
-    }
-}
-
 pub fn event(
     event: &MDEvent,
     state: &mut MDFileState,
@@ -179,18 +518,290 @@ pub fn event(
 ) -> Result<Control<MDEvent>, Error> {
     match event {
         MDEvent::TimeOut(event) => {
-            try_flow!(if state.parse_timer == Some(event.handle) {
-                state.doc_type.parse(&mut state.edit);
+            try_flow!(if state.parse_timer == Some(event.handle) && ctx.paused {
+                // postpone while unfocused
+                state.parse_timer = Some(
+                    ctx.add_timer(TimerDef::new().next(Instant::now() + Duration::from_secs(1))),
+                );
+                Control::Unchanged
+            } else if state.parse_timer == Some(event.handle) {
+                state.parse();
+                state.collect_structure();
+                state.collect_words();
+                if ctx.hyperlinks && ctx.cfg.hyperlinks {
+                    state.collect_links();
+                }
+                state.update_search_hl(ctx);
+                state.load_image_info(ctx)?;
+                Control::Changed
+            } else {
+                Control::Continue
+            });
+            try_flow!(match state.drag_scroll {
+                Some((timer, pos)) if timer == event.handle => state.drag_scroll_step(pos, ctx),
+                _ => Control::Continue,
+            });
+            try_flow!(if state.preview_timer == Some(event.handle) {
+                state.preview = state
+                    .image_at_cursor()
+                    .filter(|v| ctx.graphics.supports(v) && v.exists());
                 Control::Changed
             } else {
                 Control::Continue
             });
         }
+        MDEvent::Event(event) if state.read_only => {
+            state.track_drag(event);
+            try_flow!(match event {
+                ct_event!(mouse down Left for x,y) if state.is_tripleclick((*x, *y)) => {
+                    state.select_paragraph(ctx)
+                }
+                ct_event!(mouse any for m) if state.edit_mouse.doubleclick(state.edit.inner, m) => {
+                    state.last_doubleclick = Some((Instant::now(), (m.column, m.row)));
+                    if ctx.cfg.link_click == LinkClick::DoubleClick {
+                        state.follow_link(ctx.cfg.link_split, ctx)?
+                    } else {
+                        Control::Continue
+                    }
+                }
+                ct_event!(mouse down CONTROL-Left for x,y)
+                    if ctx.cfg.link_click == LinkClick::CtrlClick
+                        && state.edit.inner.contains((*x, *y).into()) =>
+                {
+                    state.follow_link_at((*x, *y), ctx.cfg.link_split, ctx)?
+                }
+                ct_event!(mouse down CONTROL_SHIFT-Left for x,y)
+                    if ctx.cfg.link_click == LinkClick::CtrlClick
+                        && state.edit.inner.contains((*x, *y).into()) =>
+                {
+                    state.follow_link_at((*x, *y), !ctx.cfg.link_split, ctx)?
+                }
+                ct_event!(key press CONTROL-'l') if state.edit.is_focused() => {
+                    state.follow_link(ctx.cfg.link_split, ctx)?
+                }
+                ct_event!(key press CONTROL_SHIFT-'L') if state.edit.is_focused() => {
+                    state.follow_link(!ctx.cfg.link_split, ctx)?
+                }
+                ct_event!(mouse drag Left for x,y) if state.drag_inside => {
+                    state.drag_scroll((*x, *y), ctx);
+                    Control::Continue
+                }
+                ct_event!(mouse up Left for x,y) => {
+                    let dragged = state.stop_drag_scroll(ctx);
+                    if ctx.cfg.primary_selection
+                        && (dragged || state.edit.inner.contains((*x, *y).into()))
+                    {
+                        state.update_primary(ctx);
+                    }
+                    Control::Continue
+                }
+                _ => Control::Continue,
+            });
+            try_flow!(match state.edit.handle(event, ReadOnly) {
+                TextOutcome::Changed => {
+                    state.update_cursor_pos(ctx);
+                    Control::Changed
+                }
+                r => r.into(),
+            });
+        }
         MDEvent::Event(event) => {
+            state.track_drag(event);
+            // completion popup
+            if state.edit.is_focused()
+                && state
+                    .completion
+                    .as_ref()
+                    .is_some_and(|v| v.selected().is_some())
+            {
+                try_flow!(match event {
+                    ct_event!(keycode press Up) => {
+                        if let Some(completion) = &mut state.completion {
+                            completion.prev();
+                        }
+                        Control::Changed
+                    }
+                    ct_event!(keycode press Down) => {
+                        if let Some(completion) = &mut state.completion {
+                            completion.next();
+                        }
+                        Control::Changed
+                    }
+                    ct_event!(keycode press Enter) => state.accept_completion(ctx)?,
+                    _ => Control::Continue,
+                });
+            }
             // click click
             try_flow!(match event {
+                ct_event!(mouse down Left for x,y) if state.is_tripleclick((*x, *y)) => {
+                    state.select_paragraph(ctx)
+                }
                 ct_event!(mouse any for m) if state.edit_mouse.doubleclick(state.edit.inner, m) => {
-                    state.follow_link(ctx)?
+                    state.last_doubleclick = Some((Instant::now(), (m.column, m.row)));
+                    if ctx.cfg.link_click == LinkClick::DoubleClick {
+                        state.follow_link(ctx.cfg.link_split, ctx)?
+                    } else {
+                        Control::Continue
+                    }
+                }
+                ct_event!(mouse down CONTROL-Left for x,y)
+                    if ctx.cfg.link_click == LinkClick::CtrlClick
+                        && state.edit.inner.contains((*x, *y).into()) =>
+                {
+                    state.follow_link_at((*x, *y), ctx.cfg.link_split, ctx)?
+                }
+                ct_event!(mouse down CONTROL_SHIFT-Left for x,y)
+                    if ctx.cfg.link_click == LinkClick::CtrlClick
+                        && state.edit.inner.contains((*x, *y).into()) =>
+                {
+                    state.follow_link_at((*x, *y), !ctx.cfg.link_split, ctx)?
+                }
+                ct_event!(mouse drag Left for x,y) if state.drag_inside => {
+                    state.drag_scroll((*x, *y), ctx);
+                    Control::Continue
+                }
+                ct_event!(mouse up Left for x,y) => {
+                    let dragged = state.stop_drag_scroll(ctx);
+                    if ctx.cfg.primary_selection
+                        && (dragged || state.edit.inner.contains((*x, *y).into()))
+                    {
+                        state.update_primary(ctx);
+                    }
+                    Control::Continue
+                }
+                ct_event!(mouse down Middle for x,y)
+                    if ctx.cfg.primary_selection && state.edit.inner.contains((*x, *y).into()) =>
+                {
+                    state.paste_primary((*x, *y), ctx)
+                }
+                ct_event!(mouse down Left for x,y)
+                    if state.edit.inner.contains((*x, *y).into()) =>
+                {
+                    if let Some(pos) = state.edit.screen_to_pos((*x, *y)) {
+                        let marker = state.task_marker(pos.y);
+                        let byte = state.edit.byte_at(pos).start;
+                        if marker.is_some_and(|v| v.contains(&byte)) && state.toggle_task_at(pos.y)
+                        {
+                            state.text_changed(ctx)
+                        } else {
+                            Control::Continue
+                        }
+                    } else {
+                        Control::Continue
+                    }
+                }
+                ct_event!(key press ' ')
+                    if state.edit.is_focused()
+                        && !state.edit.has_selection()
+                        && state.task_marker(state.edit.cursor().y).is_some_and(|v| {
+                            state.edit.byte_at(state.edit.cursor()).start <= v.end
+                        }) =>
+                {
+                    if state.toggle_task() {
+                        state.text_changed(ctx)
+                    } else {
+                        Control::Continue
+                    }
+                }
+                ct_event!(key press CONTROL_ALT-'v') if state.edit.is_focused() => {
+                    ctx.dialogs.push(
+                        input_dlg::render,
+                        input_dlg::event,
+                        InputDialogState::new("Paste as code block", "Language", |v| {
+                            MDEvent::PasteCodeBlock(v)
+                        }),
+                    );
+                    Control::Changed
+                }
+                ct_event!(key press CONTROL_ALT-'b') if state.edit.is_focused() => {
+                    ctx.dialogs.push(
+                        input_dlg::render,
+                        input_dlg::event,
+                        InputDialogState::new("Wrap in code block", "Language", |v| {
+                            MDEvent::WrapCodeFence(v)
+                        }),
+                    );
+                    Control::Changed
+                }
+                ct_event!(key press CONTROL-'v') if state.edit.is_focused() => {
+                    state.smart_paste(ctx)?
+                }
+                ratatui::crossterm::event::Event::Paste(txt)
+                    if state.edit.is_focused() && is_large_paste(txt, ctx) =>
+                {
+                    confirm_paste(txt.clone(), ctx)
+                }
+                ct_event!(key press CONTROL-'f') if state.edit.is_focused() => {
+                    ctx.dialogs.push(
+                        input_dlg::render,
+                        input_dlg::event,
+                        InputDialogState::new("Search", "Find", |v| MDEvent::Search(v))
+                            .with_value(&ctx.search)
+                            .with_history(&ctx.search_history),
+                    );
+                    Control::Changed
+                }
+                ct_event!(key press CONTROL_SHIFT-'F') if state.edit.is_focused() => {
+                    state.count_dialog(false, ctx)
+                }
+                _ => Control::Continue,
+            });
+            try_flow!(match event {
+                ct_event!(keycode press ALT-Up) if state.edit.is_focused() => {
+                    state.expand_selection(ctx)?
+                }
+                ct_event!(keycode press ALT-Down) if state.edit.is_focused() => {
+                    state.shrink_selection(ctx)?
+                }
+                ct_event!(keycode press CONTROL-Backspace)
+                    if state.edit.is_focused() && !state.edit.has_selection() =>
+                {
+                    state.delete_md_unit(true, ctx)?
+                }
+                ct_event!(keycode press CONTROL-Delete)
+                    if state.edit.is_focused() && !state.edit.has_selection() =>
+                {
+                    state.delete_md_unit(false, ctx)?
+                }
+                ct_event!(keycode press Enter)
+                    if ctx.cfg.auto_indent
+                        && !ctx.paste_mode
+                        && state.edit.is_focused()
+                        && !state.edit.has_selection() =>
+                {
+                    state.auto_indent(ctx)?
+                }
+                ct_event!(keycode press Tab)
+                    if state.edit.is_focused() && !state.edit.has_selection() =>
+                {
+                    state.tab_key(event, ctx)?
+                }
+                ct_event!(keycode press SHIFT-BackTab)
+                    if state.edit.is_focused()
+                        && !state.edit.has_selection()
+                        && state.is_list_item(state.edit.cursor().y) =>
+                {
+                    state.indent_list_item(true, ctx)?
+                }
+                ct_event!(keycode press Tab)
+                    if state.edit.is_focused() && state.is_multi_line_selection() =>
+                {
+                    state.indent_lines(false, ctx)?
+                }
+                ct_event!(keycode press SHIFT-BackTab)
+                    if state.edit.is_focused() && state.is_multi_line_selection() =>
+                {
+                    state.indent_lines(true, ctx)?
+                }
+                _ => Control::Continue,
+            });
+            try_flow!(match typed_char(event) {
+                Some(c @ ('"' | '\'' | '-' | '.'))
+                    if ctx.cfg.smart_punct
+                        && state.edit.is_focused()
+                        && !state.edit.has_selection() =>
+                {
+                    state.smart_punct(c, ctx)?
                 }
                 _ => Control::Continue,
             });
@@ -199,10 +810,12 @@ pub fn event(
                 match state.edit.handle(event, MarkDown::new(ctx.cfg.text_width)) {
                     TextOutcome::TextChanged => {
                         state.update_cursor_pos(ctx);
+                        state.update_completion(typed_char(event), ctx)?;
                         state.text_changed(ctx)
                     }
                     TextOutcome::Changed => {
                         state.update_cursor_pos(ctx);
+                        state.update_completion(None, ctx)?;
                         Control::Changed
                     }
                     r => r.into(),
@@ -212,18 +825,143 @@ pub fn event(
             if state.is_focused() {
                 try_flow!(match event {
                     ct_event!(key press CONTROL-'l') => {
-                        state.follow_link(ctx)? //
+                        state.follow_link(ctx.cfg.link_split, ctx)? //
+                    }
+                    ct_event!(key press CONTROL_SHIFT-'L') => {
+                        state.follow_link(!ctx.cfg.link_split, ctx)?
                     }
                     ct_event!(keycode press F(8)) => {
-                        if state.edit.is_focused() {
-                            state.reformat(false, ctx)?
+                        if state.edit.is_focused() && !ctx.cfg.format_command.is_empty() {
+                            state.external_format(ctx)?
+                        } else if state.edit.is_focused() {
+                            state.reformat(ctx.cfg.format_mode, ctx)?
                         } else {
                             Control::Continue
                         }
                     }
                     ct_event!(keycode press F(7)) => {
                         if state.edit.is_focused() {
-                            state.reformat(true, ctx)?
+                            state.reformat(FormatMode::ReflowEqWidth, ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'c') => {
+                        if state.edit.is_focused() {
+                            state.copy_code_block(ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'r') => {
+                        if state.edit.is_focused() {
+                            state.run_code_block(ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'f') => {
+                        if state.edit.is_focused() {
+                            state.insert_footnote(ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'q') => {
+                        if state.edit.is_focused() {
+                            state.toggle_blockquote(true, ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'u') => {
+                        if state.edit.is_focused() {
+                            state.toggle_blockquote(false, ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    // most terminals send ctrl-/ as ctrl-7
+                    ct_event!(key press CONTROL-'/') | ct_event!(key press CONTROL-'7') => {
+                        if state.edit.is_focused() {
+                            state.toggle_comment(ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL-'u') => {
+                        if state.edit.is_focused() {
+                            state.change_case(CaseMode::Upper, ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_SHIFT-'U') => {
+                        if state.edit.is_focused() {
+                            state.change_case(CaseMode::Lower, ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'t') => {
+                        if state.edit.is_focused() {
+                            state.change_case(CaseMode::Title, ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'d') => {
+                        if state.edit.is_focused() {
+                            state.insert_date(false, ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press ALT_SHIFT-'W') => {
+                        if state.edit.is_focused() {
+                            state.toggle_file_wrap(ctx)
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press ALT_SHIFT-'N') => {
+                        if state.edit.is_focused() {
+                            state.toggle_file_linenr(ctx)
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(keycode press F(3)) => {
+                        if state.edit.is_focused() {
+                            state.search_next(false, ctx)
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(keycode press SHIFT-F(3)) => {
+                        if state.edit.is_focused() {
+                            state.search_next(true, ctx)
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'l') => {
+                        if state.edit.is_focused() {
+                            state.recenter(ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press ALT-'/') => {
+                        if state.edit.is_focused() {
+                            state.complete_word(ctx)?
+                        } else {
+                            Control::Continue
+                        }
+                    }
+                    ct_event!(key press CONTROL_ALT-'j') => {
+                        if state.edit.is_focused() {
+                            state.jump_footnote(ctx)?
                         } else {
                             Control::Continue
                         }
@@ -241,80 +979,2695 @@ pub fn event(
             }
         }
         MDEvent::MenuFormat => {
+            try_flow!(
+                if state.edit.is_focused() && !ctx.cfg.format_command.is_empty() {
+                    state.external_format(ctx)?
+                } else if state.edit.is_focused() {
+                    state.reformat(ctx.cfg.format_mode, ctx)?
+                } else {
+                    Control::Continue
+                }
+            );
+        }
+        MDEvent::MenuFormatEq => {
             try_flow!(if state.edit.is_focused() {
-                state.reformat(false, ctx)?
+                state.reformat(FormatMode::ReflowEqWidth, ctx)?
             } else {
                 Control::Continue
             });
         }
-        MDEvent::MenuFormatEq => {
+        MDEvent::MenuFormatKeepBreaks => {
             try_flow!(if state.edit.is_focused() {
-                state.reformat(true, ctx)?
+                state.reformat(FormatMode::KeepBreaks, ctx)?
             } else {
                 Control::Continue
             });
         }
-        MDEvent::CfgShowCtrl => {
-            try_flow!({
-                state.edit.set_show_ctrl(ctx.cfg.show_ctrl);
-                Control::Changed
-            });
-        }
+        MDEvent::CopyCodeBlock => {
+            try_flow!(if state.edit.is_focused() {
+                state.copy_code_block(ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::RunCodeBlock => {
+            try_flow!(if state.edit.is_focused() {
+                state.run_code_block(ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        #[cfg(feature = "scripting")]
+        MDEvent::MenuRunScript => {
+            try_flow!(if state.edit.is_focused() {
+                state.script_dialog(ctx)
+            } else {
+                Control::Continue
+            });
+        }
+        #[cfg(feature = "scripting")]
+        MDEvent::RunScript(name) => {
+            try_flow!(if state.edit.is_focused() {
+                state.run_script(name, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::CodeBlockOutput(path, output) => {
+            try_flow!(if state.path == *path && state.run_block.is_some() {
+                state.insert_code_output(output, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::FormatOutput(path, output) => {
+            try_flow!(if state.path == *path && state.run_format.is_some() {
+                state.apply_format_output(output, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::ConvertLinkPaths(mode) => {
+            try_flow!(if state.edit.is_focused() {
+                state.convert_link_paths(*mode, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::NormalizeLinks(style) => {
+            try_flow!(if state.edit.is_focused() {
+                let r: Control<MDEvent> = state
+                    .doc_type
+                    .normalize_links(&mut state.edit, *style)
+                    .into();
+                r.and_then(|| {
+                    state.update_cursor_pos(ctx);
+                    state.text_changed(ctx)
+                })
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::AlignTable(align) => {
+            try_flow!(if state.edit.is_focused() {
+                state.align_table(*align, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::SmartPunct => {
+            try_flow!(if state.edit.is_focused() {
+                state.smart_punct_selection(ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::ConvertHeading => {
+            try_flow!(if state.edit.is_focused() {
+                state.convert_heading_style(ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::EscapeMd => {
+            try_flow!(if state.edit.is_focused() {
+                state.escape_selection(ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::UnescapeMd => {
+            try_flow!(if state.edit.is_focused() {
+                state.unescape_selection(ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::ToggleBlockquote(add) => {
+            try_flow!(if state.edit.is_focused() {
+                state.toggle_blockquote(*add, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::ToggleComment => {
+            try_flow!(if state.edit.is_focused() {
+                state.toggle_comment(ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::WrapCodeFence(lang) => {
+            try_flow!(if state.edit.is_focused() {
+                state.wrap_code_fence(lang, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::ChangeCase(mode) => {
+            try_flow!(if state.edit.is_focused() {
+                state.change_case(*mode, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::InsertDate(with_time) => {
+            try_flow!(if state.edit.is_focused() {
+                state.insert_date(*with_time, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::PasteCodeBlock(lang) => {
+            try_flow!(if state.edit.is_focused() {
+                state.paste_code_block(lang, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::PasteText(txt) => {
+            try_flow!(if state.edit.is_focused() {
+                state.paste_text(txt, ctx)
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::PasteLarge(txt, no_confirm) => {
+            try_flow!(if state.edit.is_focused() {
+                if *no_confirm {
+                    ctx.paste_no_confirm = true;
+                }
+                match state.paste_with(txt.clone(), ctx)? {
+                    Control::Continue => state.paste_text(txt, ctx),
+                    r => r,
+                }
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::LinkTargets(root, _) => {
+            // the targets are cached by now.
+            let waiting = state
+                .completion
+                .as_ref()
+                .filter(|v| v.kind == CompletionKind::Link && v.selected().is_none())
+                .map(|v| v.start);
+            try_flow!(match waiting {
+                Some(start) if state.link_root() == *root => {
+                    state.open_link_completion(start, ctx)?;
+                    state.update_completion(None, ctx)?;
+                    Control::Changed
+                }
+                _ => Control::Continue,
+            });
+        }
+        MDEvent::ImageInfo(info) => {
+            try_flow!({
+                let links = state
+                    .image_links()
+                    .into_iter()
+                    .filter_map(|(_, _, path)| path)
+                    .collect::<HashSet<_>>();
+                let mut r = Control::Continue;
+                for (path, info) in info.iter().filter(|(v, _)| links.contains(v)) {
+                    state.images.insert(path.clone(), *info);
+                    r = Control::Changed;
+                }
+                r
+            });
+        }
+        MDEvent::Search(term) => {
+            try_flow!({
+                ctx.search = term.clone();
+                ctx.hl_search = true;
+                state.update_search_hl(ctx);
+                if state.edit.is_focused() {
+                    state.search_next(false, ctx)
+                } else {
+                    Control::Changed
+                }
+            });
+        }
+        MDEvent::MenuCountTerm(project) => {
+            try_flow!(if state.edit.is_focused() {
+                state.count_dialog(*project, ctx)
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::CountTerm(term, project) => {
+            try_flow!(if state.edit.is_focused() {
+                state.count_term(term, *project, ctx)?
+            } else {
+                Control::Continue
+            });
+        }
+        MDEvent::SearchHl => {
+            try_flow!({
+                state.update_search_hl(ctx);
+                Control::Changed
+            });
+        }
+        MDEvent::CfgMdFlavor => {
+            try_flow!({
+                state.md_options = ctx.cfg.md_flavor.0;
+                state.parse();
+                state.update_search_hl(ctx);
+                Control::Changed
+            });
+        }
+        MDEvent::CfgShowCtrl => {
+            try_flow!({
+                state.edit.set_show_ctrl(ctx.cfg.show_ctrl);
+                Control::Changed
+            });
+        }
         MDEvent::CfgShowBreak => {
             try_flow!({
                 state.edit.set_wrap_ctrl(ctx.cfg.show_break);
                 Control::Changed
             });
         }
-        MDEvent::CfgShowLinenr => {
-            try_flow!({
-                state.show_linenr = !state.show_linenr;
-                Control::Changed
-            });
+        MDEvent::CfgShowLinenr => {
+            try_flow!({
+                state.show_linenr = state.view.show_linenr.unwrap_or(ctx.cfg.show_linenr);
+                Control::Changed
+            });
+        }
+        MDEvent::CfgTabWidth => {
+            try_flow!({
+                state.edit.set_tab_width(tab_width(ctx));
+                Control::Changed
+            });
+        }
+        MDEvent::CfgWrapText => {
+            try_flow!({
+                let wrap = state.view.wrap_text.unwrap_or(ctx.cfg.wrap_text);
+                state.edit.set_text_wrap(if wrap {
+                    TextWrap::Word(8)
+                } else {
+                    TextWrap::Shift
+                });
+                Control::Changed
+            });
+        }
+        _ => {}
+    }
+
+    Ok(Control::Continue)
+}
+
+/// Word around the range.
+fn word_range(text: &str, range: Range<usize>) -> Range<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let start = text[..range.start]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map(|(idx, _)| idx)
+        .unwrap_or(range.start);
+    let end = text[range.end..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map(|(idx, _)| range.end + idx)
+        .unwrap_or(text.len());
+    start..end
+}
+
+/// Sentence in the paragraph around the range.
+fn sentence_range(text: &str, para: Range<usize>, range: Range<usize>) -> Range<usize> {
+    let para_txt = &text[para.clone()];
+    let mut start = para.start;
+    let mut end = para.end;
+    let mut chars = para_txt.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') && chars.peek().is_some_and(|(_, v)| v.is_whitespace()) {
+            let pos = para.start + idx + 1;
+            if pos <= range.start {
+                // skip the whitespace
+                start = pos + para_txt[idx + 1..].len() - para_txt[idx + 1..].trim_start().len();
+            } else if pos >= range.end {
+                end = pos;
+                break;
+            }
+        }
+    }
+    start..end.max(start)
+}
+
+/// Paste above the configured size?
+fn is_large_paste(txt: &str, ctx: &GlobalState) -> bool {
+    ctx.cfg.paste_confirm_size > 0
+        && !ctx.paste_no_confirm
+        && txt.len() > ctx.cfg.paste_confirm_size * 1024
+}
+
+/// Ask before pasting a large text.
+fn confirm_paste(txt: String, ctx: &mut GlobalState) -> Control<MDEvent> {
+    let msg = format!(
+        "Paste {} KiB, {} lines?",
+        txt.len().div_ceil(1024),
+        txt.lines().count()
+    );
+    let state = ChoiceDialogState::new("Large paste", msg.as_str())
+        .choice("Paste", MDEvent::PasteLarge(txt.clone(), false))
+        .choice("Always paste", MDEvent::PasteLarge(txt, true))
+        .choice("Cancel", MDEvent::NoOp);
+    ctx.dialogs
+        .push(choice_dlg::render, choice_dlg::event, state);
+    Control::Changed
+}
+
+/// Plain char typed, maybe with shift.
+fn typed_char(event: &ratatui::crossterm::event::Event) -> Option<char> {
+    match event {
+        ratatui::crossterm::event::Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) if (*modifiers - KeyModifiers::SHIFT).is_empty() => Some(*c),
+        _ => None,
+    }
+}
+
+/// Tab-stops are configurable for wgpu only.
+fn tab_width(ctx: &GlobalState) -> u16 {
+    #[cfg(all(feature = "wgpu", not(feature = "term")))]
+    {
+        ctx.cfg.tab_width
+    }
+    #[cfg(not(all(feature = "wgpu", not(feature = "term"))))]
+    {
+        _ = ctx;
+        4
+    }
+}
+
+/// Size of the chunk that is checked for binary content.
+const SNIFF_LEN: u64 = 8192;
+
+/// NUL bytes or invalid UTF-8 in the first chunk.
+fn is_binary(file: &mut File) -> Result<bool, Error> {
+    let mut buf = Vec::new();
+    file.by_ref().take(SNIFF_LEN).read_to_end(&mut buf)?;
+    file.rewind()?;
+
+    if buf.contains(&0) {
+        return Ok(true);
+    }
+    match std::str::from_utf8(&buf) {
+        Ok(_) => Ok(false),
+        // a char cut off at the end of the chunk is fine
+        Err(e) => Ok(e.error_len().is_some()),
+    }
+}
+
+/// Parent directory of the file if it doesn't exist.
+pub fn missing_parent(path: &Path) -> Option<&Path> {
+    let parent = path.parent()?;
+    if !parent.as_os_str().is_empty() && !parent.exists() {
+        Some(parent)
+    } else {
+        None
+    }
+}
+
+/// Copy the current file content to a backup.
+/// Keeps a single `.bak` or a number of timestamped copies.
+pub fn backup(path: &Path, count: usize) -> Result<(), Error> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    if count <= 1 {
+        fs::copy(path, path.with_file_name(format!("{}.bak", file_name)))?;
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("fine")
+        .as_secs();
+    fs::copy(
+        path,
+        path.with_file_name(format!("{}.{}.bak", file_name, now)),
+    )?;
+
+    // prune
+    let Some(dir) = path.parent() else {
+        return Ok(());
+    };
+    let prefix = format!("{}.", file_name);
+    let mut backups = Vec::new();
+    for f in fs::read_dir(dir)? {
+        let f = f?;
+        let name = f.file_name().to_string_lossy().to_string();
+        let Some(stamp) = name
+            .strip_prefix(prefix.as_str())
+            .and_then(|v| v.strip_suffix(".bak"))
+        else {
+            continue;
+        };
+        if let Ok(stamp) = stamp.parse::<u64>() {
+            backups.push((stamp, f.path()));
+        }
+    }
+    backups.sort();
+    while backups.len() > count {
+        let (_, old) = backups.remove(0);
+        fs::remove_file(old)?;
+    }
+
+    Ok(())
+}
+
+/// Write to a sibling temp file and rename it over the original.
+/// The original stays untouched if anything fails before the rename.
+pub fn write_atomic(path: &Path, buf: &[u8]) -> Result<(), Error> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.mdedit-tmp", file_name));
+
+    let write_tmp = || -> Result<(), Error> {
+        let mut f = BufWriter::new(File::create(&tmp_path)?);
+        f.write_all(buf)?;
+        let f = f.into_inner().map_err(|e| e.into_error())?;
+        f.sync_all()?;
+        if let Ok(meta) = fs::metadata(path) {
+            fs::set_permissions(&tmp_path, meta.permissions())?;
+        }
+        Ok(())
+    };
+    if let Err(e) = write_tmp() {
+        _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        // rename can fail across devices or for some network drives.
+        // fall back to copying the content.
+        warn!("rename failed {:?}, copy instead", e);
+        let r = fs::copy(&tmp_path, path);
+        _ = fs::remove_file(&tmp_path);
+        r?;
+    }
+    Ok(())
+}
+
+/// Label of a footnote reference/definition.
+fn footnote_label(txt: &str) -> Option<&str> {
+    let start = txt.find("[^")? + 2;
+    let end = txt[start..].find(']')? + start;
+    Some(&txt[start..end])
+}
+
+/// All the heading styles.
+fn heading_styles() -> [usize; 6] {
+    [
+        MDStyle::Heading1.into(),
+        MDStyle::Heading2.into(),
+        MDStyle::Heading3.into(),
+        MDStyle::Heading4.into(),
+        MDStyle::Heading5.into(),
+        MDStyle::Heading6.into(),
+    ]
+}
+
+/// Delimiter row of a table.
+fn is_table_sep(line: &str) -> bool {
+    let line = line.trim();
+    line.contains('|')
+        && line.contains('-')
+        && line
+            .chars()
+            .all(|c| matches!(c, '|' | '-' | ':' | ' ' | '\t'))
+}
+
+/// Byte position of the cell in a delimiter row. Cells are
+/// counted by the pipes before them.
+fn sep_cell_pos(line: &str, cell: usize) -> usize {
+    let start = if cell > 0 {
+        match line.match_indices('|').nth(cell - 1) {
+            Some((idx, _)) => idx + 1,
+            None => line.trim_end().len(),
+        }
+    } else {
+        0
+    };
+    let rest = &line[start..];
+    start + rest.len() - rest.trim_start_matches([' ', '\t']).len()
+}
+
+impl MDFileState {
+    /// Nearest heading above the first visible line.
+    /// Uses the styles from the last parse.
+    pub fn sticky_heading(&self) -> Option<(usize, String)> {
+        if self.doc_type != DocTypes::MD {
+            return None;
+        }
+        let top = self.edit.vertical_offset() as upos_type;
+        if top == 0 {
+            return None;
+        }
+        let top_byte = self.edit.byte_at(TextPosition::new(0, top)).start;
+
+        let headings = heading_styles();
+        let mut found: Option<(usize, usize)> = None;
+        for (range, style) in self.edit.styles() {
+            if range.start < top_byte
+                && headings.contains(&style)
+                && found.is_none_or(|(start, _)| start < range.start)
+            {
+                found = Some((range.start, style));
+            }
+        }
+
+        let (start, style) = found?;
+        let pos = self.edit.byte_pos(start);
+        let txt = self
+            .edit
+            .line_at(pos.y)
+            .trim_start_matches('#')
+            .trim()
+            .to_string();
+        Some((style, txt))
+    }
+
+    /// Reformat
+    fn reformat(
+        &mut self,
+        mode: FormatMode,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        // formatting with a selection keeps the selection.
+        let anchor = if !self.edit.has_selection() {
+            Some(self.cursor_anchor())
+        } else {
+            None
+        };
+
+        let mut r: Control<MDEvent> = self
+            .doc_type
+            .format(
+                &mut self.edit,
+                ctx.cfg.text_width,
+                mode,
+                ctx.cfg.table_align,
+            )
+            .into();
+        r = r.and_then(|| {
+            if let Some(anchor) = anchor {
+                self.restore_anchor(anchor);
+            }
+            self.update_cursor_pos(ctx);
+            self.text_changed(ctx)
+        });
+        Ok(r)
+    }
+
+    /// Align the table at the cursor and re-pad the columns.
+    /// One undo step.
+    fn align_table(
+        &mut self,
+        align: TableAlign,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        self.edit.begin_undo_seq();
+        let r = match self.doc_type.align_table(&mut self.edit, align) {
+            TextOutcome::TextChanged => self.reformat(ctx.cfg.format_mode, ctx).map(|_| {
+                self.update_cursor_pos(ctx);
+                self.text_changed(ctx)
+            }),
+            r => Ok(r.into()),
+        };
+        self.edit.end_undo_seq();
+        r
+    }
+
+    /// Text of the line without the line break.
+    fn line_str(&self, y: upos_type) -> String {
+        let start = self.edit.byte_at(TextPosition::new(0, y)).start;
+        let end = self
+            .edit
+            .byte_at(TextPosition::new(self.edit.line_width(y), y))
+            .start;
+        self.edit.str_slice_byte(start..end).to_string()
+    }
+
+    /// The line starts a list item.
+    fn is_list_item(&self, y: upos_type) -> bool {
+        self.doc_type == DocTypes::MD
+            && !self.in_code(self.edit.byte_at(TextPosition::new(0, y)).start)
+            && md_list::item_marker(&self.line_str(y)).is_some()
+    }
+
+    /// Line break that keeps the leading whitespace of the line.
+    /// List items, blockquotes and tables are left to the
+    /// markdown handling.
+    fn auto_indent(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let cursor = self.edit.cursor();
+        if self.is_list_item(cursor.y) {
+            return Ok(Control::Continue);
+        }
+        let line = self.line_str(cursor.y);
+        let body = line.trim_start();
+        if body.starts_with(['>', '|']) {
+            return Ok(Control::Continue);
+        }
+
+        // only the part before the cursor
+        let indent = line
+            .chars()
+            .take(line.len() - body.len())
+            .take(cursor.x as usize)
+            .collect::<String>();
+        if indent.is_empty() {
+            return Ok(Control::Continue);
+        }
+
+        self.edit.begin_undo_seq();
+        self.edit.insert_newline();
+        self.edit.insert_str(indent);
+        self.edit.end_undo_seq();
+
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Tab without a selection. Tries the actions in the
+    /// order of the tab_order config.
+    fn tab_key(
+        &mut self,
+        event: &ratatui::crossterm::event::Event,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let cursor = self.edit.cursor();
+        for action in ctx.cfg.tab_order.clone() {
+            match action {
+                TabAction::Completion
+                    if self
+                        .completion
+                        .as_ref()
+                        .is_some_and(|v| v.selected().is_some()) =>
+                {
+                    return self.accept_completion(ctx);
+                }
+                TabAction::Table if self.in_table(self.edit.byte_at(cursor).start) => {
+                    // table navigation is part of the markdown handling
+                    let r = self.edit.handle(event, MarkDown::new(ctx.cfg.text_width));
+                    self.update_cursor_pos(ctx);
+                    return Ok(match r {
+                        TextOutcome::TextChanged => self.text_changed(ctx),
+                        r => r.into(),
+                    });
+                }
+                TabAction::List if self.is_list_item(cursor.y) => {
+                    return self.indent_list_item(false, ctx);
+                }
+                TabAction::Literal => {
+                    self.completion = None;
+                    self.edit.insert_tab();
+                    self.edit.scroll_cursor_to_visible();
+                    self.update_cursor_pos(ctx);
+                    return Ok(self.text_changed(ctx));
+                }
+                _ => {}
+            }
+        }
+        Ok(Control::Unchanged)
+    }
+
+    /// Indent/outdent the list item at the cursor together with
+    /// its nested lines. Ordered lists are renumbered.
+    fn indent_list_item(
+        &mut self,
+        outdent: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let cursor = self.edit.cursor();
+        let Some(marker) = md_list::item_marker(&self.line_str(cursor.y)) else {
+            return Ok(Control::Continue);
+        };
+
+        // the list ends at blank lines
+        let mut y0 = cursor.y;
+        while y0 > 0 && !self.line_str(y0 - 1).trim().is_empty() {
+            y0 -= 1;
+        }
+        let mut y1 = cursor.y;
+        while y1 + 1 < self.edit.len_lines() && !self.line_str(y1 + 1).trim().is_empty() {
+            y1 += 1;
+        }
+
+        let item = (cursor.y - y0) as usize;
+        let width = ctx.cfg.list_indent as usize;
+        Ok(self.replace_lines(
+            y0,
+            y1,
+            |old| {
+                let mut new = old.to_vec();
+                new[item] = md_list::shift_line(&new[item], width, outdent);
+                for line in new.iter_mut().skip(item + 1) {
+                    let indent = line.len() - line.trim_start().len();
+                    if indent <= marker.indent {
+                        break;
+                    }
+                    *line = md_list::shift_line(line, width, outdent);
+                }
+                md_list::renumber(&mut new, if outdent { &[] } else { &[item] });
+                new
+            },
+            ctx,
+        ))
+    }
+
+    /// Replace the lines y0..=y1 with the result of f as one
+    /// undo step. Cursor and anchor keep their place in the
+    /// changed lines.
+    fn replace_lines(
+        &mut self,
+        y0: upos_type,
+        y1: upos_type,
+        f: impl FnOnce(&[String]) -> Vec<String>,
+        ctx: &mut GlobalState,
+    ) -> Control<MDEvent> {
+        let old = (y0..=y1).map(|y| self.line_str(y)).collect::<Vec<_>>();
+        let new = f(&old);
+        if new == old {
+            return Control::Unchanged;
+        }
+
+        let anchor = self.edit.anchor();
+        let cursor = self.edit.cursor();
+
+        let start = self.edit.byte_at(TextPosition::new(0, y0)).start;
+        let end = self
+            .edit
+            .byte_at(TextPosition::new(self.edit.line_width(y1), y1))
+            .start;
+        let newline = self.edit.newline().to_string();
+        replace_byte_ranges(&mut self.edit, vec![(start..end, new.join(&newline))]);
+
+        let shift = |pos: TextPosition| {
+            if pos.y >= y0 && pos.y <= y1 {
+                let idx = (pos.y - y0) as usize;
+                let delta = new[idx].chars().count() as i64 - old[idx].chars().count() as i64;
+                TextPosition::new((pos.x as i64 + delta).max(0) as upos_type, pos.y)
+            } else {
+                pos
+            }
+        };
+        self.edit.set_selection(shift(anchor), shift(cursor));
+
+        self.update_cursor_pos(ctx);
+        self.text_changed(ctx)
+    }
+
+    /// Switch the heading at the cursor between ATX (`#`) and
+    /// Setext (`===`/`---`) style. Setext only knows level 1 and 2.
+    pub fn convert_heading_style(
+        &mut self,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if self.doc_type != DocTypes::MD {
+            return Ok(Control::Continue);
+        }
+        let pos = self.edit.byte_at(self.edit.cursor()).start;
+        let text = self.edit.text();
+
+        let heading = Parser::new_ext(text.as_str(), self.md_options)
+            .into_offset_iter()
+            .find_map(|(e, range)| match e {
+                Event::Start(Tag::Heading { level, .. })
+                    if range.start <= pos && pos <= range.end =>
+                {
+                    Some((level as usize, range))
+                }
+                _ => None,
+            });
+        let Some((level, range)) = heading else {
+            return Ok(Control::Event(MDEvent::Info("no heading".into())));
+        };
+
+        let src = text[range.clone()].trim_end_matches(['\r', '\n']);
+        let range = range.start..range.start + src.len();
+        let newline = self.edit.newline();
+
+        let new = if src.trim_start().starts_with('#') {
+            if level > 2 {
+                return Ok(Control::Event(MDEvent::Info(
+                    "setext has only level 1 and 2".into(),
+                )));
+            }
+            let title = src.trim_start().trim_start_matches('#').trim();
+            // a closing sequence needs a space before it.
+            let open = title.trim_end_matches('#');
+            let title = if open.is_empty() || open.ends_with([' ', '\t']) {
+                open.trim_end()
+            } else {
+                title
+            };
+            let width = title.chars().count().max(3);
+            let underline = if level == 1 { "=" } else { "-" };
+            format!("{}{}{}", title, newline, underline.repeat(width))
+        } else {
+            let title = src
+                .lines()
+                .take_while(|v| !v.trim_start().starts_with(['=', '-']))
+                .map(|v| v.trim())
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("{} {}", "#".repeat(level), title)
+        };
+
+        replace_byte_ranges(&mut self.edit, vec![(range, new)]);
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Escape markdown chars in the selection.
+    pub fn escape_selection(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let at_line_start = self.edit.selection().start.x == 0;
+        self.map_selection(|v| paste::escape_md(v, at_line_start), ctx)
+    }
+
+    /// Remove markdown escapes in the selection.
+    pub fn unescape_selection(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        self.map_selection(paste::unescape_md, ctx)
+    }
+
+    /// Replace the selected text. The selection covers the
+    /// new text afterwards.
+    fn map_selection(
+        &mut self,
+        f: impl Fn(&str) -> String,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if !self.edit.has_selection() {
+            return Ok(Control::Continue);
+        }
+        let range = self.edit.bytes_at_range(self.edit.selection());
+        let old = self.edit.str_slice_byte(range.clone()).to_string();
+        let new = f(&old);
+        if new == old {
+            return Ok(Control::Unchanged);
+        }
+
+        let end = range.start + new.len();
+        replace_byte_ranges(&mut self.edit, vec![(range.clone(), new)]);
+        let start = self.edit.byte_pos(range.start);
+        let end = self.edit.byte_pos(end);
+        self.edit.set_selection(start, end);
+
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Add or remove one level of blockquote for the selected lines.
+    /// Without a selection this works on the paragraph at the cursor,
+    /// which includes lazy continuation lines.
+    pub fn toggle_blockquote(
+        &mut self,
+        add: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if self.doc_type != DocTypes::MD {
+            return Ok(Control::Continue);
+        }
+
+        let (y0, y1) = if self.edit.has_selection() {
+            let sel = self.edit.selection();
+            if sel.end.x == 0 && sel.end.y > sel.start.y {
+                (sel.start.y, sel.end.y - 1)
+            } else {
+                (sel.start.y, sel.end.y)
+            }
+        } else {
+            let cursor = self.edit.cursor();
+            let mut y0 = cursor.y;
+            while y0 > 0 && !self.line_str(y0 - 1).trim().is_empty() {
+                y0 -= 1;
+            }
+            let mut y1 = cursor.y;
+            while y1 + 1 < self.edit.len_lines() && !self.line_str(y1 + 1).trim().is_empty() {
+                y1 += 1;
+            }
+            (y0, y1)
+        };
+
+        Ok(self.replace_lines(
+            y0,
+            y1,
+            |old| {
+                old.iter()
+                    .map(|line| {
+                        let body = line.trim_start();
+                        let indent = &line[..line.len() - body.len()];
+                        if add {
+                            if body.starts_with('>') {
+                                format!("{}>{}", indent, body)
+                            } else if body.is_empty() {
+                                format!("{}>", indent)
+                            } else {
+                                format!("{}> {}", indent, body)
+                            }
+                        } else if let Some(body) = body.strip_prefix('>') {
+                            // keep nested markers together
+                            let body = if body.starts_with('>') {
+                                body
+                            } else {
+                                body.strip_prefix(' ').unwrap_or(body)
+                            };
+                            format!("{}{}", indent, body)
+                        } else {
+                            // lazy continuation
+                            line.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            },
+            ctx,
+        ))
+    }
+
+    /// Comment out the selected lines or the cursor line,
+    /// or remove the comment.
+    ///
+    /// Inside a fenced code block the line comment of the
+    /// language is used, everywhere else html comments.
+    pub fn toggle_comment(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        if self.doc_type != DocTypes::MD {
+            return Ok(Control::Continue);
+        }
+
+        let (mut y0, mut y1) = if self.edit.has_selection() {
+            let sel = self.edit.selection();
+            if sel.end.x == 0 && sel.end.y > sel.start.y {
+                (sel.start.y, sel.end.y - 1)
+            } else {
+                (sel.start.y, sel.end.y)
+            }
+        } else {
+            let cursor = self.edit.cursor();
+            (cursor.y, cursor.y)
+        };
+
+        let pos = self.edit.byte_at(self.edit.cursor()).start;
+        let line_comment = if let Some(block) = self.doc_type.code_block_at(&self.edit, pos) {
+            let Some(line_comment) = highlight::line_comment(&block.lang) else {
+                return Ok(Control::Event(MDEvent::Info(if block.lang.is_empty() {
+                    "no comments for this code block".into()
+                } else {
+                    format!("no comments for {}", block.lang)
+                })));
+            };
+            // keep the fences.
+            if block.content.is_empty() {
+                return Ok(Control::Unchanged);
+            }
+            let c0 = self.edit.byte_pos(block.content.start).y;
+            let c1 = self.edit.byte_pos(block.content.end - 1).y;
+            y0 = y0.max(c0);
+            y1 = y1.min(c1);
+            if y0 > y1 {
+                return Ok(Control::Unchanged);
+            }
+            Some(line_comment)
+        } else {
+            None
+        };
+
+        Ok(self.replace_lines(
+            y0,
+            y1,
+            |old| {
+                if let Some(line_comment) = line_comment {
+                    let lines = old.iter().filter(|v| !v.trim().is_empty());
+                    let indent = lines
+                        .clone()
+                        .map(|v| v.len() - v.trim_start_matches([' ', '\t']).len())
+                        .min()
+                        .unwrap_or_default();
+                    let commented = lines
+                        .clone()
+                        .all(|v| v.trim_start().starts_with(line_comment));
+                    old.iter()
+                        .map(|line| {
+                            if line.trim().is_empty() {
+                                line.clone()
+                            } else if commented {
+                                let body = line.trim_start();
+                                let indent = &line[..line.len() - body.len()];
+                                let body = &body[line_comment.len()..];
+                                format!("{}{}", indent, body.strip_prefix(' ').unwrap_or(body))
+                            } else {
+                                format!("{}{} {}", &line[..indent], line_comment, &line[indent..])
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                } else {
+                    let mut new = old.to_vec();
+                    let last = new.len() - 1;
+                    let commented = new[0].trim_start().starts_with("<!--")
+                        && new[last].trim_end().ends_with("-->");
+                    if commented {
+                        let body = new[last].trim_end().strip_suffix("-->").expect("comment");
+                        new[last] = body.strip_suffix(' ').unwrap_or(body).to_string();
+                        let line = &new[0];
+                        let body = line.trim_start();
+                        let indent = &line[..line.len() - body.len()];
+                        let body = body.strip_prefix("<!--").expect("comment");
+                        new[0] = format!("{}{}", indent, body.strip_prefix(' ').unwrap_or(body));
+                    } else {
+                        let line = &new[0];
+                        let body = line.trim_start();
+                        new[0] = format!("{}<!-- {}", &line[..line.len() - body.len()], body);
+                        new[last] = format!("{} -->", new[last]);
+                    }
+                    new
+                }
+            },
+            ctx,
+        ))
+    }
+
+    /// Grow the selection to the next enclosing unit.
+    /// word, sentence, markdown element, block, document.
+    pub fn expand_selection(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let sel = self.edit.bytes_at_range(self.edit.selection());
+        // selection changed in between
+        if self.selection_stack.last() != Some(&sel) {
+            self.selection_stack.clear();
+        }
+
+        let text = self.edit.text();
+        let contains =
+            |r: &Range<usize>| r.start <= sel.start && r.end >= sel.end && r.len() > sel.len();
+
+        let mut candidates = Vec::new();
+        candidates.push(word_range(&text, sel.clone()));
+        let para_style: usize = MDStyle::Paragraph.into();
+        if let Some(para) = self
+            .edit
+            .styles()
+            .filter(|(r, style)| *style == para_style && r.start <= sel.start && r.end >= sel.end)
+            .map(|(r, _)| r)
+            .next()
+        {
+            candidates.push(sentence_range(&text, para, sel.clone()));
+        }
+        for (range, _) in self.edit.styles() {
+            candidates.push(range);
+        }
+        candidates.push(0..text.len());
+
+        let Some(next) = candidates
+            .into_iter()
+            .filter(|v| contains(v))
+            .min_by_key(|v| v.len())
+        else {
+            return Ok(Control::Unchanged);
+        };
+
+        if self.selection_stack.is_empty() {
+            self.selection_stack.push(sel);
+        }
+        self.selection_stack.push(next.clone());
+
+        let start = self.edit.byte_pos(next.start);
+        let end = self.edit.byte_pos(next.end);
+        self.edit.set_selection(start, end);
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(Control::Changed)
+    }
+
+    /// Go back to the selection before the last expand_selection().
+    pub fn shrink_selection(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let sel = self.edit.bytes_at_range(self.edit.selection());
+        if self.selection_stack.last() != Some(&sel) || self.selection_stack.len() < 2 {
+            self.selection_stack.clear();
+            return Ok(Control::Unchanged);
+        }
+        self.selection_stack.pop();
+        let prev = self.selection_stack.last().cloned().expect("prev");
+        if self.selection_stack.len() == 1 {
+            self.selection_stack.clear();
+        }
+
+        let start = self.edit.byte_pos(prev.start);
+        let end = self.edit.byte_pos(prev.end);
+        self.edit.set_selection(start, end);
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(Control::Changed)
+    }
+
+    /// Delete the link or code span at the cursor as a whole.
+    /// Continues with the regular word deletion otherwise.
+    fn delete_md_unit(
+        &mut self,
+        backward: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if self.doc_type != DocTypes::MD {
+            return Ok(Control::Continue);
+        }
+        let pos = self.edit.byte_at(self.edit.cursor()).start;
+        // deleting backward works from the end of the construct too.
+        let pos = if backward {
+            if pos == 0 {
+                return Ok(Control::Continue);
+            }
+            pos - 1
+        } else {
+            pos
+        };
+
+        let range = [MDStyle::Link, MDStyle::CodeInline]
+            .into_iter()
+            .find_map(|style| self.edit.styles_at_match(pos, style.into()));
+        let Some(range) = range else {
+            return Ok(Control::Continue);
+        };
+
+        let start = self.edit.byte_pos(range.start);
+        let end = self.edit.byte_pos(range.end);
+        self.edit.begin_undo_seq();
+        self.edit.delete_range(TextRange::new(start, end));
+        self.edit.end_undo_seq();
+
+        self.edit.set_cursor(start, false);
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Selection covers more than one line.
+    fn is_multi_line_selection(&self) -> bool {
+        let sel = self.edit.selection();
+        sel.start.y != sel.end.y
+    }
+
+    /// Indent/outdent all selected lines. The selection is
+    /// kept on the same text.
+    fn indent_lines(
+        &mut self,
+        outdent: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let sel = self.edit.selection();
+        let y0 = sel.start.y;
+        // a selection ending at the start of a line doesn't include it.
+        let y1 = if sel.end.x == 0 && sel.end.y > y0 {
+            sel.end.y - 1
+        } else {
+            sel.end.y
+        };
+
+        let width = if self.is_list_item(y0) {
+            ctx.cfg.list_indent
+        } else {
+            tab_width(ctx)
+        } as usize;
+
+        Ok(self.replace_lines(
+            y0,
+            y1,
+            |old| {
+                old.iter()
+                    .map(|v| md_list::shift_line(v, width, outdent))
+                    .collect()
+            },
+            ctx,
+        ))
+    }
+
+    /// Is the byte position inside a table.
+    fn in_table(&self, byte_pos: usize) -> bool {
+        self.doc_type == DocTypes::MD
+            && self
+                .edit
+                .styles_at_match(byte_pos, MDStyle::Table.into())
+                .is_some()
+    }
+
+    /// Is the byte position inside a code span or block.
+    fn in_code(&self, byte_pos: usize) -> bool {
+        self.edit
+            .styles_at_match(byte_pos, MDStyle::CodeBlock.into())
+            .is_some()
+            || self
+                .edit
+                .styles_at_match(byte_pos, MDStyle::CodeInline.into())
+                .is_some()
+    }
+
+    /// Insert the typed char and replace quotes, dashes and ellipsis.
+    /// The replacement is its own undo step.
+    fn smart_punct(&mut self, c: char, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let cursor = self.edit.cursor();
+        let pos = self.edit.byte_at(cursor).start;
+        if self.doc_type != DocTypes::MD || self.in_code(pos) {
+            return Ok(Control::Continue);
+        }
+
+        self.edit.insert_char(c);
+
+        let line_start = self.edit.byte_at(TextPosition::new(0, cursor.y)).start;
+        let end = pos + c.len_utf8();
+        let before = self.edit.str_slice_byte(line_start..end).to_string();
+        if let Some((n, new)) = smart_punct::typed(&before, c) {
+            let start = self.edit.byte_pos(end - n);
+            let end = self.edit.byte_pos(end);
+            self.edit.begin_undo_seq();
+            self.edit.delete_range(TextRange::new(start, end));
+            self.edit.insert_str(new);
+            self.edit.end_undo_seq();
+        }
+
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Replace quotes, dashes and ellipsis in the selection.
+    /// Code is left alone.
+    fn smart_punct_selection(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        self.map_selection_skip_code(smart_punct::convert, ctx)
+    }
+
+    /// Change the case of the selection. Code is left alone.
+    pub fn change_case(
+        &mut self,
+        mode: CaseMode,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        self.map_selection_skip_code(|v| text_case::change_case(v, mode), ctx)
+    }
+
+    /// Replace the selected text outside of code spans and blocks.
+    /// The selection covers the new text afterwards.
+    fn map_selection_skip_code(
+        &mut self,
+        f: impl Fn(&str) -> String,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if !self.edit.has_selection() {
+            return Ok(Control::Continue);
+        }
+        let sel = self.edit.bytes_at_range(self.edit.selection());
+
+        // split at code
+        let code_block: usize = MDStyle::CodeBlock.into();
+        let code_inline: usize = MDStyle::CodeInline.into();
+        let mut code = self
+            .edit
+            .styles()
+            .filter(|(range, style)| {
+                (*style == code_block || *style == code_inline)
+                    && range.start < sel.end
+                    && range.end > sel.start
+            })
+            .map(|(range, _)| range)
+            .collect::<Vec<_>>();
+        code.sort_by_key(|v| v.start);
+
+        let mut replace = Vec::new();
+        let mut sel_end = sel.end;
+        let mut start = sel.start;
+        for range in code.into_iter().chain([sel.end..sel.end]) {
+            let end = range.start.clamp(start, sel.end);
+            if start < end {
+                let text = self.edit.str_slice_byte(start..end);
+                let new = f(text.as_ref());
+                if new != text {
+                    sel_end = sel_end + new.len() - text.len();
+                    replace.push((start..end, new));
+                }
+            }
+            start = start.max(range.end);
+        }
+        if replace.is_empty() {
+            return Ok(Control::Unchanged);
+        }
+        replace_byte_ranges(&mut self.edit, replace);
+        let start = self.edit.byte_pos(sel.start);
+        let end = self.edit.byte_pos(sel_end);
+        self.edit.set_selection(start, end);
+
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Count of non-whitespace chars before the cursor.
+    /// Formatting mostly moves whitespace around, so this
+    /// identifies the same logical position afterwards.
+    /// Table separator rows change their width, they are
+    /// not counted. If the cursor is on one, the cell index
+    /// is kept instead.
+    fn cursor_anchor(&self) -> (usize, Option<usize>) {
+        let text = self.edit.text();
+        let pos = self.edit.byte_at(self.edit.cursor()).start.min(text.len());
+        let line_start = text[..pos].rfind('\n').map(|v| v + 1).unwrap_or(0);
+        let line_end = text[pos..]
+            .find('\n')
+            .map(|v| pos + v)
+            .unwrap_or(text.len());
+
+        let count = text[..line_start]
+            .split_inclusive('\n')
+            .filter(|v| !is_table_sep(v))
+            .flat_map(|v| v.chars())
+            .filter(|c| !c.is_whitespace())
+            .count();
+        if is_table_sep(&text[line_start..line_end]) {
+            (count, Some(text[line_start..pos].matches('|').count()))
+        } else {
+            let n = text[line_start..pos]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .count();
+            (count + n, None)
+        }
+    }
+
+    /// Move the cursor back to the position found with cursor_anchor().
+    fn restore_anchor(&mut self, anchor: (usize, Option<usize>)) {
+        let (anchor, cell) = anchor;
+        let text = self.edit.text();
+        let mut count = 0;
+        let mut byte = text.len();
+        let mut line_start = 0;
+        'lines: for line in text.split_inclusive('\n') {
+            if is_table_sep(line) {
+                if let Some(cell) = cell {
+                    if count == anchor {
+                        byte = line_start + sep_cell_pos(line, cell);
+                        break 'lines;
+                    }
+                }
+            } else {
+                for (idx, c) in line.char_indices() {
+                    if c.is_whitespace() {
+                        continue;
+                    }
+                    if count == anchor && cell.is_none() {
+                        byte = line_start + idx;
+                        break 'lines;
+                    }
+                    count += 1;
+                }
+            }
+            line_start += line.len();
+        }
+        let pos = self.edit.byte_pos(byte);
+        self.edit.set_cursor(pos, false);
+        self.edit.scroll_cursor_to_visible();
+    }
+
+    /// Copy the content of the code block at the cursor.
+    fn copy_code_block(&mut self, _ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let pos = self.edit.byte_at(self.edit.cursor());
+        let Some(block) = self.doc_type.code_block_at(&self.edit, pos.start) else {
+            return Ok(Control::Continue);
+        };
+
+        let content = self.edit.str_slice_byte(block.content);
+        if let Some(clip) = self.edit.clipboard() {
+            if clip.set_string(content.as_ref()).is_err() {
+                return Err(anyhow!("Can't copy to the clipboard."));
+            }
+        }
+        Ok(Control::Event(MDEvent::Info("copied code block".into())))
+    }
+
+    /// Run the code block at the cursor with the configured interpreter.
+    fn run_code_block(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let pos = self.edit.byte_at(self.edit.cursor());
+        let Some(block) = self.doc_type.code_block_at(&self.edit, pos.start) else {
+            return Ok(Control::Continue);
+        };
+        if self.run_block.is_some() {
+            return Ok(Control::Event(MDEvent::Info("already running".into())));
+        }
+        let Some(cmd) = ctx.cfg.run_code_for(&block.lang) else {
+            return Err(anyhow!(
+                "Running '{}' is not enabled. Add it to the [run] section of the config.",
+                block.lang
+            ));
+        };
+
+        let (program, args) = command::split(cmd)?;
+        let code = self.edit.str_slice_byte(block.content.clone()).to_string();
+        let path = self.path.clone();
+        let dir = self
+            .path
+            .parent()
+            .map(|v| v.to_path_buf())
+            .unwrap_or_default();
+
+        let run_block = RunBlock {
+            start: block.range.start,
+            fence: self.fence_line(&block),
+            code: code.clone(),
+        };
+        ctx.spawn(move || {
+            // always answer, otherwise run_block is never cleared.
+            let output = match command::run(&program, &args, &dir, code) {
+                Ok(v) if v.status.success() => Ok(String::from_utf8_lossy(&v.stdout).to_string()),
+                Ok(v) => Err(format!(
+                    "{}: {}",
+                    v.status,
+                    String::from_utf8_lossy(&v.stderr).trim_end()
+                )),
+                Err(e) => Err(format!("{:#}", e)),
+            };
+            Ok(Control::Event(MDEvent::CodeBlockOutput(path, output)))
+        })?;
+        self.run_block = Some(run_block);
+
+        Ok(Control::Event(MDEvent::Info("running ...".into())))
+    }
+
+    /// First line of the code block.
+    fn fence_line(&self, block: &CodeBlock) -> String {
+        let start = self.edit.byte_pos(block.range.start);
+        self.line_str(start.y)
+    }
+
+    /// Ask for the script to run. Up/Down cycle through
+    /// the configured scripts.
+    #[cfg(feature = "scripting")]
+    fn script_dialog(&mut self, ctx: &mut GlobalState) -> Control<MDEvent> {
+        let names = ctx
+            .cfg
+            .scripts
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        ctx.dialogs.push(
+            input_dlg::render,
+            input_dlg::event,
+            InputDialogState::new("Run script", "Script", MDEvent::RunScript).with_history(&names),
+        );
+        Control::Changed
+    }
+
+    /// Run a script from the [scripts] section and replace the
+    /// text with its result. One undo step.
+    #[cfg(feature = "scripting")]
+    fn run_script(&mut self, name: &str, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        if self.read_only {
+            return Ok(Control::Event(MDEvent::Info("read-only".into())));
+        }
+        let Some(script) = ctx.cfg.script_for(name.trim()) else {
+            return Err(anyhow!(
+                "No script '{}'. Add it to the [scripts] section of the config.",
+                name
+            ));
+        };
+
+        let text = self.edit.text();
+        let selection = self.edit.selected_text().to_string();
+        let Some(output) = ctx.scripts.run(script, &self.path, &text, &selection)? else {
+            return Ok(Control::Event(MDEvent::Info(format!("{} done", name))));
+        };
+        if output == text {
+            return Ok(Control::Event(MDEvent::Info(format!("{} done", name))));
+        }
+        Ok(self.replace_text(&output, ctx))
+    }
+
+    /// Run the configured format_command on the whole text.
+    fn external_format(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        if self.doc_type != DocTypes::MD {
+            return Ok(Control::Continue);
+        }
+        if self.run_format.is_some() {
+            return Ok(Control::Event(MDEvent::Info("already formatting".into())));
+        }
+
+        let (program, args) = command::split(&ctx.cfg.format_command)?;
+        let text = self.edit.text();
+        let path = self.path.clone();
+        let dir = self
+            .path
+            .parent()
+            .map(|v| v.to_path_buf())
+            .unwrap_or_default();
+
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        ctx.spawn(move || {
+            // always answer, otherwise run_format is never cleared.
+            let result = match command::run(&program, &args, &dir, text) {
+                Ok(v) if v.status.success() => Ok(String::from_utf8_lossy(&v.stdout).to_string()),
+                Ok(v) => Err(String::from_utf8_lossy(&v.stderr).to_string()),
+                Err(e) => Err(format!("{:#}", e)),
+            };
+            Ok(Control::Event(MDEvent::FormatOutput(path, result)))
+        })?;
+        self.run_format = Some(hash);
+
+        Ok(Control::Event(MDEvent::Info("formatting ...".into())))
+    }
+
+    /// Replace the text with the output of the formatter.
+    fn apply_format_output(
+        &mut self,
+        output: &Result<String, String>,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let Some(hash) = self.run_format.take() else {
+            return Ok(Control::Continue);
+        };
+        let output = match output {
+            Ok(v) => v,
+            Err(e) => return Ok(Control::Event(MDEvent::Message(e.clone()))),
+        };
+
+        let text = self.edit.text();
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        if hasher.finish() != hash {
+            return Ok(Control::Event(MDEvent::Info(
+                "text changed while formatting".into(),
+            )));
+        }
+        if *output == text {
+            return Ok(Control::Event(MDEvent::Info("formatted".into())));
+        }
+
+        Ok(self.replace_text(output, ctx))
+    }
+
+    /// Replace the whole text as one undo step. The cursor
+    /// stays near its logical position.
+    pub fn replace_text(&mut self, new: &str, ctx: &mut GlobalState) -> Control<MDEvent> {
+        let len = self.edit.text().len();
+        let anchor = self.cursor_anchor();
+        replace_byte_ranges(&mut self.edit, vec![(0..len, new.to_string())]);
+        self.restore_anchor(anchor);
+
+        self.update_cursor_pos(ctx);
+        self.text_changed(ctx)
+    }
+
+    /// Insert the output of a code block after the block.
+    fn insert_code_output(
+        &mut self,
+        output: &Result<String, String>,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let Some(run_block) = self.run_block.take() else {
+            return Ok(Control::Continue);
+        };
+        let output = match output {
+            Ok(v) => v,
+            Err(e) => return Ok(Control::Event(MDEvent::Message(e.clone()))),
+        };
+        // the block closest to where it was.
+        let Some(block) = self
+            .doc_type
+            .code_blocks(&self.edit)
+            .into_iter()
+            .filter(|v| {
+                self.fence_line(v) == run_block.fence
+                    && self.edit.str_slice_byte(v.content.clone()).as_ref() == run_block.code
+            })
+            .min_by_key(|v| v.range.start.abs_diff(run_block.start))
+        else {
+            return Err(anyhow!("Code block vanished while running."));
+        };
+
+        let mut fence = "```".to_string();
+        while output.contains(fence.as_str()) {
+            fence.push('`');
+        }
+        let mut insert = String::new();
+        let end = self.edit.byte_pos(block.range.end);
+        if end.x != 0 {
+            insert.push_str(self.edit.newline());
+        }
+        insert.push_str(self.edit.newline());
+        insert.push_str(&fence);
+        insert.push_str("output");
+        insert.push_str(self.edit.newline());
+        for line in output.lines() {
+            insert.push_str(line);
+            insert.push_str(self.edit.newline());
+        }
+        insert.push_str(&fence);
+        insert.push_str(self.edit.newline());
+
+        self.edit.begin_undo_seq();
+        self.edit.set_cursor(end, false);
+        self.edit.insert_str(insert);
+        self.edit.end_undo_seq();
+
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Paste the clipboard, ask first if it's large.
+    fn smart_paste(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let Some(clip) = self.edit.clipboard() else {
+            return Ok(Control::Continue);
+        };
+        let Ok(txt) = clip.get_string() else {
+            return Ok(Control::Continue);
+        };
+        if is_large_paste(&txt, ctx) {
+            return Ok(confirm_paste(txt, ctx));
+        }
+        self.paste_with(txt, ctx)
+    }
+
+    /// Paste urls as link to the selection and convert html.
+    /// Anything else is left to the regular paste.
+    fn paste_with(
+        &mut self,
+        txt: String,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        // continue the indent of the current line
+        let indent = if ctx.cfg.paste_indent && self.doc_type == DocTypes::MD {
+            let start = self.edit.selection().start;
+            let line_start = self.edit.byte_at(TextPosition::new(0, start.y)).start;
+            let cursor = self.edit.byte_at(start).start;
+            paste::continuation_indent(self.edit.str_slice_byte(line_start..cursor).as_ref())
+        } else {
+            String::new()
+        };
+
+        let insert = if ctx.cfg.paste_wrap_url && self.edit.has_selection() && paste::is_url(&txt) {
+            format!("[{}]({})", self.edit.selected_text(), txt.trim())
+        } else if ctx.cfg.paste_html
+            && self.doc_type == DocTypes::MD
+            && !self.in_code(self.edit.byte_at(self.edit.selection().start).start)
+            && paste::is_html(&txt)
+        {
+            paste::html_to_md(&txt)
+        } else if ctx.cfg.paste_format_table && self.doc_type == DocTypes::MD && txt.contains('|') {
+            txt
+        } else if !indent.is_empty() && txt.contains('\n') {
+            txt
+        } else {
+            return Ok(Control::Continue);
+        };
+        let insert = paste::indent_continuation(&insert, &indent);
+
+        let paste_start = self.edit.selection().start;
+        self.edit.begin_undo_seq();
+        self.replace_selection(&insert);
+        if ctx.cfg.paste_format_table {
+            self.format_pasted_table(paste_start, ctx);
+        }
+        self.edit.end_undo_seq();
+
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Reformat a table touched by a paste.
+    /// The cursor stays at the end of the paste.
+    fn format_pasted_table(&mut self, paste_start: TextPosition, ctx: &mut GlobalState) {
+        // need current styles
+        self.parse();
+
+        let paste_end = self.edit.cursor();
+        let end_byte = self.edit.byte_at(paste_end).start;
+        let start_byte = self.edit.byte_at(paste_start).start;
+
+        let table_style: usize = MDStyle::Table.into();
+        let in_table = [start_byte, end_byte.saturating_sub(1)]
+            .into_iter()
+            .find(|v| self.edit.styles_at_match(*v, table_style).is_some());
+        let Some(table_byte) = in_table else {
+            return;
+        };
+
+        self.edit.set_cursor(self.edit.byte_pos(table_byte), false);
+        self.doc_type.format(
+            &mut self.edit,
+            ctx.cfg.text_width,
+            FormatMode::Reflow,
+            ctx.cfg.table_align,
+        );
+
+        let width = self.edit.line_width(paste_end.y);
+        self.edit.set_cursor(
+            TextPosition::new(paste_end.x.min(width), paste_end.y),
+            false,
+        );
+    }
+
+    /// Wrap the selection in a fenced code block. Without a
+    /// selection an empty block is inserted with the cursor inside.
+    pub fn wrap_code_fence(
+        &mut self,
+        lang: &str,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let txt = self.edit.selected_text().to_string();
+
+        // longer than any backtick run inside
+        let mut run = 0;
+        let mut max_run = 0;
+        for c in txt.chars() {
+            if c == '`' {
+                run += 1;
+                max_run = max_run.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        let fence = "`".repeat((max_run + 1).max(3));
+
+        let newline = self.edit.newline().to_string();
+        let sel = self.edit.selection();
+        let mut insert = String::new();
+        if sel.start.x != 0 {
+            insert.push_str(&newline);
+        }
+        insert.push_str(&fence);
+        insert.push_str(lang.trim());
+        insert.push_str(&newline);
+        let content_start = insert.len();
+        insert.push_str(&txt);
+        if !txt.ends_with('\n') {
+            insert.push_str(&newline);
+        }
+        insert.push_str(&fence);
+        insert.push_str(&newline);
+
+        let start = self.edit.byte_at(sel.start).start;
+        self.edit.begin_undo_seq();
+        self.replace_selection(&insert);
+        self.edit.end_undo_seq();
+        if txt.is_empty() {
+            self.edit
+                .set_cursor(self.edit.byte_pos(start + content_start), false);
+        }
+
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Paste the clipboard as fenced code block.
+    fn paste_code_block(
+        &mut self,
+        lang: &str,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let Some(clip) = self.edit.clipboard() else {
+            return Ok(Control::Continue);
+        };
+        let Ok(txt) = clip.get_string() else {
+            return Ok(Control::Continue);
+        };
+
+        let mut fence = "```".to_string();
+        while txt.contains(fence.as_str()) {
+            fence.push('`');
+        }
+
+        let newline = self.edit.newline().to_string();
+        let mut insert = String::new();
+        if self.edit.selection().start.x != 0 {
+            insert.push_str(&newline);
+        }
+        insert.push_str(&fence);
+        insert.push_str(lang.trim());
+        insert.push_str(&newline);
+        for line in txt.lines() {
+            insert.push_str(line);
+            insert.push_str(&newline);
+        }
+        insert.push_str(&fence);
+        insert.push_str(&newline);
+
+        self.edit.begin_undo_seq();
+        self.replace_selection(&insert);
+        self.edit.end_undo_seq();
+
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Insert the current date with the configured format.
+    fn insert_date(
+        &mut self,
+        with_time: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        let format = if with_time {
+            &ctx.cfg.datetime_format
+        } else {
+            &ctx.cfg.date_format
+        };
+        let date = match date::now(format, with_time, ctx.cfg.loc) {
+            Ok(v) => v,
+            Err(e) => return Ok(Control::Event(MDEvent::Message(format!("{:#}", e)))),
+        };
+        Ok(self.paste_text(&date, ctx))
+    }
+
+    /// Paste the text as is.
+    fn paste_text(&mut self, txt: &str, ctx: &mut GlobalState) -> Control<MDEvent> {
+        self.edit.begin_undo_seq();
+        self.replace_selection(txt);
+        self.edit.end_undo_seq();
+
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        self.text_changed(ctx)
+    }
+
+    /// Remember if the left button went down inside the text.
+    fn track_drag(&mut self, event: &ratatui::crossterm::event::Event) {
+        match event {
+            ct_event!(mouse down Left for x,y) | ct_event!(mouse down SHIFT-Left for x,y) => {
+                self.drag_inside = self.edit.inner.contains((*x, *y).into());
+            }
+            ct_event!(mouse up Left for _x,_y) => {
+                self.drag_inside = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Start scrolling when a selection is dragged to the
+    /// top/bottom edge, stop when it leaves the edge.
+    fn drag_scroll(&mut self, pos: (u16, u16), ctx: &mut GlobalState) {
+        let inner = self.edit.inner;
+        if pos.1 <= inner.top() || pos.1 + 1 >= inner.bottom() {
+            if let Some((_, last)) = &mut self.drag_scroll {
+                *last = pos;
+            } else {
+                let timer = ctx.add_timer(
+                    TimerDef::new()
+                        .repeat_forever()
+                        .timer(Duration::from_millis(50)),
+                );
+                self.drag_scroll = Some((timer, pos));
+            }
+        } else {
+            self.stop_drag_scroll(ctx);
+        }
+    }
+
+    /// Stop scrolling. Returns true if it was running.
+    fn stop_drag_scroll(&mut self, ctx: &mut GlobalState) -> bool {
+        if let Some((timer, _)) = self.drag_scroll.take() {
+            ctx.remove_timer(timer);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Scroll one line and extend the selection.
+    fn drag_scroll_step(&mut self, pos: (u16, u16), ctx: &mut GlobalState) -> Control<MDEvent> {
+        let inner = self.edit.inner;
+        if pos.1 <= inner.top() {
+            self.edit.scroll_up(1);
+        } else {
+            self.edit.scroll_down(1);
+        }
+        let x = pos.0.clamp(inner.left(), inner.right().saturating_sub(1));
+        let y = pos.1.clamp(inner.top(), inner.bottom().saturating_sub(1));
+        if let Some(pos) = self.edit.screen_to_pos((x, y)) {
+            self.edit.set_cursor(pos, true);
+        }
+        self.update_cursor_pos(ctx);
+        Control::Changed
+    }
+
+    /// Third click shortly after a double-click.
+    fn is_tripleclick(&self, pos: (u16, u16)) -> bool {
+        self.last_doubleclick
+            .is_some_and(|(t, p)| p == pos && t.elapsed() < Duration::from_millis(500))
+    }
+
+    /// First and last line of the paragraph around the line.
+    /// Paragraphs end at blank lines.
+    fn paragraph_at(&self, y: upos_type) -> (upos_type, upos_type) {
+        let mut y0 = y;
+        while y0 > 0 && !self.line_str(y0 - 1).trim().is_empty() {
+            y0 -= 1;
+        }
+        let mut y1 = y;
+        while y1 + 1 < self.edit.len_lines() && !self.line_str(y1 + 1).trim().is_empty() {
+            y1 += 1;
+        }
+        (y0, y1)
+    }
+
+    /// Collect the table and list styles for cursor_context().
+    fn collect_structure(&mut self) {
+        let structure: [usize; 6] = [
+            MDStyle::Table.into(),
+            MDStyle::TableHead.into(),
+            MDStyle::TableRow.into(),
+            MDStyle::TableCell.into(),
+            MDStyle::List.into(),
+            MDStyle::Item.into(),
+        ];
+        self.structure = self
+            .edit
+            .styles()
+            .filter(|(_, s)| structure.contains(s))
+            .collect();
+        self.structure.sort_by_key(|(r, _)| r.start);
+    }
+
+    /// Table or list around the cursor, from the last parse.
+    ///
+    /// "table 2, row 3, col 2" or "list L2, item 4".
+    pub fn cursor_context(&self) -> String {
+        let byte = self.edit.byte_at(self.edit.cursor()).start;
+        let contains = |r: &Range<usize>| r.start <= byte && byte <= r.end;
+
+        let table_style: usize = MDStyle::Table.into();
+        let head_style: usize = MDStyle::TableHead.into();
+        let row_style: usize = MDStyle::TableRow.into();
+        let cell_style: usize = MDStyle::TableCell.into();
+        let list_style: usize = MDStyle::List.into();
+        let item_style: usize = MDStyle::Item.into();
+
+        // everything that counts starts before the cursor.
+        let styles = &self.structure[..self.structure.partition_point(|(r, _)| r.start <= byte)];
+
+        if let Some(table) = styles
+            .iter()
+            .find(|(r, s)| *s == table_style && contains(r))
+            .map(|(r, _)| r.clone())
+        {
+            let n_table = styles
+                .iter()
+                .filter(|(r, s)| *s == table_style && r.start <= table.start)
+                .count();
+            let Some(row) = styles
+                .iter()
+                .find(|(r, s)| (*s == head_style || *s == row_style) && contains(r))
+                .map(|(r, s)| (r.clone(), *s))
+            else {
+                return format!("table {}", n_table);
+            };
+            let n_col = styles
+                .iter()
+                .filter(|(r, s)| {
+                    *s == cell_style
+                        && r.start >= row.0.start
+                        && r.end <= row.0.end
+                        && r.start <= byte
+                })
+                .count()
+                .max(1);
+            if row.1 == head_style {
+                return format!("table {}, head, col {}", n_table, n_col);
+            }
+            let n_row = styles
+                .iter()
+                .filter(|(r, s)| {
+                    *s == row_style && r.start >= table.start && r.start <= row.0.start
+                })
+                .count();
+            return format!("table {}, row {}, col {}", n_table, n_row, n_col);
+        }
+
+        let lists = styles
+            .iter()
+            .filter(|(r, s)| *s == list_style && contains(r))
+            .map(|(r, _)| r.clone())
+            .collect::<Vec<_>>();
+        let Some(list) = lists.iter().max_by_key(|r| r.start) else {
+            return String::new();
+        };
+        let level = lists.len();
+        // items of the innermost list have the same nesting level.
+        let n_item = styles
+            .iter()
+            .filter(|(r, s)| {
+                *s == item_style
+                    && r.start >= list.start
+                    && r.start <= byte
+                    && styles
+                        .iter()
+                        .filter(|(l, s)| *s == list_style && l.start <= r.start && r.start <= l.end)
+                        .count()
+                        == level
+            })
+            .count();
+        format!("list L{}, item {}", level, n_item)
+    }
+
+    /// Select the paragraph at the cursor.
+    fn select_paragraph(&mut self, ctx: &mut GlobalState) -> Control<MDEvent> {
+        self.last_doubleclick = None;
+
+        let (y0, y1) = self.paragraph_at(self.edit.cursor().y);
+        self.edit.set_selection(
+            TextPosition::new(0, y0),
+            TextPosition::new(self.edit.line_width(y1), y1),
+        );
+        if ctx.cfg.primary_selection {
+            self.update_primary(ctx);
+        }
+        self.update_cursor_pos(ctx);
+        Control::Changed
+    }
+
+    /// Selected text becomes the primary selection.
+    fn update_primary(&self, ctx: &mut GlobalState) {
+        if self.edit.has_selection() {
+            ctx.clipboard
+                .set_primary(self.edit.selected_text().as_ref());
+        }
+    }
+
+    /// Insert the primary selection at the screen position.
+    fn paste_primary(&mut self, pos: (u16, u16), ctx: &mut GlobalState) -> Control<MDEvent> {
+        let Some(pos) = self.edit.screen_to_pos(pos) else {
+            return Control::Continue;
+        };
+        let txt = ctx.clipboard.primary();
+        if txt.is_empty() {
+            return Control::Continue;
+        }
+
+        self.edit.set_cursor(pos, false);
+        self.edit.begin_undo_seq();
+        self.edit.insert_str(&txt);
+        self.edit.end_undo_seq();
+
+        self.update_cursor_pos(ctx);
+        self.text_changed(ctx)
+    }
+
+    /// Replace the selection with the text.
+    fn replace_selection(&mut self, txt: &str) {
+        let sel = self.edit.selection();
+        if self.edit.has_selection() {
+            self.edit.delete_range(sel);
+        }
+        self.edit.set_cursor(sel.start, false);
+        self.edit.insert_str(txt);
+    }
+
+    /// All image links with their alt text and the local path.
+    fn image_links(&self) -> Vec<(Range<usize>, String, Option<PathBuf>)> {
+        let image_style: usize = MDStyle::Image.into();
+        let mut images = Vec::new();
+        for (range, style) in self.edit.styles() {
+            if style != image_style {
+                continue;
+            }
+            let txt = self.edit.str_slice_byte(range.clone());
+            let mut dest = None;
+            let mut alt = String::new();
+            for e in Parser::new_ext(txt.as_ref(), self.md_options) {
+                match e {
+                    Event::Start(Tag::Image { dest_url, .. }) => {
+                        dest = Some(dest_url.to_string());
+                    }
+                    Event::Text(t) | Event::Code(t) => {
+                        alt.push_str(t.as_ref());
+                    }
+                    _ => {}
+                }
+            }
+            let Some(dest) = dest else {
+                continue;
+            };
+            let path = if dest.contains("://") {
+                None
+            } else {
+                self.path.parent().map(|v| v.join(dest))
+            };
+            images.push((range, alt, path));
+        }
+        images
+    }
+
+    /// Collect all links with a url a terminal can open.
+    /// Relative links become file urls if the file exists.
+    fn collect_links(&mut self) {
+        let link_style: usize = MDStyle::Link.into();
+        let mut links = Vec::new();
+        for (range, style) in self.edit.styles() {
+            if style != link_style {
+                continue;
+            }
+            let txt = self.edit.str_slice_byte(range.clone());
+            let dest = Parser::new_ext(txt.as_ref(), self.md_options).find_map(|e| match e {
+                Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url.to_string()),
+                _ => None,
+            });
+            let Some(dest) = dest else {
+                continue;
+            };
+            let url = if dest.contains("://") || dest.starts_with("mailto:") {
+                dest
+            } else {
+                let file = dest.split('#').next().unwrap_or_default();
+                let Some(path) = self.path.parent().map(|v| v.join(file)) else {
+                    continue;
+                };
+                let Ok(path) = path.canonicalize() else {
+                    continue;
+                };
+                format!("file://{}", path.display())
+            };
+            links.push((range, url));
+        }
+        self.links = links;
+    }
+
+    /// Screen areas covered by the byte range, one per row.
+    fn screen_spans(&self, range: Range<usize>) -> Vec<Rect> {
+        let mut spans: Vec<Rect> = Vec::new();
+        let txt = self.edit.str_slice_byte(range.clone());
+        for (idx, c) in txt.char_indices() {
+            let pos = self.edit.byte_pos(range.start + idx);
+            let Some((x, y)) = self.edit.pos_to_screen(pos) else {
+                continue;
+            };
+            // control chars, tabs are at least one cell.
+            let width = c.width().unwrap_or(1) as u16;
+            match spans.last_mut() {
+                Some(last) if last.y == y && x >= last.x => {
+                    last.width = (x + width).max(last.right()) - last.x;
+                }
+                _ => {
+                    if width > 0 {
+                        spans.push(Rect::new(x, y, width, 1));
+                    }
+                }
+            }
+        }
+        spans
+    }
+
+    /// Local image at the cursor.
+    fn image_at_cursor(&self) -> Option<PathBuf> {
+        let cursor = self.edit.byte_at(self.edit.cursor()).start;
+        self.image_links()
+            .into_iter()
+            .find(|(range, _, _)| range.contains(&cursor))
+            .and_then(|(_, _, path)| path)
+    }
+
+    /// Load missing or changed image metadata in the background.
+    fn load_image_info(&mut self, ctx: &mut GlobalState) -> Result<(), Error> {
+        // with the modification time if already known.
+        let check = self
+            .image_links()
+            .into_iter()
+            .filter_map(|(_, _, path)| path)
+            .map(|path| {
+                let modified = self.images.get(&path).map(|v| v.and_then(|v| v.modified));
+                (path, modified)
+            })
+            .collect::<Vec<_>>();
+        if check.is_empty() {
+            return Ok(());
+        }
+
+        ctx.spawn(move || {
+            let info = check
+                .into_iter()
+                .filter(|(v, modified)| *modified != Some(ImageInfo::modified(v)))
+                .map(|(v, _)| {
+                    let info = ImageInfo::load(&v);
+                    (v, info)
+                })
+                .collect::<Vec<_>>();
+            if info.is_empty() {
+                Ok(Control::Continue)
+            } else {
+                Ok(Control::Event(MDEvent::ImageInfo(info)))
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Collect the words of the text for complete_word().
+    /// The word at the cursor is the one being typed, it
+    /// is left out.
+    fn collect_words(&mut self) {
+        self.words.clear();
+        if self.read_only {
+            return;
+        }
+        let cursor = self.edit.byte_at(self.edit.cursor()).start;
+        let text = self.edit.text();
+        let mut start = None;
+        for (idx, c) in text.char_indices().chain([(text.len(), ' ')]) {
+            if c.is_alphanumeric() || c == '_' {
+                start.get_or_insert(idx);
+            } else if let Some(start) = start.take() {
+                let word = &text[start..idx];
+                if !(start..=idx).contains(&cursor) && word.chars().count() >= 3 {
+                    self.words.insert(word.to_string());
+                }
+            }
+        }
+    }
+
+    /// Complete the word before the cursor with a word from the text
+    /// that shares its prefix. Repeated calls replace the inserted
+    /// word with the next candidate.
+    pub fn complete_word(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let pos = self.edit.cursor();
+        let cursor = self.edit.byte_at(pos).start;
+
+        let (prefix, start, last) = match self.dabbrev.take() {
+            Some((prefix, start, last))
+                if start + last.len() == cursor
+                    && self.edit.str_slice_byte(start..cursor) == last.as_str() =>
+            {
+                (prefix, start, Some(last))
+            }
+            _ => {
+                let line_start = self.edit.byte_at(TextPosition::new(0, pos.y)).start;
+                let before = self.edit.str_slice_byte(line_start..cursor).to_string();
+                let len = before
+                    .chars()
+                    .rev()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .map(|c| c.len_utf8())
+                    .sum::<usize>();
+                if len == 0 {
+                    return Ok(Control::Continue);
+                }
+                (before[before.len() - len..].to_string(), cursor - len, None)
+            }
+        };
+
+        if self.words.is_empty() {
+            self.collect_words();
+        }
+        let candidates = self
+            .words
+            .range(prefix.clone()..)
+            .take_while(|v| v.starts_with(prefix.as_str()))
+            .filter(|v| **v != prefix)
+            .cloned()
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Ok(Control::Event(MDEvent::Info(format!(
+                "no completion for {}",
+                prefix
+            ))));
+        }
+
+        let next = last
+            .and_then(|last| candidates.iter().position(|v| *v == last))
+            .map(|idx| (idx + 1) % candidates.len())
+            .unwrap_or(0);
+        let word = candidates[next].clone();
+
+        replace_byte_ranges(&mut self.edit, vec![(start..cursor, word.clone())]);
+        let end = self.edit.byte_pos(start + word.len());
+        self.edit.set_cursor(end, false);
+        self.dabbrev = Some((prefix, start, word));
+
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Root directory for link completion.
+    fn link_root(&self) -> PathBuf {
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        FileSysStructure::find_root(dir).unwrap_or(dir.to_path_buf())
+    }
+
+    /// Open the completion for link targets at start.
+    /// Walks the root first, if it's not cached.
+    fn open_link_completion(&mut self, start: usize, ctx: &mut GlobalState) -> Result<(), Error> {
+        let root = self.link_root();
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+
+        let candidates = match &ctx.link_targets {
+            Some((cached, targets)) if *cached == root => targets
+                .iter()
+                .filter(|v| **v != self.path)
+                .map(|v| {
+                    completion::relative_path(dir, v)
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect(),
+            _ => {
+                // the popup opens when the walk is done.
+                ctx.spawn(move || {
+                    let targets = completion::link_targets(&root);
+                    Ok(Control::Event(MDEvent::LinkTargets(
+                        root,
+                        Box::new(AtomicCell::new(targets)),
+                    )))
+                })?;
+                Vec::new()
+            }
+        };
+
+        self.completion = Some(Completion::new(CompletionKind::Link, start, candidates));
+        Ok(())
+    }
+
+    /// Open, filter or close the completion popup after
+    /// the text or the cursor changed.
+    fn update_completion(
+        &mut self,
+        typed: Option<char>,
+        ctx: &mut GlobalState,
+    ) -> Result<(), Error> {
+        let pos = self.edit.cursor();
+        let cursor = self.edit.byte_at(pos).start;
+        let line_start = self.edit.byte_at(TextPosition::new(0, pos.y)).start;
+        let before = self.edit.str_slice_byte(line_start..cursor).to_string();
+
+        match typed {
+            Some('(') if before.ends_with("](") && !self.in_code(cursor) => {
+                return self.open_link_completion(cursor, ctx);
+            }
+            Some('^') if before.ends_with("[^") && !self.in_code(cursor) => {
+                let mut ids = self
+                    .footnotes(MDStyle::FootnoteDefinition)
+                    .into_iter()
+                    .map(|(_, v)| v)
+                    .collect::<Vec<_>>();
+                ids.sort();
+                ids.dedup();
+                if !ids.is_empty() {
+                    self.completion = Some(Completion::new(CompletionKind::Footnote, cursor, ids));
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let Some(completion) = &mut self.completion else {
+            return Ok(());
+        };
+        if cursor < completion.start || cursor - completion.start > before.len() {
+            self.completion = None;
+            return Ok(());
         }
-        MDEvent::CfgWrapText => {
-            try_flow!({
-                state.edit.set_text_wrap(if ctx.cfg.wrap_text {
-                    TextWrap::Word(8)
-                } else {
-                    TextWrap::Shift
-                });
-                Control::Changed
-            });
+        let prefix = &before[before.len() - (cursor - completion.start)..];
+        if prefix.contains([' ', '\t', ')', ']']) {
+            self.completion = None;
+        } else {
+            completion.filter(prefix);
         }
-        _ => {}
+        Ok(())
     }
 
-    Ok(Control::Continue)
-}
+    /// Replace the typed prefix with the selected candidate.
+    fn accept_completion(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let Some(completion) = self.completion.take() else {
+            return Ok(Control::Continue);
+        };
+        let Some(item) = completion.selected() else {
+            return Ok(Control::Continue);
+        };
 
-impl MDFileState {
-    /// Reformat
-    fn reformat(
+        let pos = self.edit.cursor();
+        let cursor = self.edit.byte_at(pos).start;
+        let line_start = self.edit.byte_at(TextPosition::new(0, pos.y)).start;
+        let line = self.line_str(pos.y);
+
+        let (mut txt, close) = match completion.kind {
+            CompletionKind::Link if item.contains(' ') => (format!("<{}>", item), ')'),
+            CompletionKind::Link => (item.to_string(), ')'),
+            CompletionKind::Footnote => (item.to_string(), ']'),
+        };
+        let end = completion.start + txt.len() + close.len_utf8();
+        if !line[cursor - line_start..].starts_with(close) {
+            txt.push(close);
+        }
+
+        replace_byte_ranges(&mut self.edit, vec![(completion.start..cursor, txt)]);
+        let end = self.edit.byte_pos(end);
+        self.edit.set_cursor(end, false);
+
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Task list marker in the given row.
+    fn task_marker(&self, row: upos_type) -> Option<Range<usize>> {
+        if row >= self.edit.len_lines() {
+            return None;
+        }
+        let line_start = self.edit.byte_at(TextPosition::new(0, row)).start;
+        let line_end = line_start + self.edit.line_at(row).len();
+
+        let marker_style: usize = MDStyle::TaskListMarker.into();
+        self.edit
+            .styles()
+            .find(|(range, style)| {
+                *style == marker_style && range.start >= line_start && range.start < line_end
+            })
+            .map(|(range, _)| range)
+    }
+
+    /// Toggle the task list marker in the cursor row.
+    pub fn toggle_task(&mut self) -> bool {
+        self.toggle_task_at(self.edit.cursor().y)
+    }
+
+    /// Toggle the task list marker in the given row.
+    /// Keeps the cursor where it is.
+    pub fn toggle_task_at(&mut self, row: upos_type) -> bool {
+        let Some(marker) = self.task_marker(row) else {
+            return false;
+        };
+        let marker_txt = self.edit.str_slice_byte(marker.clone()).to_string();
+        let Some(open) = marker_txt.find('[') else {
+            return false;
+        };
+        let check_byte = marker.start + open + 1;
+        let check = self
+            .edit
+            .str_slice_byte(check_byte..check_byte + 1)
+            .to_string();
+        let new_check = if check == " " { "x" } else { " " };
+
+        let check_pos = self.edit.byte_pos(check_byte);
+        let cursor = self.edit.cursor();
+        let anchor = self.edit.anchor();
+
+        self.edit.begin_undo_seq();
+        self.edit.delete_range(TextRange::new(
+            check_pos,
+            TextPosition::new(check_pos.x + 1, check_pos.y),
+        ));
+        self.edit.set_cursor(check_pos, false);
+        self.edit.insert_str(new_check);
+        self.edit.set_selection(anchor, cursor);
+        self.edit.end_undo_seq();
+
+        true
+    }
+
+    /// All footnote references or definitions with their label.
+    fn footnotes(&self, style: MDStyle) -> Vec<(Range<usize>, String)> {
+        let style: usize = style.into();
+        let mut footnotes = self
+            .edit
+            .styles()
+            .filter(|(_, v)| *v == style)
+            .filter_map(|(range, _)| {
+                let txt = self.edit.str_slice_byte(range.clone());
+                footnote_label(txt.as_ref()).map(|v| (range, v.to_string()))
+            })
+            .collect::<Vec<_>>();
+        footnotes.sort_by_key(|(range, _)| range.start);
+        footnotes
+    }
+
+    /// Insert a new footnote reference at the cursor and
+    /// create its definition at the end of the document.
+    fn insert_footnote(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let next_id = self
+            .footnotes(MDStyle::FootnoteReference)
+            .into_iter()
+            .chain(self.footnotes(MDStyle::FootnoteDefinition))
+            .filter_map(|(_, label)| label.parse::<u32>().ok())
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        self.edit.begin_undo_seq();
+        self.edit.insert_str(format!("[^{}]", next_id));
+
+        let last = self.edit.len_lines().saturating_sub(1);
+        let last_width = self.edit.line_width(last);
+        self.edit
+            .set_cursor(TextPosition::new(last_width, last), false);
+        let mut def = String::new();
+        if last_width > 0 {
+            def.push_str(self.edit.newline());
+        }
+        if last > 0 || last_width > 0 {
+            def.push_str(self.edit.newline());
+        }
+        def.push_str(format!("[^{}]: ", next_id).as_str());
+        self.edit.insert_str(def);
+        self.edit.end_undo_seq();
+
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Jump from a footnote reference to its definition and back.
+    fn jump_footnote(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let pos = self.edit.byte_at(self.edit.cursor());
+
+        let (range, target) = if let Some(range) = self
+            .edit
+            .styles_at_match(pos.start, MDStyle::FootnoteReference.into())
+        {
+            (range, MDStyle::FootnoteDefinition)
+        } else if let Some(range) = self
+            .edit
+            .styles_at_match(pos.start, MDStyle::FootnoteDefinition.into())
+        {
+            (range, MDStyle::FootnoteReference)
+        } else {
+            return Ok(Control::Continue);
+        };
+
+        let txt = self.edit.str_slice_byte(range).to_string();
+        let Some(label) = footnote_label(&txt) else {
+            return Ok(Control::Continue);
+        };
+
+        let Some((target_range, _)) = self.footnotes(target).into_iter().find(|(_, v)| v == label)
+        else {
+            return Ok(Control::Event(MDEvent::Info(format!(
+                "no match for [^{}]",
+                label
+            ))));
+        };
+
+        let target_pos = self.edit.byte_pos(target_range.start);
+        self.edit.set_cursor(target_pos, false);
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Ok(Control::Changed)
+    }
+
+    /// Move the cursor to the screen position and follow the link.
+    fn follow_link_at(
         &mut self,
-        eq_width: bool,
+        pos: (u16, u16),
+        split: bool,
         ctx: &mut GlobalState,
     ) -> Result<Control<MDEvent>, Error> {
-        let mut r: Control<MDEvent> = self
-            .doc_type
-            .format(&mut self.edit, ctx.cfg.text_width, eq_width)
-            .into();
-        r = r.and_then(|| {
-            self.update_cursor_pos(ctx);
-            self.text_changed(ctx)
-        });
-        Ok(r)
+        let Some(pos) = self.edit.screen_to_pos(pos) else {
+            return Ok(Control::Continue);
+        };
+        self.edit.set_cursor(pos, false);
+        self.follow_link(split, ctx)
+    }
+
+    /// Rewrite the local links between relative and root-relative.
+    /// One undo step.
+    fn convert_link_paths(
+        &mut self,
+        mode: LinkPaths,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if self.doc_type != DocTypes::MD {
+            return Ok(Control::Continue);
+        }
+        let path = fs::canonicalize(&self.path).unwrap_or(self.path.clone());
+        let Some(dir) = path.parent() else {
+            return Err(anyhow!("Can't locate current file??"));
+        };
+        let root = FileSysStructure::find_root(dir).unwrap_or(dir.to_path_buf());
+
+        let text = self.edit.text();
+        let replace = convert_link_paths(&text, self.md_options, dir, &root, mode);
+        if replace.is_empty() {
+            return Ok(Control::Event(MDEvent::Info("no links to convert".into())));
+        }
+
+        let count = replace.len();
+        replace_byte_ranges(&mut self.edit, replace);
+        self.update_cursor_pos(ctx);
+        ctx.queue_event(MDEvent::Info(format!("converted {} links", count)));
+        Ok(self.text_changed(ctx))
+    }
+
+    /// Rewrite the links to a renamed file. Paths are canonical.
+    /// Other views of this file must be synced by the caller.
+    ///
+    /// Returns the number of changed links.
+    pub fn retarget_links(
+        &mut self,
+        root: &Path,
+        old: &Path,
+        new: &Path,
+        ctx: &mut GlobalState,
+    ) -> usize {
+        if self.doc_type != DocTypes::MD || self.read_only {
+            return 0;
+        }
+        let path = fs::canonicalize(&self.path).unwrap_or(self.path.clone());
+        let Some(dir) = path.parent() else {
+            return 0;
+        };
+
+        let text = self.edit.text();
+        let replace = retarget_links(&text, self.md_options, dir, root, old, new);
+        let count = replace.len();
+        if count > 0 {
+            replace_byte_ranges(&mut self.edit, replace);
+            self.changed = true;
+            self.parse_timer = Some(ctx.replace_timer(
+                self.parse_timer,
+                TimerDef::new().next(Instant::now() + Duration::from_millis(200)),
+            ));
+        }
+        count
     }
 
     /// Follow the link at the cursor.
-    fn follow_link(&mut self, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+    /// Opens the file in the next split or in the current one.
+    fn follow_link(
+        &mut self,
+        split: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
         let pos = self.edit.byte_at(self.edit.cursor());
+        if self
+            .edit
+            .styles_at_match(pos.start, MDStyle::FootnoteReference.into())
+            .is_some()
+            || self
+                .edit
+                .styles_at_match(pos.start, MDStyle::FootnoteDefinition.into())
+                .is_some()
+        {
+            return self.jump_footnote(ctx);
+        }
+
         let Some(link_range) = self.edit.styles_at_match(pos.start, MDStyle::Link.into()) else {
             return Ok(Control::Continue);
         };
 
         let link_txt = self.edit.str_slice_byte(link_range);
-        let p = Parser::new_ext(link_txt.as_ref(), Options::empty()).into_iter();
+        let p = Parser::new_ext(link_txt.as_ref(), self.md_options).into_iter();
         for e in p {
             match e {
                 Event::Start(Tag::Link { dest_url, .. }) => {
@@ -331,7 +3684,11 @@ impl MDFileState {
                                 }
                             }
 
-                            return Ok(Control::Event(MDEvent::SelectOrOpen(path)));
+                            return Ok(Control::Event(if split {
+                                MDEvent::SelectOrOpenSplit(path)
+                            } else {
+                                MDEvent::SelectOrOpen(path)
+                            }));
                         } else {
                             return Err(anyhow!("Can't locate current file??"));
                         }
@@ -354,6 +3711,7 @@ impl MDFileState {
         }
 
         let doc_type = Self::doc_type(&path);
+        let view = Self::file_view(&path, ctx);
 
         let mut edit = TextAreaState::named(
             path.file_name()
@@ -361,33 +3719,96 @@ impl MDFileState {
                 .to_string_lossy()
                 .as_ref(),
         );
-        edit.set_clipboard(Some(CliClipboard::default()));
+        edit.set_clipboard(Some(ctx.clipboard.clone()));
         edit.set_show_ctrl(ctx.cfg.show_ctrl);
         edit.set_wrap_ctrl(ctx.cfg.show_break);
-        edit.set_text_wrap(if ctx.cfg.wrap_text {
+        edit.set_text_wrap(if view.wrap_text.unwrap_or(ctx.cfg.wrap_text) {
             TextWrap::Word(8)
         } else {
             TextWrap::Shift
         });
-        edit.set_tab_width(4);
+        edit.set_tab_width(tab_width(ctx));
+
+        // seed from the template. goes through undo, so
+        // the buffer counts as changed.
+        let template = if doc_type == DocTypes::MD {
+            Self::template(&path, ctx)
+        } else {
+            None
+        };
+        if let Some(template) = &template {
+            edit.insert_str(template);
+            edit.set_cursor((0, 0), false);
+        }
 
         MDFileState {
             path: path.clone(),
-            changed: Default::default(),
+            changed: template.is_some(),
             doc_type,
+            md_options: ctx.cfg.md_flavor.0,
+            show_linenr: view.show_linenr.unwrap_or(ctx.cfg.show_linenr),
+            view,
             edit,
             edit_mouse: Default::default(),
-            show_linenr: ctx.cfg.show_linenr,
+            last_doubleclick: None,
             linenr: Default::default(),
-            parse_timer: None,
+            parse_timer: template.as_ref().map(|_| {
+                ctx.add_timer(TimerDef::new().next(Instant::now() + Duration::from_millis(0)))
+            }),
+            run_block: None,
+            images: Default::default(),
+            preview_timer: None,
+            drag_scroll: None,
+            drag_inside: false,
+            preview: None,
+            style_cache: Default::default(),
+            last_focus: Instant::now(),
+            read_only: false,
+            pinned: false,
+            selection_stack: Default::default(),
+            run_format: None,
+            completion: None,
+            words: Default::default(),
+            links: Default::default(),
+            structure: Default::default(),
+            dabbrev: None,
+            last_cursor: Default::default(),
+            recenter: None,
+            search_hl: Default::default(),
         }
     }
 
+    /// Text of the template_file with {filename} and {date}
+    /// replaced. None if there is no template.
+    fn template(path: &Path, ctx: &mut GlobalState) -> Option<String> {
+        let template_file = ctx.cfg.template_file.as_ref()?;
+        let template = match fs::read_to_string(template_file) {
+            Ok(v) => v,
+            Err(e) => {
+                ctx.queue_event(MDEvent::Info(format!(
+                    "template {}: {}",
+                    template_file.display(),
+                    e
+                )));
+                return None;
+            }
+        };
+
+        let filename = path.file_stem().unwrap_or_default().to_string_lossy();
+        let date = date::now(&ctx.cfg.date_format, false, ctx.cfg.loc).unwrap_or_default();
+        Some(
+            template
+                .replace("{filename}", filename.as_ref())
+                .replace("{date}", date.as_str()),
+        )
+    }
+
     // New editor with existing file.
     pub fn open_file(path: &Path, ctx: &mut GlobalState) -> Result<MDFileState, Error> {
         let path = PathBuf::from(path);
 
         let doc_type = Self::doc_type(&path);
+        let view = Self::file_view(&path, ctx);
 
         let mut edit = TextAreaState::named(
             path.file_name()
@@ -395,32 +3816,95 @@ impl MDFileState {
                 .to_string_lossy()
                 .as_ref(),
         );
-        edit.set_clipboard(Some(CliClipboard::default()));
-        let t = fs::read_to_string(&path)?;
-        edit.set_text(t.as_str());
+        edit.set_clipboard(Some(ctx.clipboard.clone()));
+        let mut file = File::open(&path)?;
+        if is_binary(&mut file)? {
+            return Err(anyhow!("{} is not a text file", path.display()));
+        }
+        let read_only = file.metadata()?.len() > ctx.cfg.large_file_threshold * 1024 * 1024;
+        if read_only {
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)?;
+            let t = match String::from_utf8(buf) {
+                Ok(v) => v,
+                Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            };
+            edit.set_text(t.as_str());
+            // any change to the text is refused by the text-area.
+            edit.set_read_only(true);
+            ctx.queue_event(MDEvent::Info("large file, read-only".into()));
+        } else {
+            let t = match io::read_to_string(file) {
+                Ok(v) => v,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    return Err(anyhow!("{} is not a text file", path.display()));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            edit.set_text(t.as_str());
+        }
         edit.set_show_ctrl(ctx.cfg.show_ctrl);
         edit.set_wrap_ctrl(ctx.cfg.show_break);
-        edit.set_text_wrap(if ctx.cfg.wrap_text {
+        edit.set_text_wrap(if view.wrap_text.unwrap_or(ctx.cfg.wrap_text) {
             TextWrap::Word(8)
         } else {
             TextWrap::Shift
         });
-        edit.set_tab_width(4);
+        edit.set_tab_width(tab_width(ctx));
 
         Ok(MDFileState {
             path: path.clone(),
             changed: Default::default(),
             doc_type,
+            md_options: ctx.cfg.md_flavor.0,
+            show_linenr: view.show_linenr.unwrap_or(ctx.cfg.show_linenr),
+            view,
             edit,
             edit_mouse: Default::default(),
-            show_linenr: ctx.cfg.show_linenr,
+            last_doubleclick: None,
             linenr: Default::default(),
             parse_timer: Some(
                 ctx.add_timer(TimerDef::new().next(Instant::now() + Duration::from_millis(0))),
             ),
+            run_block: None,
+            images: Default::default(),
+            preview_timer: None,
+            drag_scroll: None,
+            drag_inside: false,
+            preview: None,
+            style_cache: Default::default(),
+            last_focus: Instant::now(),
+            read_only,
+            pinned: false,
+            selection_stack: Default::default(),
+            run_format: None,
+            completion: None,
+            words: Default::default(),
+            links: Default::default(),
+            structure: Default::default(),
+            dabbrev: None,
+            last_cursor: Default::default(),
+            recenter: None,
+            search_hl: Default::default(),
         })
     }
 
+    fn file_view(path: &Path, ctx: &GlobalState) -> FileView {
+        ctx.cfg.file_view(path).cloned().unwrap_or(FileView {
+            path: path.to_path_buf(),
+            ..Default::default()
+        })
+    }
+
+    /// Extension for save-as. The current one, or the default
+    /// of the doc type.
+    pub fn extension(&self) -> String {
+        match self.path.extension() {
+            Some(ext) => ext.to_string_lossy().into_owned(),
+            None => self.doc_type.extension().to_string(),
+        }
+    }
+
     fn doc_type(path: &Path) -> DocTypes {
         if let Some(ext) = path.extension() {
             match ext.to_string_lossy().as_ref() {
@@ -432,22 +3916,76 @@ impl MDFileState {
         }
     }
 
-    // Save as
-    pub fn save_as(&mut self, path: &Path) -> Result<(), Error> {
+    // The file has been renamed. A new extension can
+    // change the document type.
+    pub fn renamed(&mut self, path: &Path) {
         self.path = path.into();
-        self.save()
+        self.view.path = path.into();
+        let doc_type = Self::doc_type(path);
+        if doc_type != self.doc_type {
+            self.doc_type = doc_type;
+            // the cached styles belong to the old type.
+            *self.style_cache.borrow_mut() = None;
+            self.parse();
+        }
+    }
+
+    // Parse the styles, or reuse them if another split
+    // has already parsed the same text.
+    pub fn parse(&mut self) {
+        let mut hasher = DefaultHasher::new();
+        self.edit.text().hash(&mut hasher);
+        self.md_options.bits().hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut cache = self.style_cache.borrow_mut();
+        if let Some((cache_hash, styles)) = cache.as_ref() {
+            if *cache_hash == hash {
+                self.edit.set_styles(styles.clone());
+                return;
+            }
+        }
+        self.doc_type.parse(&mut self.edit, self.md_options);
+        *cache = Some((hash, self.edit.styles().collect()));
+    }
+
+    // Save as
+    pub fn save_as(&mut self, path: &Path, cfg: &MDConfig) -> Result<(), Error> {
+        if self.read_only {
+            return Err(anyhow!("{} is opened read-only.", self.path.display()));
+        }
+        let old_path = mem::replace(&mut self.path, path.into());
+        let old_changed = self.changed;
+        // always write the new file
+        self.changed = true;
+        if let Err(e) = self.save(cfg) {
+            self.path = old_path;
+            self.changed = old_changed;
+            return Err(e);
+        }
+        self.view.path = path.into();
+        Ok(())
     }
 
     // Save
-    pub fn save(&mut self) -> Result<(), Error> {
+    pub fn save(&mut self, cfg: &MDConfig) -> Result<(), Error> {
+        if self.read_only {
+            return Ok(());
+        }
         if self.changed {
-            let mut f = BufWriter::new(File::create(&self.path)?);
+            if let Some(parent) = missing_parent(&self.path) {
+                return Err(anyhow!("Directory {} doesn't exist.", parent.display()));
+            }
+            if cfg.backup && self.path.exists() {
+                backup(&self.path, cfg.backup_count)?;
+            }
+
             let mut buf = Vec::new();
             for line in self.edit.text().lines() {
                 buf.extend(line.bytes());
                 buf.extend_from_slice(self.edit.newline().as_bytes());
             }
-            f.write_all(&buf)?;
+            write_atomic(&self.path, &buf)?;
 
             self.changed = false;
         }
@@ -456,6 +3994,21 @@ impl MDFileState {
 
     // Update cursor info
     pub fn update_cursor_pos(&mut self, ctx: &mut GlobalState) {
+        // only when the cursor moved, scrolling alone is fine.
+        if self.edit.cursor() != self.last_cursor {
+            self.last_cursor = self.edit.cursor();
+            self.scroll_off(ctx.cfg.scroll_off);
+        }
+
+        // debounce image preview
+        if ctx.graphics != Graphics::None {
+            self.preview = None;
+            self.preview_timer = Some(ctx.replace_timer(
+                self.preview_timer,
+                TimerDef::new().next(Instant::now() + Duration::from_millis(300)),
+            ));
+        }
+
         // update cursor / selection info
         if self.edit.is_focused() {
             let cursor = self.edit.cursor();
@@ -465,11 +4018,218 @@ impl MDFileState {
             } else {
                 sel.end.y.saturating_sub(sel.start.y) + 1
             };
-            ctx.queue(Control::Event(MDEvent::Info(format!(
-                "{}:{}|{}",
-                cursor.x, cursor.y, sel_len
+            let context = self.cursor_context();
+            ctx.queue(Control::Event(MDEvent::Info(if context.is_empty() {
+                format!("{}:{}|{}", cursor.x, cursor.y, sel_len)
+            } else {
+                format!("{}  {}:{}|{}", context, cursor.x, cursor.y, sel_len)
+            })));
+        }
+    }
+
+    /// Replace the search highlight with the matches
+    /// of the current search term.
+    fn update_search_hl(&mut self, ctx: &GlobalState) {
+        for range in mem::take(&mut self.search_hl) {
+            self.edit.remove_style(range, search::SEARCH_MATCH);
+        }
+        if ctx.hl_search && !ctx.search.is_empty() {
+            self.search_hl = search::find_all(self.edit.text().as_str(), &ctx.search);
+            for range in &self.search_hl {
+                self.edit.add_style(range.clone(), search::SEARCH_MATCH);
+            }
+        }
+    }
+
+    /// Select the next/previous match of the search term.
+    fn search_next(&mut self, backward: bool, ctx: &mut GlobalState) -> Control<MDEvent> {
+        if ctx.search.is_empty() {
+            return Control::Continue;
+        }
+        let matches = search::find_all(self.edit.text().as_str(), &ctx.search);
+        let cursor = self.edit.byte_at(self.edit.cursor()).start;
+        let Some(found) = search::next_match(&matches, cursor, backward) else {
+            return Control::Event(MDEvent::Info(format!("{} not found", ctx.search)));
+        };
+
+        let start = self.edit.byte_pos(found.start);
+        let end = self.edit.byte_pos(found.end);
+        self.edit.set_selection(end, start);
+        self.edit.scroll_cursor_to_visible();
+        self.update_cursor_pos(ctx);
+        Control::Changed
+    }
+
+    /// Ask for the term to count.
+    fn count_dialog(&mut self, project: bool, ctx: &mut GlobalState) -> Control<MDEvent> {
+        let dlg = if project {
+            InputDialogState::new("Count in project", "Term", |v| MDEvent::CountTerm(v, true))
+        } else {
+            InputDialogState::new("Count", "Term", |v| MDEvent::CountTerm(v, false))
+        };
+        ctx.dialogs.push(
+            input_dlg::render,
+            input_dlg::event,
+            dlg.with_value(&ctx.search)
+                .with_history(&ctx.search_history),
+        );
+        Control::Changed
+    }
+
+    /// Count the term in this document, or in all files of the
+    /// project. The project is scanned in the background.
+    fn count_term(
+        &mut self,
+        term: &str,
+        project: bool,
+        ctx: &mut GlobalState,
+    ) -> Result<Control<MDEvent>, Error> {
+        if term.is_empty() {
+            return Ok(Control::Continue);
+        }
+        let count = search::find_all(self.edit.text().as_str(), term).len();
+        if !project {
+            return Ok(Control::Event(MDEvent::Message(format!(
+                "'{}' occurs {} times in this document.",
+                term, count
             ))));
         }
+
+        let root = self.link_root();
+        let globs = ctx.cfg.globs.clone();
+        let term = term.to_string();
+        let path = self.path.clone();
+        ctx.spawn(move || {
+            let mut counts = search::count_files(&root, &globs, &term);
+            // the buffer may be ahead of the file.
+            counts.retain(|(p, _)| *p != path);
+            if count > 0 {
+                counts.push((path, count));
+                counts.sort();
+            }
+
+            let total = counts.iter().map(|(_, n)| *n).sum::<usize>();
+            let mut msg = format!(
+                "'{}' occurs {} times in {} files.\n\n",
+                term,
+                total,
+                counts.len()
+            );
+            for (p, n) in &counts {
+                let rel = completion::relative_path(&root, p);
+                msg.push_str(format!("{:>6}  {}\n", n, rel.display()).as_str());
+            }
+            Ok(Control::Event(MDEvent::Message(msg)))
+        })?;
+
+        Ok(Control::Event(MDEvent::Info(format!(
+            "counting '{}' ...",
+            term
+        ))))
+    }
+
+    /// Toggle text-wrapping for this file only.
+    fn toggle_file_wrap(&mut self, ctx: &mut GlobalState) -> Control<MDEvent> {
+        let wrap = !self.view.wrap_text.unwrap_or(ctx.cfg.wrap_text);
+        self.view.wrap_text = (wrap != ctx.cfg.wrap_text).then_some(wrap);
+        self.edit.set_text_wrap(if wrap {
+            TextWrap::Word(8)
+        } else {
+            TextWrap::Shift
+        });
+        ctx.cfg.set_file_view(self.view.clone());
+        ctx.queue_event(MDEvent::StoreConfig);
+        Control::Changed
+    }
+
+    /// Toggle line-numbers for this file only.
+    fn toggle_file_linenr(&mut self, ctx: &mut GlobalState) -> Control<MDEvent> {
+        self.show_linenr = !self.show_linenr;
+        self.view.show_linenr =
+            (self.show_linenr != ctx.cfg.show_linenr).then_some(self.show_linenr);
+        ctx.cfg.set_file_view(self.view.clone());
+        ctx.queue_event(MDEvent::StoreConfig);
+        Control::Changed
+    }
+
+    /// Put the cursor line in the middle of the view.
+    /// Pressed again it goes to the top, then to the bottom.
+    pub fn recenter(&mut self, _ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+        let cursor = self.edit.cursor();
+        let align = match self.recenter {
+            Some((pos, ViewAlign::Center)) if pos == cursor => ViewAlign::Top,
+            Some((pos, ViewAlign::Top)) if pos == cursor => ViewAlign::Bottom,
+            _ => ViewAlign::Center,
+        };
+        self.recenter = Some((cursor, align));
+        self.align_view(align);
+        Ok(Control::Changed)
+    }
+
+    /// Scroll the cursor line to the given position.
+    /// Works with screen rows, so wrapped lines count
+    /// with their full height.
+    pub fn align_view(&mut self, align: ViewAlign) {
+        let height = self.edit.inner.height;
+        if height == 0 {
+            return;
+        }
+        let target = match align {
+            ViewAlign::Center => (height - 1) / 2,
+            ViewAlign::Top => 0,
+            ViewAlign::Bottom => height - 1,
+        };
+
+        let cursor = self.edit.cursor();
+        let row = if let Some((_, y)) = self.edit.pos_to_screen(cursor) {
+            y.saturating_sub(self.edit.inner.y)
+        } else {
+            // off-screen, start with the line at the top.
+            let (ox, _) = self.edit.offset();
+            self.edit.set_offset((ox, cursor.y as usize));
+            self.edit.set_sub_row_offset(0);
+            0
+        };
+
+        if row > target {
+            self.edit.scroll_down((row - target) as usize);
+        } else if row < target {
+            self.edit.scroll_up((target - row) as usize);
+        }
+    }
+
+    /// Keep margin rows between the cursor and the top/bottom edge.
+    /// Rows are screen rows, so this works with wrapped lines too.
+    fn scroll_off(&mut self, margin: u16) {
+        let height = self.edit.inner.height;
+        // a short view can't keep both margins.
+        let margin = margin.min(height.saturating_sub(1) / 2);
+        if margin == 0 {
+            return;
+        }
+
+        let cursor = self.edit.cursor();
+        // row of the cursor relative to the view, may be outside.
+        let row = if let Some((_, y)) = self.edit.pos_to_screen(cursor) {
+            y.saturating_sub(self.edit.inner.y) as i64
+        } else {
+            cursor.y as i64 - self.edit.vertical_offset() as i64
+        };
+        let margin = margin as i64;
+        if row < margin {
+            self.edit.scroll_up((margin - row) as usize);
+        } else {
+            // don't scroll beyond the end of a short document.
+            let lines_below = self
+                .edit
+                .len_lines()
+                .saturating_sub(cursor.y + 1)
+                .min(margin as upos_type) as i64;
+            let rows_below = height as i64 - (row + 1);
+            if rows_below < lines_below {
+                self.edit.scroll_down((lines_below - rows_below) as usize);
+            }
+        }
     }
 
     // Flag any text-changes.
@@ -485,3 +4245,83 @@ impl MDFileState {
         Control::Changed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{missing_parent, write_atomic};
+    use std::path::{Path, PathBuf};
+    use std::{env, fs, io, process};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("mdedit-{}-{}", name, process::id()));
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("dir");
+        dir
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = test_dir("atomic");
+        let path = dir.join("a.md");
+        fs::write(&path, "original").expect("write");
+
+        write_atomic(&path, b"changed").expect("write_atomic");
+        assert_eq!(fs::read_to_string(&path).expect("read"), "changed");
+        assert!(!dir.join(".a.md.mdedit-tmp").exists());
+
+        fs::remove_dir_all(&dir).expect("cleanup");
+    }
+
+    #[test]
+    fn test_write_atomic_fails() {
+        let dir = test_dir("atomic-fails");
+        let path = dir.join("a.md");
+        fs::write(&path, "original").expect("write");
+
+        // a directory in place of the temp file can't be written.
+        fs::create_dir(dir.join(".a.md.mdedit-tmp")).expect("dir");
+        assert!(write_atomic(&path, b"changed").is_err());
+        assert_eq!(fs::read_to_string(&path).expect("read"), "original");
+
+        fs::remove_dir_all(&dir).expect("cleanup");
+    }
+
+    #[test]
+    fn test_missing_parent() {
+        let dir = test_dir("missing");
+        assert_eq!(missing_parent(&dir.join("a.md")), None);
+        let gone = dir.join("gone");
+        assert_eq!(missing_parent(&gone.join("a.md")), Some(gone.as_path()));
+        // relative to the current directory
+        assert_eq!(missing_parent(Path::new("a.md")), None);
+
+        fs::remove_dir_all(&dir).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permission_denied() {
+        use std::fs::{File, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("denied");
+        let path = dir.join("a.md");
+        fs::write(&path, "original").expect("write");
+        fs::set_permissions(&dir, Permissions::from_mode(0o555)).expect("perm");
+
+        // root can write anyway.
+        if File::create(dir.join("probe")).is_err() {
+            // the directory exists, this is not offered to be created.
+            assert_eq!(missing_parent(&path), None);
+            let err = write_atomic(&path, b"changed").expect_err("denied");
+            assert_eq!(
+                err.downcast_ref::<io::Error>().map(|v| v.kind()),
+                Some(io::ErrorKind::PermissionDenied)
+            );
+            assert_eq!(fs::read_to_string(&path).expect("read"), "original");
+        }
+
+        fs::set_permissions(&dir, Permissions::from_mode(0o755)).expect("perm");
+        fs::remove_dir_all(&dir).expect("cleanup");
+    }
+}
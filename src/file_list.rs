@@ -2,8 +2,9 @@ use crate::fsys::FileSysStructure;
 use crate::global::event::MDEvent;
 use crate::global::theme::MDWidgets;
 use crate::global::GlobalState;
-use anyhow::Error;
 use crate::rat_salsa::{Control, SalsaContext};
+use anyhow::Error;
+use log::warn;
 use rat_theme4::palette::Colors;
 use rat_theme4::{StyleName, WidgetStyle};
 use rat_widget::choice::{Choice, ChoiceClose, ChoiceSelect, ChoiceState};
@@ -16,7 +17,7 @@ use rat_widget::scrolled::Scroll;
 use rat_widget::util::revert_style;
 use ratatui::buffer::Buffer;
 use ratatui::layout::{Constraint, Layout, Rect};
-use ratatui::style::Style;
+use ratatui::style::{Modifier, Style};
 use ratatui::text::Line;
 use ratatui::widgets::{StatefulWidget, Widget};
 use std::cmp::min;
@@ -86,7 +87,7 @@ pub fn render(
         .popup_scroll(Scroll::new())
         .popup_placement(Placement::Below)
         .popup_len(popup_len)
-        .behave_select(ChoiceSelect::MouseMove)
+        .behave_select(ChoiceSelect::MouseClick)
         .behave_close(ChoiceClose::SingleClick)
         .into_widgets();
     choice.render(l_file_list[2], buf, &mut state.file_system);
@@ -105,32 +106,38 @@ pub fn render(
         .styles(theme.style(WidgetStyle::LIST))
         .render(l_file_list[4], buf, &mut state.file_list);
 
-    // render hover for overlong file names
-    if state.file_list.is_focused() {
-        if let Some(selected) = state.file_list.selected() {
-            let idx = selected - state.file_list.offset();
-
-            let focus_style =
-                theme
-                    .style::<ListStyle>(WidgetStyle::LIST)
-                    .focus
-                    .unwrap_or(revert_style(
-                        theme.style::<ListStyle>(WidgetStyle::LIST).style,
-                    ));
-
-            let line = state.sys.files().iter().nth(idx).map(|v| {
-                if let Some(name) = v.file_name() {
-                    Line::from(name.to_string_lossy().to_string()).style(focus_style)
-                } else {
-                    Line::from("???").style(focus_style)
-                }
-            });
-            if let Some(line) = line {
-                let mut area = state.file_list.row_areas[idx];
-                area.width = line.width() as u16 + 1;
+    // render hover for overlong file names, or a dimmed
+    // selection marker if not focused.
+    if let Some(selected) = state.file_list.selected() {
+        let row_area = selected
+            .checked_sub(state.file_list.offset())
+            .and_then(|idx| state.file_list.row_areas.get(idx))
+            .copied();
+        let list_style = theme.style::<ListStyle>(WidgetStyle::LIST);
+
+        if let Some(mut area) = row_area {
+            if state.file_list.is_focused() {
+                let focus_style = list_style.focus.unwrap_or(revert_style(list_style.style));
+
+                let line = state.sys.files().get(selected).map(|v| {
+                    if let Some(name) = v.file_name() {
+                        Line::from(name.to_string_lossy().to_string())
+                    } else {
+                        Line::from("???")
+                    }
+                });
+                if let Some(line) = line {
+                    area.width = line.width() as u16 + 1;
 
-                line.style(focus_style)
-                    .render(area, ctx.hover.buffer_mut(area));
+                    line.style(focus_style)
+                        .render(area, ctx.hover.buffer_mut(area));
+                }
+            } else {
+                let select_style = list_style
+                    .select
+                    .unwrap_or(theme.style_style(Style::SELECT))
+                    .add_modifier(Modifier::DIM);
+                buf.set_style(area, select_style);
             }
         }
     }
@@ -178,14 +185,23 @@ pub fn event(
                 if matches!(event, ct_event!(keycode press Enter)) {
                     ctx.focus().next();
                 }
-                let sel_path = state.file_system.value();
-                state.load_current(&sel_path, &ctx.cfg.globs)?;
+                // the arrow keys only move through the popup, the
+                // directory is read after a click or Enter.
+                if !state.file_system.is_popup_active() {
+                    let sel_path = state.file_system.value();
+                    state.load_current(&sel_path, &ctx.cfg.globs)?;
+                }
                 Control::Changed
             }
             ChoiceOutcome::Changed => {
                 if matches!(event, ct_event!(keycode press Enter)) {
                     ctx.focus().next();
                 }
+                // popup closed on the value from the arrow keys.
+                let sel_path = state.file_system.value();
+                if !state.file_system.is_popup_active() && sel_path != state.sys.files_dir() {
+                    state.load_current(&sel_path, &ctx.cfg.globs)?;
+                }
                 Control::Changed
             }
             r => r.into(),
@@ -207,6 +223,20 @@ pub fn event(
                         Control::Continue
                     }
                 }
+                ct_event!(key press 'r') => {
+                    if let Some(row) = state.file_list.selected() {
+                        Control::Event(MDEvent::MenuRenameFile(state.sys.file(row).into()))
+                    } else {
+                        Control::Continue
+                    }
+                }
+                ct_event!(keycode press Delete) => {
+                    if let Some(row) = state.file_list.selected() {
+                        Control::Event(MDEvent::DeleteFile(state.sys.file(row).into()))
+                    } else {
+                        Control::Continue
+                    }
+                }
                 _ => Control::Continue,
             });
         }
@@ -238,6 +268,11 @@ impl FileListState {
         self.sys.root()
     }
 
+    /// Expanded directories of the tree.
+    pub fn expanded(&self) -> Vec<PathBuf> {
+        self.sys.expanded()
+    }
+
     /// Replace the file-system.
     pub fn replace_fs(&mut self, fs: FileSysStructure) {
         self.sys = fs;
@@ -306,6 +341,18 @@ impl FileListState {
         Ok(())
     }
 
+    /// Move the file to the trash. Returns false if
+    /// that's not possible.
+    pub fn trash(&mut self, file: &Path) -> bool {
+        match trash::delete(file) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("trash failed {:?}", e);
+                false
+            }
+        }
+    }
+
     /// Select this file.
     pub fn select(&mut self, file: &Path) -> Result<(), Error> {
         self.file_list.clear_selection();
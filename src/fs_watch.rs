@@ -0,0 +1,113 @@
+use anyhow::Error;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::warn;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Watches the root directory for changes.
+///
+/// The watcher only collects the changed paths, which are
+/// polled with a timer. This debounces bursts of events too.
+#[derive(Debug, Default)]
+pub struct FsWatch {
+    watcher: Option<RecommendedWatcher>,
+    root: PathBuf,
+    changed: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl FsWatch {
+    /// Watch this root. Noop if it is already watched.
+    pub fn watch(&mut self, root: &Path) -> Result<(), Error> {
+        if self.root == root || root.as_os_str().is_empty() {
+            return Ok(());
+        }
+
+        self.root = root.to_path_buf();
+        self.watcher = None;
+
+        let changed = self.changed.clone();
+        let root_path = self.root.clone();
+        let mut watcher = notify::recommended_watcher(move |ev: notify::Result<notify::Event>| {
+            match ev {
+                Ok(ev) => {
+                    if matches!(ev.kind, EventKind::Access(_)) {
+                        return;
+                    }
+                    let Ok(mut changed) = changed.lock() else {
+                        return;
+                    };
+                    if ev.paths.is_empty() {
+                        // something changed, somewhere.
+                        changed.push(root_path.clone());
+                    } else {
+                        changed.extend(ev.paths.into_iter().filter(|v| !is_own_temp(v)));
+                    }
+                }
+                Err(e) => warn!("watch {:?}", e),
+            }
+        })?;
+        watcher.watch(root, RecursiveMode::Recursive)?;
+        self.watcher = Some(watcher);
+
+        Ok(())
+    }
+
+    /// Paths changed since the last call. Hidden paths and
+    /// paths excluded by .gitignore or .ignore are left out,
+    /// as in the file list.
+    pub fn take_changed(&self) -> Vec<PathBuf> {
+        let Ok(mut changed) = self.changed.lock() else {
+            return Vec::new();
+        };
+        let mut changed = mem::take(&mut *changed);
+        changed.sort();
+        changed.dedup();
+
+        let mut ignores = HashMap::new();
+        changed.retain(|v| !is_ignored(&self.root, v, &mut ignores));
+        changed
+    }
+}
+
+/// Hidden or excluded by the ignore files of the root
+/// and the directories below it.
+fn is_ignored(root: &Path, path: &Path, ignores: &mut HashMap<PathBuf, Gitignore>) -> bool {
+    let Ok(rel) = path.strip_prefix(root) else {
+        return false;
+    };
+    if rel
+        .components()
+        .any(|v| v.as_os_str().to_string_lossy().starts_with('.'))
+    {
+        return true;
+    }
+
+    let is_dir = path.is_dir();
+    let mut dir = root.to_path_buf();
+    let mut rel_dirs = rel.parent().unwrap_or(Path::new("")).components();
+    loop {
+        let ignore = ignores.entry(dir.clone()).or_insert_with(|| {
+            let mut builder = GitignoreBuilder::new(&dir);
+            // missing files are fine.
+            _ = builder.add(dir.join(".gitignore"));
+            _ = builder.add(dir.join(".ignore"));
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        });
+        if ignore.matched_path_or_any_parents(path, is_dir).is_ignore() {
+            return true;
+        }
+        match rel_dirs.next() {
+            Some(v) => dir.push(v),
+            None => return false,
+        }
+    }
+}
+
+/// Backups and the temp files of a save.
+fn is_own_temp(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.ends_with(".bak") || name.ends_with(".mdedit-tmp")
+}
@@ -2,14 +2,53 @@ use anyhow::Error;
 use log::debug;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Progress of a background load.
+///
+/// Starting a new load supersedes the running one,
+/// which stops early and its result is dropped.
+#[derive(Debug, Default, Clone)]
+pub struct LoadProgress {
+    current: Arc<AtomicUsize>,
+    id: usize,
+    count: Arc<AtomicUsize>,
+}
+
+impl LoadProgress {
+    /// Start a new load. The result goes to the worker.
+    pub fn start(&self) -> LoadProgress {
+        let id = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.count.store(0, Ordering::Relaxed);
+        LoadProgress {
+            current: self.current.clone(),
+            id,
+            count: self.count.clone(),
+        }
+    }
+
+    /// Still the latest load?
+    pub fn is_current(&self) -> bool {
+        self.current.load(Ordering::SeqCst) == self.id
+    }
+
+    /// Entries visited so far.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+}
 
 /// Logic for the file-view.
 ///
 /// Contains both the file-system tree and the current files.
+/// The tree is loaded lazily, a directory is read when it
+/// is expanded.
 #[derive(Debug, Default)]
 pub struct FileSysStructure {
     root: PathBuf,
     name: String,
+    nodes: Vec<DirNode>,
     dirs: Vec<PathBuf>,
     display: Vec<String>,
 
@@ -20,6 +59,17 @@ pub struct FileSysStructure {
     files: Vec<PathBuf>,
 }
 
+/// Directory in the tree. The nodes are kept in display order.
+#[derive(Debug, Clone)]
+struct DirNode {
+    path: PathBuf,
+    name: String,
+    depth: usize,
+    expanded: bool,
+    /// Has subdirectories, checked when the parent is expanded.
+    has_dirs: bool,
+}
+
 // only needed for MDEvent ...
 impl PartialEq for FileSysStructure {
     fn eq(&self, _: &Self) -> bool {
@@ -34,6 +84,7 @@ impl FileSysStructure {
         Self {
             root: Default::default(),
             name: Default::default(),
+            nodes: Default::default(),
             dirs: Default::default(),
             display: Default::default(),
             is_cargo: Default::default(),
@@ -119,14 +170,23 @@ impl FileSysStructure {
     /// Limits the file list with globs.
     pub fn load(&mut self, path: &Path, globs: &[String]) -> Result<(), Error> {
         debug!("** load {:?} {:?}", path, globs);
-        self.load_current(path, globs)?;
         self.load_filesys(path)?;
+        self.load_current(path, globs)?;
         Ok(())
     }
 
     /// Loads only the file-system for the given path.
     /// Does the root finding though.
     pub fn load_filesys(&mut self, path: &Path) -> Result<(), Error> {
+        self.load_filesys_progress(path, &LoadProgress::default())
+    }
+
+    /// Loads the file-system and reports the progress.
+    pub fn load_filesys_progress(
+        &mut self,
+        path: &Path,
+        progress: &LoadProgress,
+    ) -> Result<(), Error> {
         let new_root = if let Some(v) = Self::find_root(path) {
             v
         } else {
@@ -141,8 +201,7 @@ impl FileSysStructure {
 
         self.name = String::default();
         self.root = new_root;
-        self.dirs.clear();
-        self.display.clear();
+        self.nodes.clear();
 
         if let Some(v) = cargo_name(&self.root)? {
             self.name = v;
@@ -162,19 +221,131 @@ impl FileSysStructure {
             self.is_mdbook = false;
         }
 
-        self.dirs.push(self.root.clone());
-        self.display.push(self.name.clone());
+        self.nodes.push(DirNode {
+            path: self.root.clone(),
+            name: self.name.clone(),
+            depth: 0,
+            expanded: false,
+            has_dirs: true,
+        });
+        self.expand_node(0, progress)?;
 
-        // fs_recurse(&self.root, "", &mut self.dirs, &mut self.display)?;
-        fs_recurse(&self.root, "", &mut self.dirs, &mut self.display)?;
+        Ok(())
+    }
+
+    /// Expand the tree down to the given directory.
+    /// The directory itself is expanded too.
+    pub fn expand(&mut self, path: &Path) -> Result<(), Error> {
+        if !path.starts_with(&self.root) {
+            return Ok(());
+        }
+
+        let mut idx = 0;
+        loop {
+            if idx >= self.nodes.len() {
+                return Ok(());
+            }
+            if !self.nodes[idx].expanded {
+                self.expand_node(idx, &LoadProgress::default())?;
+            }
+            if self.nodes[idx].path == path {
+                return Ok(());
+            }
+
+            // next step down
+            let depth = self.nodes[idx].depth;
+            let Some(next) = self.nodes[idx + 1..]
+                .iter()
+                .take_while(|v| v.depth > depth)
+                .position(|v| v.depth == depth + 1 && path.starts_with(&v.path))
+            else {
+                return Ok(());
+            };
+            idx = idx + 1 + next;
+        }
+    }
 
+    /// Directories that are expanded in the tree.
+    pub fn expanded(&self) -> Vec<PathBuf> {
+        self.nodes
+            .iter()
+            .filter(|v| v.expanded)
+            .map(|v| v.path.clone())
+            .collect()
+    }
+
+    /// Read the subdirectories of the node.
+    fn expand_node(&mut self, idx: usize, progress: &LoadProgress) -> Result<(), Error> {
+        let node = &self.nodes[idx];
+        let depth = node.depth + 1;
+        let children = read_dirs(&node.path, progress)?
+            .into_iter()
+            .map(|path| DirNode {
+                name: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                has_dirs: has_dirs(&path),
+                path,
+                depth,
+                expanded: false,
+            })
+            .collect::<Vec<_>>();
+
+        self.nodes[idx].expanded = true;
+        self.nodes.splice(idx + 1..idx + 1, children);
+        self.build_display();
         Ok(())
     }
 
+    /// Rebuild dirs + display from the nodes.
+    fn build_display(&mut self) {
+        self.dirs.clear();
+        self.display.clear();
+
+        // last child at this depth? going backwards, a sibling
+        // follows if its depth was seen since the last shallower node.
+        let mut last = vec![false; self.nodes.len()];
+        let mut follows: Vec<bool> = Vec::new();
+        for (idx, node) in self.nodes.iter().enumerate().rev() {
+            follows.resize(node.depth + 1, false);
+            last[idx] = !follows[node.depth];
+            follows[node.depth] = true;
+        }
+
+        // prefix for the children of each depth
+        let mut prefix: Vec<&str> = Vec::new();
+        for (idx, node) in self.nodes.iter().enumerate() {
+            prefix.truncate(node.depth.saturating_sub(1));
+
+            let more = if node.expanded || !node.has_dirs {
+                ""
+            } else {
+                " \u{2026}"
+            };
+            let display = if node.depth == 0 {
+                node.name.clone()
+            } else if last[idx] {
+                format!("{}\u{2514}{}{}", prefix.concat(), node.name, more)
+            } else {
+                format!("{}\u{251C}{}{}", prefix.concat(), node.name, more)
+            };
+            self.dirs.push(node.path.clone());
+            self.display.push(display);
+
+            if node.depth > 0 {
+                prefix.push(if last[idx] { " " } else { "\u{2502}" });
+            }
+        }
+    }
+
     /// Load the current directory listing.
     pub fn load_current(&mut self, path: &Path, globs: &[String]) -> Result<(), Error> {
         debug!("load current {:?} {:?}", path, globs);
 
+        self.expand(path)?;
+
         self.files_dir = path.into();
         self.files.clear();
 
@@ -254,109 +425,38 @@ fn cargo_name(cargo_dir: &Path) -> Result<Option<String>, Error> {
     Ok(None)
 }
 
-/// tree builder
-fn fs_recurse(
-    dir: &Path,
-    _prefix: &str,
-    dirs: &mut Vec<PathBuf>,
-    display: &mut Vec<String>,
-) -> Result<(), Error> {
-    #[derive(Debug, Default)]
-    struct Tree {
-        path: PathBuf,
-        name: String,
-        items: Vec<Tree>,
-    }
-
-    let mut tree = Tree::default();
+/// Subdirectories of the given directory.
+/// Uses the standard filters for ignored and hidden files.
+fn read_dirs(dir: &Path, progress: &LoadProgress) -> Result<Vec<PathBuf>, Error> {
+    let mut dirs = Vec::new();
 
     let walk = ignore::WalkBuilder::new(dir)
-        .standard_filters(true) //
+        .standard_filters(true)
+        .max_depth(Some(1))
         .build();
     for w in walk {
-        let w = w?;
-        let next = w.path().strip_prefix(dir)?;
-
-        let Some(parent) = next.parent() else {
-            continue;
-        };
-
-        let mut branch = &mut tree;
-        for c in parent.components() {
-            let c_str = c.as_os_str().to_string_lossy();
-            let c_str = c_str.as_ref();
-
-            let found = branch.items.iter().position(|v| v.name == c_str);
-            if let Some(found) = found {
-                branch = &mut branch.items[found];
-            } else {
-                let new = Tree {
-                    path: parent.to_path_buf(),
-                    name: c_str.to_string(),
-                    items: Vec::new(),
-                };
-                branch.items.push(new);
-                branch = branch.items.last_mut().expect("last");
-            }
-        }
-    }
-
-    let mut stack = Vec::new();
-
-    #[derive(Debug)]
-    struct TreeStack<'a> {
-        branch: &'a Tree,
-        idx: usize,
-        prefix: String,
-    }
-
-    stack.push(TreeStack {
-        branch: &tree,
-        idx: 0,
-        prefix: "".to_string(),
-    });
-
-    loop {
-        let Some(mut v) = stack.pop() else {
+        if !progress.is_current() {
+            // superseded
             break;
-        };
-
-        if v.idx >= v.branch.items.len() {
-            continue;
         }
+        progress.count.fetch_add(1, Ordering::Relaxed);
 
-        if v.idx + 1 == v.branch.items.len() {
-            let b = &v.branch.items[v.idx];
-            dirs.push(dir.join(&b.path));
-            display.push(format!("{}└{}", v.prefix, b.name));
-        } else {
-            let b = &v.branch.items[v.idx];
-            dirs.push(dir.join(&b.path));
-            display.push(format!("{}├{}", v.prefix, b.name));
-        }
-
-        let next = if v.idx + 1 == v.branch.items.len() {
-            TreeStack {
-                branch: &v.branch.items[v.idx],
-                idx: 0,
-                prefix: format!("{} ", v.prefix),
-            }
-        } else {
-            TreeStack {
-                branch: &v.branch.items[v.idx],
-                idx: 0,
-                prefix: format!("{}│", v.prefix),
-            }
-        };
-
-        v.idx += 1;
-        if v.idx < v.branch.items.len() {
-            stack.push(v);
-        }
-        if next.branch.items.len() > 0 {
-            stack.push(next);
+        let w = w?;
+        if w.depth() == 1 && w.file_type().is_some_and(|v| v.is_dir()) {
+            dirs.push(w.into_path());
         }
     }
+    dirs.sort();
+
+    Ok(dirs)
+}
 
-    Ok(())
+/// Any subdirectory that read_dirs() would list?
+fn has_dirs(dir: &Path) -> bool {
+    let walk = ignore::WalkBuilder::new(dir)
+        .standard_filters(true)
+        .max_depth(Some(1))
+        .build();
+    walk.flatten()
+        .any(|w| w.depth() == 1 && w.file_type().is_some_and(|v| v.is_dir()))
 }
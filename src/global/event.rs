@@ -1,6 +1,10 @@
+use crate::cfg::TableAlign;
+use crate::doc_type::{LinkPaths, LinkStyle};
 use crate::fsys::FileSysStructure;
+use crate::image_info::ImageInfo;
 use crate::rat_salsa::event::{QuitEvent, RenderedEvent};
 use crate::rat_salsa::timer::TimeOut;
+use crate::text_case::CaseMode;
 use crossbeam::atomic::AtomicCell;
 use ratatui::crossterm::event::Event;
 use std::path::PathBuf;
@@ -22,6 +26,8 @@ pub enum MDEvent {
     Message(String),
     // status flags
     Info(String),
+    // long running tasks
+    Status(String),
     //
     NoOp,
 
@@ -30,23 +36,89 @@ pub enum MDEvent {
     MenuOpen,
     MenuSave,
     MenuSaveAs,
+    MenuSaveAll,
+    MenuOpenGlob,
+    MenuExportSession,
+    MenuImportSession,
     MenuFormat,
     MenuFormatEq,
+    MenuFormatKeepBreaks,
+    CopyCodeBlock,
+    RunCodeBlock,
+    CodeBlockOutput(PathBuf, Result<String, String>),
+    #[cfg(feature = "scripting")]
+    MenuRunScript,
+    #[cfg(feature = "scripting")]
+    RunScript(String),
+    FormatOutput(PathBuf, Result<String, String>),
+    NormalizeLinks(LinkStyle),
+    ConvertLinkPaths(LinkPaths),
+    AlignTable(TableAlign),
+    SmartPunct,
+    ConvertHeading,
+    EscapeMd,
+    UnescapeMd,
+    ToggleBlockquote(bool),
+    ToggleComment,
+    WrapCodeFence(String),
+    ChangeCase(CaseMode),
+    /// Current date, with or without time.
+    InsertDate(bool),
+    PasteCodeBlock(String),
+    PasteText(String),
+    /// Confirmed large paste, and don't ask again.
+    PasteLarge(String, bool),
+    Search(String),
+    MenuCountTerm(bool),
+    CountTerm(String, bool),
+    SearchHl,
+    /// Loaded image metadata keyed by the image path.
+    /// Every file that links one of them takes it.
+    ImageInfo(Vec<(PathBuf, Option<ImageInfo>)>),
     CfgShowCtrl,
     CfgShowBreak,
     CfgShowLinenr,
     CfgWrapText,
+    CfgTabWidth,
+    CfgMdFlavor,
     SyncEdit,
     SyncFileList,
     New(PathBuf),
+    GenerateSample(usize),
     Open(PathBuf),
+    /// Open a large file after asking.
+    OpenLarge(PathBuf),
+    /// Open a large file as new split after asking.
+    OpenLargeSplit(PathBuf),
+    OpenGlob(String),
+    OpenFiles(Vec<PathBuf>),
     SelectOrOpen(PathBuf),
     SelectOrOpenSplit(PathBuf),
     SaveAs(PathBuf),
+    ExportSession(PathBuf),
+    ImportSession(PathBuf),
+    SaveFile(PathBuf),
+    SaveFiles(Vec<PathBuf>),
+    SaveAndQuit(Vec<PathBuf>),
+    SaveFailed(PathBuf, String),
+    SaveCreateDir(PathBuf),
+    DeleteFile(PathBuf),
+    /// Delete without trash, after asking.
+    DeleteFilePermanently(PathBuf),
+    MenuRenameFile(PathBuf),
+    RenameFile(String),
+    /// Files with links to the renamed file. Old and new path.
+    RenamedLinks(PathBuf, PathBuf, Vec<PathBuf>),
+    RewriteLinks(PathBuf, PathBuf, Vec<PathBuf>),
     FileSysChanged(Box<AtomicCell<FileSysStructure>>),
     FileSysReloaded(Box<AtomicCell<FileSysStructure>>),
+    /// Background load of the file-system failed.
+    FileSysFailed(String),
+    LinkTargets(PathBuf, Box<AtomicCell<Vec<PathBuf>>>),
     Save,
     Split,
+    SwapSplit,
+    ScrollSync,
     JumpToFileSplit,
     JumpToTree,
     JumpToFiles,
@@ -57,8 +129,12 @@ pub enum MDEvent {
     HideFiles,
     Close,
     CloseAll,
+    CloseOutsideRoot,
+    TogglePin,
+    PinFileList,
     CloseAt(usize, usize),
     SelectAt(usize, usize),
+    SelectLastTab,
     StoreConfig,
 }
 
@@ -1,11 +1,18 @@
 use crate::cfg::MDConfig;
+use crate::clipboard::MDClipboard;
+use crate::fsys::LoadProgress;
 use crate::global::event::MDEvent;
+use crate::image_preview::Graphics;
 use crate::rat_salsa::dialog_stack::DialogStack;
 use crate::rat_salsa::{SalsaAppContext, SalsaContext};
+#[cfg(feature = "scripting")]
+use crate::script::Scripts;
 use anyhow::Error;
 use rat_theme4::palette::Palette;
 use rat_theme4::theme::SalsaTheme;
 use rat_widget::hover::HoverState;
+use ratatui::layout::Rect;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub struct GlobalState {
@@ -14,6 +21,33 @@ pub struct GlobalState {
     pub theme: SalsaTheme,
     pub hover: HoverState,
     pub dialogs: DialogStack<MDEvent, GlobalState, Error>,
+    /// Terminal graphics.
+    pub graphics: Graphics,
+    /// Terminal supports OSC 8 hyperlinks.
+    pub hyperlinks: bool,
+    /// Clipboard for all editors.
+    pub clipboard: MDClipboard,
+    /// Image preview requested by the last render.
+    pub preview: Option<(PathBuf, Rect)>,
+    /// Running file-system load.
+    pub load: LoadProgress,
+    /// Terminal is unfocused, background timers are paused.
+    pub paused: bool,
+    /// Last search term.
+    pub search: String,
+    /// Recent search terms, newest last.
+    pub search_history: Vec<String>,
+    /// Highlight all matches of the last search.
+    pub hl_search: bool,
+    /// Paste mode, no auto-indent.
+    pub paste_mode: bool,
+    /// Don't ask before large pastes for the rest of the session.
+    pub paste_no_confirm: bool,
+    /// Files below a root for link completion.
+    pub link_targets: Option<(PathBuf, Vec<PathBuf>)>,
+    /// Script engine.
+    #[cfg(feature = "scripting")]
+    pub scripts: Scripts,
 }
 
 impl SalsaContext<MDEvent, Error> for GlobalState {
@@ -28,12 +62,27 @@ impl SalsaContext<MDEvent, Error> for GlobalState {
 
 impl GlobalState {
     pub fn new(cfg: MDConfig, theme: SalsaTheme) -> Self {
+        let clipboard = MDClipboard::new(cfg.clipboard);
         Self {
             ctx: Default::default(),
             cfg,
             theme,
             hover: Default::default(),
             dialogs: Default::default(),
+            graphics: Default::default(),
+            hyperlinks: false,
+            clipboard,
+            preview: None,
+            load: Default::default(),
+            paused: false,
+            search: Default::default(),
+            search_history: Default::default(),
+            hl_search: false,
+            paste_mode: false,
+            paste_no_confirm: false,
+            link_targets: None,
+            #[cfg(feature = "scripting")]
+            scripts: Default::default(),
         }
     }
 
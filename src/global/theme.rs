@@ -1,3 +1,5 @@
+use crate::highlight::{CODE_COMMENT, CODE_KEYWORD, CODE_NUMBER, CODE_STRING};
+use crate::search::SEARCH_MATCH;
 use rat_markdown::styles::MDStyle;
 use rat_theme4::palette::{ColorIdx, Colors};
 use rat_theme4::theme::SalsaTheme;
@@ -6,7 +8,7 @@ use rat_widget::choice::ChoiceStyle;
 use rat_widget::menu::MenuStyle;
 use rat_widget::scrolled::{ScrollStyle, ScrollSymbols};
 use rat_widget::text::TextStyle;
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::widgets::{Block, Borders};
 use std::collections::HashMap;
 
@@ -21,6 +23,9 @@ impl MDWidgets for WidgetStyle {}
 pub trait MDStyles {
     const TEXT_BASE: &'static str = "md+text-base";
     const STATUS_HIDDEN: &'static str = "md+status-hidden";
+    const CODE_BLOCK_BG: &'static str = "md+code-block-bg";
+    const TRAILING_WS: &'static str = "md+trailing-ws";
+    const TEXT_GUIDE: &'static str = "md+text-guide";
 }
 impl MDStyles for Style {}
 
@@ -122,6 +127,21 @@ pub fn create_mdedit_theme(name: &str) -> SalsaTheme {
         }
     }
 
+    // background only, the text styles keep their colors.
+    let code_bg = if theme.theme == "Light" {
+        theme.p.color(Colors::Gray, 0)
+    } else {
+        theme.p.color(Colors::Gray, 3)
+    };
+    theme.define_style(Style::CODE_BLOCK_BG, Style::new().bg(code_bg));
+    let trailing_bg = theme.p.color(Colors::Red, 2);
+    theme.define_style(Style::TRAILING_WS, Style::new().bg(trailing_bg));
+    let guide_fg = theme.p.color(Colors::Gray, 2);
+    theme.define_style(
+        Style::TEXT_GUIDE,
+        Style::new().fg(guide_fg).add_modifier(Modifier::DIM),
+    );
+
     theme.modify(WidgetStyle::SCROLL, |mut s: ScrollStyle, _| {
         s.horizontal = Some(ScrollSymbols {
             track: "─",
@@ -300,6 +320,13 @@ fn text_style(th: &SalsaTheme) -> HashMap<usize, Style> {
     map.insert(MDStyle::MathInline.into(), p.fg_style(Colors::RedPink, 1));
     map.insert(MDStyle::MetadataBlock.into(), p.fg_style(Colors::Orange, 1));
 
+    map.insert(CODE_KEYWORD, p.fg_style(Colors::Orange, 2));
+    map.insert(CODE_STRING, p.fg_style(Colors::BlueGreen, 1));
+    map.insert(CODE_COMMENT, p.fg_style(Colors::Gray, 2));
+    map.insert(CODE_NUMBER, p.fg_style(Colors::Yellow, 1));
+
+    map.insert(SEARCH_MATCH, p.fg_style(Colors::Yellow, 1).reversed());
+
     map
 }
 
@@ -392,5 +419,12 @@ fn text_style_light(th: &SalsaTheme) -> HashMap<usize, Style> {
     map.insert(MDStyle::MathInline.into(), p.fg_style(Colors::RedPink, 5));
     map.insert(MDStyle::MetadataBlock.into(), p.fg_style(Colors::Orange, 5));
 
+    map.insert(CODE_KEYWORD, p.fg_style(Colors::Orange, 6));
+    map.insert(CODE_STRING, p.fg_style(Colors::BlueGreen, 5));
+    map.insert(CODE_COMMENT, p.fg_style(Colors::Gray, 6));
+    map.insert(CODE_NUMBER, p.fg_style(Colors::Yellow, 5));
+
+    map.insert(SEARCH_MATCH, p.fg_style(Colors::Yellow, 5).reversed());
+
     map
 }
@@ -0,0 +1,313 @@
+//! Basic syntax highlighting for fenced code blocks.
+//!
+//! Keywords, strings, comments and numbers for a few common
+//! languages. Unknown languages keep the plain code block style.
+
+use std::ops::Range;
+
+/// Style ids for highlighted code.
+/// Outside the range used by MDStyle.
+pub const CODE_KEYWORD: usize = 1010;
+pub const CODE_STRING: usize = 1011;
+pub const CODE_COMMENT: usize = 1012;
+pub const CODE_NUMBER: usize = 1013;
+
+struct Lang {
+    keywords: &'static [&'static str],
+    line_comment: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Keywords are given in upper case.
+    ignore_case: bool,
+}
+
+const RUST: Lang = Lang {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    ignore_case: false,
+};
+
+const PYTHON: Lang = Lang {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+    line_comment: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    ignore_case: false,
+};
+
+const JS: Lang = Lang {
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "null",
+        "return",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "type",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    ignore_case: false,
+};
+
+const C: Lang = Lang {
+    keywords: &[
+        "auto",
+        "bool",
+        "break",
+        "case",
+        "char",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "double",
+        "else",
+        "enum",
+        "extern",
+        "false",
+        "final",
+        "float",
+        "for",
+        "func",
+        "go",
+        "if",
+        "import",
+        "int",
+        "interface",
+        "long",
+        "namespace",
+        "new",
+        "nil",
+        "null",
+        "package",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "short",
+        "signed",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "true",
+        "typedef",
+        "unsigned",
+        "var",
+        "void",
+        "while",
+    ],
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    ignore_case: false,
+};
+
+const SHELL: Lang = Lang {
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "return", "then", "until", "while",
+    ],
+    line_comment: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    ignore_case: false,
+};
+
+const JSON: Lang = Lang {
+    keywords: &["true", "false", "null"],
+    line_comment: &[],
+    block_comment: None,
+    quotes: &['"'],
+    ignore_case: false,
+};
+
+const TOML: Lang = Lang {
+    keywords: &["true", "false"],
+    line_comment: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    ignore_case: false,
+};
+
+const SQL: Lang = Lang {
+    keywords: &[
+        "AND", "AS", "BY", "CREATE", "DELETE", "DISTINCT", "DROP", "FALSE", "FROM", "GROUP",
+        "HAVING", "IN", "INSERT", "INTO", "IS", "JOIN", "LEFT", "LIMIT", "NOT", "NULL", "ON", "OR",
+        "ORDER", "SELECT", "SET", "TABLE", "TRUE", "UNION", "UPDATE", "VALUES", "WHERE", "WITH",
+    ],
+    line_comment: &["--"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\''],
+    ignore_case: true,
+};
+
+const LUA: Lang = Lang {
+    keywords: &[
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in",
+        "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+    ],
+    line_comment: &["--"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    ignore_case: false,
+};
+
+/// Language from the info-string.
+fn lang(info: &str) -> Option<&'static Lang> {
+    let name = info
+        .split([',', ' ', '{'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match name.as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "python" | "py" => Some(&PYTHON),
+        "javascript" | "js" | "typescript" | "ts" => Some(&JS),
+        "c" | "cpp" | "c++" | "h" | "java" | "go" | "cs" | "csharp" => Some(&C),
+        "sh" | "bash" | "shell" | "zsh" => Some(&SHELL),
+        "json" => Some(&JSON),
+        "toml" | "ini" => Some(&TOML),
+        "sql" => Some(&SQL),
+        "lua" => Some(&LUA),
+        _ => None,
+    }
+}
+
+/// Line comment of the language, if it has one.
+pub fn line_comment(info: &str) -> Option<&'static str> {
+    lang(info)?.line_comment.first().copied()
+}
+
+/// Style ranges for the code. Relative to the start of the code.
+pub fn highlight(info: &str, code: &str) -> Vec<(Range<usize>, usize)> {
+    let Some(lang) = lang(info) else {
+        return Vec::new();
+    };
+
+    let mut styles = Vec::new();
+    let mut pos = 0;
+    while pos < code.len() {
+        let rest = &code[pos..];
+        let c = rest.chars().next().expect("char");
+
+        if let Some((start, end)) = lang.block_comment {
+            if rest.starts_with(start) {
+                let len = rest[start.len()..]
+                    .find(end)
+                    .map(|v| start.len() + v + end.len())
+                    .unwrap_or(rest.len());
+                styles.push((pos..pos + len, CODE_COMMENT));
+                pos += len;
+                continue;
+            }
+        }
+        if lang.line_comment.iter().any(|v| rest.starts_with(v)) {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            styles.push((pos..pos + len, CODE_COMMENT));
+            pos += len;
+            continue;
+        }
+        if lang.quotes.contains(&c) {
+            let len = string_len(rest, c);
+            styles.push((pos..pos + len, CODE_STRING));
+            pos += len;
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let len = rest
+                .find(|v: char| !(v.is_ascii_alphanumeric() || v == '_' || v == '.'))
+                .unwrap_or(rest.len());
+            styles.push((pos..pos + len, CODE_NUMBER));
+            pos += len;
+            continue;
+        }
+        if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|v: char| !(v.is_alphanumeric() || v == '_'))
+                .unwrap_or(rest.len());
+            let is_keyword = if lang.ignore_case {
+                lang.keywords
+                    .contains(&rest[..len].to_ascii_uppercase().as_str())
+            } else {
+                lang.keywords.contains(&&rest[..len])
+            };
+            if is_keyword {
+                styles.push((pos..pos + len, CODE_KEYWORD));
+            }
+            pos += len;
+            continue;
+        }
+        pos += c.len_utf8();
+    }
+    styles
+}
+
+/// Length of a string literal up to and including the closing quote.
+/// Unterminated strings end at the line end.
+fn string_len(txt: &str, quote: char) -> usize {
+    let mut escape = false;
+    for (idx, c) in txt.char_indices().skip(1) {
+        if escape {
+            escape = false;
+        } else if c == '\\' {
+            escape = true;
+        } else if c == quote {
+            return idx + c.len_utf8();
+        } else if c == '\n' {
+            return idx;
+        }
+    }
+    txt.len()
+}
@@ -0,0 +1,72 @@
+//! OSC 8 hyperlinks.
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use std::env;
+use unicode_width::UnicodeWidthStr;
+
+/// Guess from the environment whether the terminal
+/// understands OSC 8.
+///
+/// Terminals that don't know it are supposed to ignore it,
+/// but some print the url or break the layout.
+pub fn detect() -> bool {
+    if env::var("KITTY_WINDOW_ID").is_ok() || env::var("WT_SESSION").is_ok() {
+        return true;
+    }
+    if let Ok(term) = env::var("TERM") {
+        if term == "xterm-kitty" || term == "xterm-ghostty" || term == "foot" || term == "alacritty"
+        {
+            return true;
+        }
+    }
+    if let Ok(term) = env::var("TERM_PROGRAM") {
+        if term == "WezTerm" || term == "ghostty" || term == "iTerm.app" || term == "vscode" {
+            return true;
+        }
+    }
+    if let Ok(vte) = env::var("VTE_VERSION") {
+        if vte.parse::<u32>().is_ok_and(|v| v >= 5000) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Wrap the cells of the area in a hyperlink.
+///
+/// The escape sequence is added to the symbol of every
+/// second cell, the cell after that is skipped. This keeps
+/// the width calculation of the buffer intact. A wide glyph
+/// already covers two cells and is wrapped alone, a single
+/// cell before a wide glyph is left out.
+pub fn render(area: Rect, url: &str, buf: &mut Buffer) {
+    if url.chars().any(|c| c.is_control()) {
+        return;
+    }
+    let area = area.intersection(buf.area);
+    for y in area.top()..area.bottom() {
+        let mut x = area.left();
+        while x < area.right() {
+            let width = buf[(x, y)].symbol().width() as u16;
+            if width > 1 {
+                let txt = buf[(x, y)].symbol().to_string();
+                buf[(x, y)].set_symbol(&osc8(url, &txt));
+                x += width;
+                continue;
+            }
+            if x + 1 < area.right() && buf[(x + 1, y)].symbol().width() == 1 {
+                let txt = format!("{}{}", buf[(x, y)].symbol(), buf[(x + 1, y)].symbol());
+                buf[(x, y)].set_symbol(&osc8(url, &txt));
+                buf[(x + 1, y)].set_skip(true);
+                x += 2;
+            } else {
+                x += 1;
+            }
+        }
+    }
+}
+
+fn osc8(url: &str, txt: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, txt)
+}
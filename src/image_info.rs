@@ -0,0 +1,94 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Metadata of an image file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// File size in bytes.
+    pub size: u64,
+    /// Width x height if the format is known.
+    pub dim: Option<(u32, u32)>,
+    /// Modification time, to notice a changed image.
+    pub modified: Option<SystemTime>,
+}
+
+impl ImageInfo {
+    /// Read the metadata. None if the file doesn't exist.
+    pub fn load(path: &Path) -> Option<ImageInfo> {
+        let meta = fs::metadata(path).ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        Some(ImageInfo {
+            size: meta.len(),
+            dim: image_dimensions(path),
+            modified: meta.modified().ok(),
+        })
+    }
+
+    /// Modification time of the file, None if it doesn't exist.
+    pub fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).ok()?.modified().ok()
+    }
+
+    /// Short display text.
+    pub fn display(&self) -> String {
+        let size = if self.size >= 1024 * 1024 {
+            format!("{:.1} MiB", self.size as f64 / (1024.0 * 1024.0))
+        } else if self.size >= 1024 {
+            format!("{} KiB", self.size / 1024)
+        } else {
+            format!("{} B", self.size)
+        };
+        if let Some((w, h)) = self.dim {
+            format!("{} {}\u{00D7}{}", size, w, h)
+        } else {
+            size
+        }
+    }
+}
+
+/// Reads the dimensions from the image header.
+/// Knows png, gif and jpeg.
+pub fn image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let mut f = File::open(path).ok()?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let len = f.read(&mut buf).ok()?;
+    let buf = &buf[..len];
+
+    if buf.starts_with(b"\x89PNG\r\n\x1a\n") && buf.len() >= 24 {
+        let w = u32::from_be_bytes(buf[16..20].try_into().ok()?);
+        let h = u32::from_be_bytes(buf[20..24].try_into().ok()?);
+        Some((w, h))
+    } else if (buf.starts_with(b"GIF87a") || buf.starts_with(b"GIF89a")) && buf.len() >= 10 {
+        let w = u16::from_le_bytes(buf[6..8].try_into().ok()?) as u32;
+        let h = u16::from_le_bytes(buf[8..10].try_into().ok()?) as u32;
+        Some((w, h))
+    } else if buf.starts_with(b"\xff\xd8") {
+        jpeg_dimensions(buf)
+    } else {
+        None
+    }
+}
+
+/// Find the SOFn marker.
+fn jpeg_dimensions(buf: &[u8]) -> Option<(u32, u32)> {
+    let mut idx = 2;
+    while idx + 9 < buf.len() {
+        if buf[idx] != 0xff {
+            return None;
+        }
+        let marker = buf[idx + 1];
+        let len = u16::from_be_bytes([buf[idx + 2], buf[idx + 3]]) as usize;
+        if matches!(marker, 0xc0..=0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf) {
+            let h = u16::from_be_bytes([buf[idx + 5], buf[idx + 6]]) as u32;
+            let w = u16::from_be_bytes([buf[idx + 7], buf[idx + 8]]) as u32;
+            return Some((w, h));
+        }
+        idx += 2 + len;
+    }
+    None
+}
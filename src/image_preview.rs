@@ -0,0 +1,131 @@
+use anyhow::Error;
+use ratatui::layout::Rect;
+use std::env;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::Path;
+
+/// Graphics capabilities of the terminal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Graphics {
+    /// Text only.
+    #[default]
+    None,
+    /// Kitty graphics protocol.
+    Kitty,
+}
+
+impl Graphics {
+    /// Guess the capabilities from the environment.
+    ///
+    /// Sixel would need a decoder for the image data,
+    /// so only kitty is supported. Everything else falls
+    /// back to the text annotation.
+    pub fn detect() -> Graphics {
+        if env::var("KITTY_WINDOW_ID").is_ok() {
+            return Graphics::Kitty;
+        }
+        if let Ok(term) = env::var("TERM") {
+            if term == "xterm-kitty" || term == "xterm-ghostty" {
+                return Graphics::Kitty;
+            }
+        }
+        if let Ok(term) = env::var("TERM_PROGRAM") {
+            if term == "WezTerm" || term == "ghostty" {
+                return Graphics::Kitty;
+            }
+        }
+        Graphics::None
+    }
+
+    /// Can show this image?
+    pub fn supports(&self, path: &Path) -> bool {
+        match self {
+            Graphics::None => false,
+            Graphics::Kitty => path
+                .extension()
+                .is_some_and(|v| v.eq_ignore_ascii_case("png")),
+        }
+    }
+}
+
+/// Show the image in the given area.
+pub fn show(graphics: Graphics, path: &Path, area: Rect) -> Result<(), Error> {
+    match graphics {
+        Graphics::None => Ok(()),
+        Graphics::Kitty => kitty_show(path, area),
+    }
+}
+
+/// Remove all images.
+pub fn clear(graphics: Graphics) -> Result<(), Error> {
+    match graphics {
+        Graphics::None => Ok(()),
+        Graphics::Kitty => {
+            let mut out = stdout();
+            out.write_all(b"\x1b_Ga=d,q=2\x1b\\")?;
+            out.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn kitty_show(path: &Path, area: Rect) -> Result<(), Error> {
+    let data = fs::read(path)?;
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Ok(());
+    }
+    let data = base64(&data);
+
+    let mut out = stdout().lock();
+    // save cursor, move to area
+    write!(out, "\x1b7\x1b[{};{}H", area.y + 1, area.x + 1)?;
+    let mut chunks = data.as_bytes().chunks(4096).peekable();
+    let mut first = true;
+    while let Some(chunk) = chunks.next() {
+        let more = if chunks.peek().is_some() { 1 } else { 0 };
+        if first {
+            write!(
+                out,
+                "\x1b_Gf=100,a=T,q=2,C=1,c={},r={},m={};",
+                area.width, area.height, more
+            )?;
+            first = false;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+    // restore cursor
+    out.write_all(b"\x1b8")?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut res = String::with_capacity(data.len().div_ceil(3) * 4);
+    for c in data.chunks(3) {
+        let b = [
+            c[0],
+            c.get(1).copied().unwrap_or(0),
+            c.get(2).copied().unwrap_or(0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        res.push(CHARS[(n >> 18) as usize & 63] as char);
+        res.push(CHARS[(n >> 12) as usize & 63] as char);
+        res.push(if c.len() > 1 {
+            CHARS[(n >> 6) as usize & 63] as char
+        } else {
+            '='
+        });
+        res.push(if c.len() > 2 {
+            CHARS[n as usize & 63] as char
+        } else {
+            '='
+        });
+    }
+    res
+}
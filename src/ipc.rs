@@ -0,0 +1,200 @@
+//! Local IPC server for external tools.
+//!
+//! Started with `mdedit --ipc`. Listens on a unix socket in the
+//! runtime directory that only the user can access. The token
+//! is written next to it, both are removed on exit.
+//!
+//! ```text
+//! $XDG_RUNTIME_DIR/mdedit-<pid>.sock
+//! $XDG_RUNTIME_DIR/mdedit-<pid>.token
+//! ```
+//!
+//! One request per connection. The first line is the token
+//! and the command, for set-buffer everything after it is the
+//! new text. The answer is 'ok' or 'err <msg>' on the first line,
+//! followed by the result.
+//!
+//! ```text
+//! <token> get-buffer
+//! <token> set-buffer\n<text>
+//! <token> get-cursor
+//! <token> open-file <path>
+//! ```
+
+use anyhow::{anyhow, Error};
+use log::warn;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use std::{env, process};
+
+/// Max time to wait for the editor to answer.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Max time to wait for the client to send the request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Commands of the protocol.
+#[derive(Debug)]
+pub enum IpcCommand {
+    GetBuffer,
+    SetBuffer(String),
+    GetCursor,
+    OpenFile(PathBuf),
+}
+
+/// Request waiting for the editor.
+#[derive(Debug)]
+pub struct IpcRequest {
+    pub command: IpcCommand,
+    reply: Sender<Result<String, String>>,
+}
+
+impl IpcRequest {
+    /// Send the answer to the client.
+    pub fn reply(self, result: Result<String, String>) {
+        // client may be gone already
+        _ = self.reply.send(result);
+    }
+}
+
+/// The server. The listener runs in its own thread and
+/// collects the requests, which are polled with a timer.
+#[derive(Debug)]
+pub struct IpcServer {
+    pub socket: PathBuf,
+    token_file: PathBuf,
+    requests: Arc<Mutex<Vec<IpcRequest>>>,
+}
+
+impl IpcServer {
+    /// Bind the socket and start listening.
+    pub fn start() -> Result<IpcServer, Error> {
+        let dir = dirs::runtime_dir().unwrap_or(env::temp_dir());
+        let socket = dir.join(format!("mdedit-{}.sock", process::id()));
+        let token_file = dir.join(format!("mdedit-{}.token", process::id()));
+
+        let token = new_token()?;
+        _ = fs::remove_file(&token_file);
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&token_file)?
+            .write_all(token.as_bytes())?;
+
+        _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)?;
+        fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))?;
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let thread_requests = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let requests = thread_requests.clone();
+                let token = token.clone();
+                thread::spawn(move || {
+                    if let Err(e) = serve(stream, &token, &requests) {
+                        warn!("ipc {:?}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(IpcServer {
+            socket,
+            token_file,
+            requests,
+        })
+    }
+
+    /// Requests since the last call.
+    pub fn take_requests(&self) -> Vec<IpcRequest> {
+        match self.requests.lock() {
+            Ok(mut v) => v.drain(..).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        _ = fs::remove_file(&self.socket);
+        _ = fs::remove_file(&self.token_file);
+    }
+}
+
+/// Handle one connection.
+fn serve(stream: UnixStream, token: &str, requests: &Mutex<Vec<IpcRequest>>) -> Result<(), Error> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut it = line.trim_end().splitn(3, ' ');
+    let (Some(client_token), Some(cmd)) = (it.next(), it.next()) else {
+        writeln!(writer, "err invalid request")?;
+        return Ok(());
+    };
+    if !same_token(client_token, token) {
+        writeln!(writer, "err invalid token")?;
+        return Ok(());
+    }
+    let arg = it.next().unwrap_or_default();
+
+    let command = match cmd {
+        "get-buffer" => IpcCommand::GetBuffer,
+        "set-buffer" => {
+            let mut text = String::new();
+            reader.read_to_string(&mut text)?;
+            IpcCommand::SetBuffer(text)
+        }
+        "get-cursor" => IpcCommand::GetCursor,
+        "open-file" if !arg.is_empty() => IpcCommand::OpenFile(PathBuf::from(arg)),
+        _ => {
+            writeln!(writer, "err unknown command {}", cmd)?;
+            return Ok(());
+        }
+    };
+
+    let (reply, answer) = channel();
+    requests
+        .lock()
+        .map_err(|_| anyhow!("poisoned"))?
+        .push(IpcRequest { command, reply });
+
+    match answer.recv_timeout(REPLY_TIMEOUT) {
+        Ok(Ok(v)) => {
+            writeln!(writer, "ok")?;
+            writer.write_all(v.as_bytes())?;
+        }
+        Ok(Err(e)) => writeln!(writer, "err {}", e)?,
+        Err(_) => writeln!(writer, "err timeout")?,
+    }
+    Ok(())
+}
+
+/// Compare without leaking the position of the first difference.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Random token from the system random source.
+fn new_token() -> Result<String, Error> {
+    let mut buf = [0u8; 32];
+    fs::File::open("/dev/urandom")?.read_exact(&mut buf)?;
+    Ok(buf.iter().map(|v| format!("{:02x}", v)).collect())
+}
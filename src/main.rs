@@ -7,20 +7,30 @@ pub(crate) use rat_salsa;
 #[cfg(all(feature = "wgpu", not(feature = "term")))]
 pub(crate) use rat_salsa_wgpu as rat_salsa;
 
-use crate::cfg::MDConfig;
+use crate::cfg::{EditorLayout, MDConfig, OnFocusLost, OnQuit, Startup, TableAlign};
 use crate::config_dlg::ConfigDialogState;
 use crate::dlg::config_dlg;
+use crate::doc_type::{LinkPaths, LinkStyle};
 use crate::editor::MDEditState;
-use crate::fsys::FileSysStructure;
+use crate::fs_watch::FsWatch;
+use crate::fsys::{FileSysStructure, LoadProgress};
 use crate::global::event::MDEvent;
 use crate::global::theme::{create_mdedit_theme, MDStyles, MDWidgets};
 use crate::global::GlobalState;
+#[cfg(feature = "term")]
+use crate::image_preview::Graphics;
+#[cfg(unix)]
+use crate::ipc::{IpcCommand, IpcServer};
+use crate::text_case::CaseMode;
 use anyhow::Error;
 use crossbeam::atomic::AtomicCell;
 use crossbeam::channel::SendError;
 use dirs::cache_dir;
-use dlg::{file_dlg, msg_dialog};
-use log::error;
+use dlg::choice_dlg::ChoiceDialogState;
+use dlg::dirty_dlg::DirtyDialogState;
+use dlg::input_dlg::InputDialogState;
+use dlg::{choice_dlg, dirty_dlg, file_dlg, input_dlg, msg_dialog};
+use log::{error, warn};
 #[cfg(all(feature = "wgpu", not(feature = "term")))]
 use rat_salsa::events::ConvertCrossterm;
 #[cfg(feature = "term")]
@@ -29,6 +39,8 @@ use rat_salsa::poll::{PollQuit, PollRendered, PollTasks, PollTimers};
 use rat_salsa::timer::{TimerDef, TimerHandle};
 use rat_salsa::{run_tui, Control, RunConfig, SalsaContext};
 #[cfg(all(feature = "wgpu", not(feature = "term")))]
+use rat_salsa_wgpu::font::FontData;
+#[cfg(all(feature = "wgpu", not(feature = "term")))]
 use rat_salsa_wgpu::poll::PollBlink;
 use rat_theme4::{StyleName, WidgetStyle};
 use rat_widget::event::{ct_event, try_flow, HandleEvent, MenuOutcome, Popup};
@@ -47,20 +59,41 @@ use ratatui::widgets::{Block, StatefulWidget, Widget};
 use std::cmp::max;
 use std::env::args;
 use std::fs::create_dir_all;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::from_utf8;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use std::{env, fs, mem};
 
 mod cfg;
+mod clipboard;
+mod command;
+mod completion;
+mod date;
 mod dlg;
 mod doc_type;
 mod editor;
 mod editor_file;
 mod file_list;
+mod fs_watch;
 mod fsys;
 mod global;
+mod highlight;
+mod hyperlink;
+mod image_info;
+mod image_preview;
+#[cfg(unix)]
+mod ipc;
+mod md_list;
+mod paste;
+mod sample;
+#[cfg(feature = "scripting")]
+mod script;
+mod search;
+mod session;
+mod smart_punct;
 mod split_tab;
+mod text_case;
+mod text_width;
 
 #[cfg(all(feature = "wgpu", not(feature = "term")))]
 static MD_ICON: &'static [u8] = include_bytes!("md.raw");
@@ -70,11 +103,26 @@ fn main() -> Result<(), Error> {
 
     let mut config = MDConfig::load()?;
 
-    let mut args = args();
+    let mut args = args().peekable();
     args.next();
     config.load_file = {
         let mut load = Vec::new();
-        for arg1 in args {
+        while let Some(arg1) = args.next() {
+            // hidden: generate a test document with n sections.
+            if arg1 == "--sample" {
+                config.sample = match args.peek().and_then(|v| v.parse().ok()) {
+                    Some(n) => {
+                        args.next();
+                        n
+                    }
+                    None => 100,
+                };
+                continue;
+            }
+            if arg1 == "--ipc" {
+                config.ipc = true;
+                continue;
+            }
             for path in glob::glob(&arg1)? {
                 let mut path = path?;
                 // need __some__ parent directory
@@ -98,9 +146,15 @@ fn main() -> Result<(), Error> {
             .poll(PollRendered)
             .poll(PollQuit);
         if !config.font.is_empty() {
-            r = r.font_family(config.font.clone());
+            if FontData.installed_fonts().contains(&config.font) {
+                r = r.font_family(config.font.clone());
+            } else {
+                warn!("font '{}' is not installed, using the default", config.font);
+            }
         }
-        r = r.font_size(config.font_size.max(7.0));
+        r = r.font_size(config.font_size.clamp(7.0, 72.0));
+        r = r.ligatures(config.ligatures);
+        r = r.line_spacing(config.line_spacing);
         r
     };
     #[cfg(feature = "term")]
@@ -113,6 +167,11 @@ fn main() -> Result<(), Error> {
 
     let theme = create_mdedit_theme(&config.theme);
     let mut global = GlobalState::new(config, theme);
+    #[cfg(feature = "term")]
+    {
+        global.graphics = Graphics::detect();
+        global.hyperlinks = hyperlink::detect();
+    }
     let mut state = Scenery::default();
 
     run_tui(init, render, event, error, &mut global, &mut state, run_cfg)?;
@@ -126,6 +185,8 @@ struct Menu {
     show_break: bool,
     wrap_text: bool,
     show_linenr: bool,
+    focus_mode: bool,
+    hide_menu: bool,
 }
 
 impl<'a> MenuStructure<'a> for Menu {
@@ -141,14 +202,55 @@ impl<'a> MenuStructure<'a> for Menu {
             0 => {
                 submenu.item_parsed("_New..|Ctrl-N");
                 submenu.item_parsed("_Open..|Ctrl-O");
+                submenu.item_parsed("Open _glob..|Ctrl-Shift-O");
                 submenu.item_parsed("_Save..|Ctrl-S");
                 submenu.item_parsed("Save _as..");
+                submenu.item_parsed("Save a_ll..|Ctrl-Shift-S");
+                submenu.item_parsed("\\___");
+                submenu.item_parsed("_Export session..");
+                submenu.item_parsed("_Import session..");
+                submenu.item_parsed("_Close|Ctrl-E");
+                submenu.item_parsed("Close files outside _root");
                 submenu.item_parsed("\\___");
                 submenu.item_parsed("_Configure");
             }
             1 => {
                 submenu.item_parsed("Format Item|F8");
                 submenu.item_parsed("Alt-Format Item|F7");
+                submenu.item_parsed("Format keep breaks");
+                submenu.item_parsed("Copy code block|Ctrl-Alt-C");
+                submenu.item_parsed("Run code block|Ctrl-Alt-R");
+                submenu.separator(Separator::Dotted);
+                submenu.item_parsed("Links to reference");
+                submenu.item_parsed("Links to inline");
+                submenu.item_parsed("Link paths relative");
+                submenu.item_parsed("Link paths root-relative");
+                submenu.separator(Separator::Dotted);
+                submenu.item_parsed("Table align left");
+                submenu.item_parsed("Table align center");
+                submenu.item_parsed("Table align right");
+                submenu.item_parsed("Table align none");
+                submenu.separator(Separator::Dotted);
+                submenu.item_parsed("Smart punctuation");
+                submenu.item_parsed("Heading ATX/Setext");
+                submenu.item_parsed("Escape markdown");
+                submenu.item_parsed("Unescape markdown");
+                submenu.item_parsed("Blockquote|Ctrl-Alt-Q");
+                submenu.item_parsed("Remove blockquote|Ctrl-Alt-U");
+                submenu.item_parsed("Toggle comment|Ctrl-/");
+                submenu.item_parsed("Upper case|Ctrl-U");
+                submenu.item_parsed("Lower case|Ctrl-Shift-U");
+                submenu.item_parsed("Title case|Ctrl-Alt-T");
+                submenu.item_parsed("Insert date|Ctrl-Alt-D");
+                submenu.item_parsed("Insert date and time");
+                submenu.separator(Separator::Dotted);
+                submenu.item_parsed("Count occurrences|Ctrl-Shift-F");
+                submenu.item_parsed("Count in project");
+                #[cfg(feature = "scripting")]
+                {
+                    submenu.separator(Separator::Dotted);
+                    submenu.item_parsed("Run script..");
+                }
             }
             2 => {
                 if self.show_ctrl {
@@ -171,6 +273,16 @@ impl<'a> MenuStructure<'a> for Menu {
                 } else {
                     submenu.item_parsed("\u{2610} Line numbers|Alt-L");
                 }
+                if self.focus_mode {
+                    submenu.item_parsed("\u{2611} Focus mode|Alt-D");
+                } else {
+                    submenu.item_parsed("\u{2610} Focus mode|Alt-D");
+                }
+                if self.hide_menu {
+                    submenu.item_parsed("\u{2611} Hide menu bar|Alt-M");
+                } else {
+                    submenu.item_parsed("\u{2610} Hide menu bar|Alt-M");
+                }
                 submenu.separator(Separator::Dotted);
                 submenu.item_parsed("_Split view|Ctrl-W D");
                 submenu.item_parsed("_Jump to Tree|F4");
@@ -189,6 +301,25 @@ pub struct Scenery {
     pub short: String,
     pub info: String,
     pub clear_status: TimerHandle,
+    /// Progress of background loads.
+    pub status: String,
+    pub load_timer: Option<TimerHandle>,
+    pub load_tick: usize,
+    /// Debounce reload on focus-gained.
+    pub reload_timer: Option<TimerHandle>,
+    /// Modification time of root and current dir at the last load.
+    pub dir_mtime: (Option<SystemTime>, Option<SystemTime>),
+    /// Watch the root for changes.
+    pub fs_watch: FsWatch,
+    pub watch_timer: TimerHandle,
+    /// Server for external tools.
+    #[cfg(unix)]
+    pub ipc: Option<IpcServer>,
+    pub ipc_timer: Option<TimerHandle>,
+    /// Image preview currently on screen.
+    pub preview_shown: Option<(PathBuf, Rect)>,
+    /// Unsaved files have been dealt with.
+    pub quit_confirmed: bool,
 
     pub window_cmd: bool,
 }
@@ -201,6 +332,18 @@ impl Default for Scenery {
             short: Default::default(),
             info: Default::default(),
             clear_status: Default::default(),
+            status: Default::default(),
+            load_timer: None,
+            load_tick: 0,
+            reload_timer: None,
+            dir_mtime: (None, None),
+            fs_watch: Default::default(),
+            watch_timer: Default::default(),
+            #[cfg(unix)]
+            ipc: None,
+            ipc_timer: None,
+            preview_shown: None,
+            quit_confirmed: false,
             window_cmd: false,
         };
         s
@@ -213,12 +356,15 @@ pub fn render(
     state: &mut Scenery,
     ctx: &mut GlobalState,
 ) -> Result<(), Error> {
+    // a hidden menu only shows up while it has the focus.
+    let show_menu = !ctx.cfg.hide_menu || state.menu.is_focused();
     let r = Layout::vertical([
         Constraint::Fill(1), //
-        Constraint::Length(1),
+        Constraint::Length(if show_menu { 1 } else { 0 }),
     ])
     .split(area);
 
+    ctx.preview = None;
     editor::render(r[0], buf, &mut state.editor, ctx)?;
 
     let s = Layout::horizontal([
@@ -232,6 +378,8 @@ pub fn render(
         show_break: ctx.cfg.show_break,
         wrap_text: ctx.cfg.wrap_text,
         show_linenr: ctx.cfg.show_linenr,
+        focus_mode: ctx.cfg.focus_mode,
+        hide_menu: ctx.cfg.hide_menu,
     };
     let (menu, menu_popup) = Menubar::new(&menu_struct)
         .title("^^°n°^^")
@@ -246,10 +394,13 @@ pub fn render(
         .into_widgets();
     menu.render(s[0], buf, &mut state.menu);
 
+    // leave half of the status line for the short/status part
+    let info = text_width::truncate(state.info.as_str(), s[1].width / 2);
     StatusLineStacked::new()
         .start_bare(state.short.as_str())
+        .start(state.status.as_str(), " ")
         .end_bare("]")
-        .end(state.info.as_str(), "[")
+        .end(info.as_ref(), "[")
         .style(if state.menu.is_focused() {
             ctx.theme.style(Style::STATUS_BASE)
         } else {
@@ -291,66 +442,361 @@ pub fn init(state: &mut Scenery, ctx: &mut GlobalState) -> Result<(), Error> {
     state.menu.bar.select(Some(0));
     state.short = format!("mdedit {}", env!("CARGO_PKG_VERSION"));
     state.clear_status = ctx.add_timer(TimerDef::new().timer(Duration::from_secs(1)));
+    state.watch_timer = ctx.add_timer(
+        TimerDef::new()
+            .repeat_forever()
+            .timer(Duration::from_millis(500)),
+    );
 
-    fn spawn_load_dir(path: PathBuf, ctx: &mut GlobalState) -> Result<(), SendError<()>> {
-        let cfg = ctx.cfg.globs.clone();
-        ctx.spawn(move || {
-            let mut sys = FileSysStructure::new();
-            sys.load_filesys(&path)?;
-
-            if sys.is_mdbook() {
-                let src_path = path.join("src");
-                sys.load_current(&src_path, &cfg)?;
-            } else {
-                sys.load_current(&path, &cfg)?;
-            }
-
-            Ok(Control::Event(MDEvent::FileSysChanged(
-                Box::new(AtomicCell::new(sys)), //
-            )))
-        })?;
-        Ok(())
+    if ctx.cfg.ipc {
+        start_ipc(state, ctx);
     }
 
-    if !ctx.cfg.load_file.is_empty() {
-        for load in mem::take(&mut ctx.cfg.load_file) {
+    let load_file = mem::take(&mut ctx.cfg.load_file);
+    if load_file.len() == 1 && load_file[0].is_dir() {
+        // project directory
+        let root = project_root(&load_file[0]);
+        if let Some(layout) = ctx.cfg.layout_for(&root).cloned() {
+            _ = restore_layout(&layout, state, ctx)?;
+        }
+        ctx.cfg.session_root = Some(root);
+        spawn_load_dir(load_file[0].clone(), state, ctx)?;
+    } else if !load_file.is_empty() {
+        for load in load_file {
             if load.is_dir() {
-                spawn_load_dir(load, ctx)?;
-            } else {
-                _ = state.editor.open(&load, ctx)?;
+                spawn_load_dir(load, state, ctx)?;
+            } else if let Err(e) = state.editor.open(&load, ctx) {
+                ctx.queue_event(MDEvent::Message(format!("{:#}", e)));
             }
         }
         _ = state.editor.select_tab_at(0, 0, ctx)?;
         _ = state.editor.sync_file_list(true, ctx)?;
-    } else if !ctx.cfg.tab_state.is_empty() {
-        for (s, t, load) in ctx.cfg.tab_state.clone() {
-            _ = state.editor.open_in((s, t), &load, ctx)?;
-        }
-        for (s, t, x, y) in ctx.cfg.tab_cursor.clone() {
-            if let Some(edit) = state.editor.editor_at(s, t) {
-                edit.edit.set_cursor((x, y), false);
+    } else if ctx.cfg.sample > 0 {
+        ctx.queue_event(MDEvent::GenerateSample(ctx.cfg.sample));
+    } else {
+        let dir = match &ctx.cfg.startup_dir {
+            Some(dir) if dir.is_dir() => dir.clone(),
+            _ => env::current_dir()?,
+        };
+        let root = project_root(&dir);
+        match ctx.cfg.startup {
+            Startup::Restore => {
+                ctx.cfg.session_root = Some(root.clone());
+                let layout = ctx
+                    .cfg
+                    .layout_for(&root)
+                    .cloned()
+                    .unwrap_or_else(|| ctx.cfg.layout.clone());
+                // the files may be gone
+                if !restore_layout(&layout, state, ctx)? {
+                    spawn_load_dir(dir, state, ctx)?;
+                }
             }
-        }
-        for (s, t, x, y, z) in ctx.cfg.tab_offset.clone() {
-            if let Some(edit) = state.editor.editor_at(s, t) {
-                edit.edit.set_offset((x, y));
-                edit.edit.set_sub_row_offset(z);
+            Startup::OpenDir => {
+                ctx.cfg.session_root = Some(root);
+                spawn_load_dir(dir, state, ctx)?;
             }
+            Startup::Empty => {}
         }
-        _ = state
-            .editor
-            .select_tab_at(ctx.cfg.tab_selected.0, ctx.cfg.tab_selected.1, ctx)?;
-        state
-            .editor
-            .split_tab
-            .split
-            .set_area_lengths(ctx.cfg.edit_split_at.clone());
-        _ = state.editor.sync_file_list(true, ctx)?;
+    }
+
+    Ok(())
+}
+
+/// Project root for the directory.
+fn project_root(path: &Path) -> PathBuf {
+    let path = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    FileSysStructure::find_root(&path).unwrap_or(path)
+}
+
+/// Open the files of a stored layout.
+/// Returns false if none of the files exist.
+fn restore_layout(
+    layout: &EditorLayout,
+    state: &mut Scenery,
+    ctx: &mut GlobalState,
+) -> Result<bool, Error> {
+    let layout = layout.existing();
+    if layout.tab_state.is_empty() {
+        return Ok(false);
+    }
+    for (s, t, load) in layout.tab_state.iter() {
+        _ = state.editor.open_in((*s, *t), load, ctx)?;
+    }
+    for (s, t, x, y) in layout.tab_cursor.iter() {
+        if let Some(edit) = state.editor.editor_at(*s, *t) {
+            edit.edit.set_cursor((*x, *y), false);
+        }
+    }
+    for (s, t) in layout.tab_pinned.iter() {
+        if let Some(edit) = state.editor.editor_at(*s, *t) {
+            edit.pinned = true;
+        }
+    }
+    for (s, t, x, y) in layout.tab_anchor.iter() {
+        if let Some(edit) = state.editor.editor_at(*s, *t) {
+            let cursor = edit.edit.cursor();
+            edit.edit.set_selection((*x, *y), cursor);
+        }
+    }
+    for (s, t, x, y, z) in layout.tab_offset.iter() {
+        if let Some(edit) = state.editor.editor_at(*s, *t) {
+            edit.edit.set_offset((*x, *y));
+            edit.edit.set_sub_row_offset(*z);
+        }
+    }
+    _ = state
+        .editor
+        .select_tab_at(layout.tab_selected.0, layout.tab_selected.1, ctx)?;
+    state
+        .editor
+        .split_tab
+        .split
+        .set_area_lengths(layout.edit_split_at.clone());
+    _ = state.editor.sync_file_list(true, ctx)?;
+    Ok(true)
+}
+
+/// Replace the open files with the session.
+/// Missing files are reported, the rest is opened anyway.
+fn import_session(
+    path: &Path,
+    state: &mut Scenery,
+    ctx: &mut GlobalState,
+) -> Result<Control<MDEvent>, Error> {
+    let layout = session::import(path)?;
+    if !state.editor.close_all_splits(ctx)? {
+        return Ok(Control::Event(MDEvent::Message(
+            "Can't close all files, import cancelled.".into(),
+        )));
+    }
+
+    let missing = layout
+        .tab_state
+        .iter()
+        .filter(|(_, _, v)| !v.exists())
+        .map(|(_, _, v)| v.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    _ = restore_layout(&layout, state, ctx)?;
+
+    if missing.is_empty() {
+        Ok(Control::Event(MDEvent::Info(format!(
+            "Imported {}",
+            path.display()
+        ))))
     } else {
-        let cwd = env::current_dir()?;
-        spawn_load_dir(cwd, ctx)?;
+        Ok(Control::Event(MDEvent::Message(format!(
+            "Files not found:\n{}",
+            missing.join("\n")
+        ))))
+    }
+}
+
+/// Modification time of a directory.
+fn dir_mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+/// File that is open in the editor and still exists.
+/// Changes to it don't change the file list.
+fn is_open(state: &Scenery, path: &Path) -> bool {
+    let Ok(path) = fs::canonicalize(path) else {
+        return false;
+    };
+    state
+        .editor
+        .split_tab
+        .split_tab_file
+        .iter()
+        .flatten()
+        .any(|v| fs::canonicalize(&v.path).is_ok_and(|v| v == path))
+}
+
+/// Start the ipc server and poll for requests.
+/// This timer isn't paused, the requests come while
+/// another program has the focus.
+#[cfg(unix)]
+fn start_ipc(state: &mut Scenery, ctx: &mut GlobalState) {
+    match IpcServer::start() {
+        Ok(ipc) => {
+            ctx.queue_event(MDEvent::Info(format!("ipc at {}", ipc.socket.display())));
+            state.ipc = Some(ipc);
+            state.ipc_timer = Some(
+                ctx.add_timer(
+                    TimerDef::new()
+                        .repeat_forever()
+                        .timer(Duration::from_millis(100)),
+                ),
+            );
+        }
+        Err(e) => {
+            ctx.queue_event(MDEvent::Message(format!("Can't start ipc: {:#}", e)));
+        }
     }
+}
+
+#[cfg(not(unix))]
+fn start_ipc(_state: &mut Scenery, ctx: &mut GlobalState) {
+    ctx.queue_event(MDEvent::Message("--ipc is only supported on unix.".into()));
+}
+
+/// Answer the waiting ipc requests.
+#[cfg(unix)]
+fn handle_ipc(state: &mut Scenery, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+    let Some(ipc) = &state.ipc else {
+        return Ok(Control::Continue);
+    };
+
+    let mut r = Control::Continue;
+    for req in ipc.take_requests() {
+        let selected = state.editor.split_tab.selected_mut().map(|(_, md)| md);
+        match (&req.command, selected) {
+            (IpcCommand::OpenFile(path), _) => {
+                ctx.queue_event(MDEvent::SelectOrOpen(path.clone()));
+                req.reply(Ok(String::new()));
+            }
+            (_, None) => {
+                req.reply(Err("no file open".into()));
+            }
+            (IpcCommand::GetBuffer, Some(md)) => {
+                let text = md.edit.text();
+                req.reply(Ok(text));
+            }
+            (IpcCommand::GetCursor, Some(md)) => {
+                let cursor = md.edit.cursor();
+                req.reply(Ok(format!("{} {}\n", cursor.x, cursor.y)));
+            }
+            (IpcCommand::SetBuffer(text), Some(md)) => {
+                if md.read_only {
+                    req.reply(Err(format!("{} is opened read-only", md.path.display())));
+                } else {
+                    r = max(r, md.replace_text(text, ctx));
+                    req.reply(Ok(String::new()));
+                }
+            }
+        }
+    }
+    Ok(r)
+}
+
+#[cfg(not(unix))]
+fn handle_ipc(_state: &mut Scenery, _ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+    Ok(Control::Continue)
+}
+
+/// Stop the repeating timers while the terminal is unfocused.
+/// A running load continues, only the spinner stops.
+fn pause_timers(state: &mut Scenery, ctx: &mut GlobalState) {
+    ctx.paused = true;
+    ctx.remove_timer(state.watch_timer);
+    if let Some(load_timer) = state.load_timer {
+        ctx.remove_timer(load_timer);
+    }
+}
+
+/// Restart the timers stopped by pause_timers.
+fn resume_timers(state: &mut Scenery, ctx: &mut GlobalState) {
+    ctx.paused = false;
+    state.watch_timer = ctx.add_timer(
+        TimerDef::new()
+            .repeat_forever()
+            .timer(Duration::from_millis(500)),
+    );
+    if state.load_timer.is_some() {
+        state.load_timer = Some(
+            ctx.replace_timer(
+                state.load_timer,
+                TimerDef::new()
+                    .repeat_forever()
+                    .timer(Duration::from_millis(100)),
+            ),
+        );
+    }
+}
+
+/// Start a new background load with status updates.
+/// A running load is superseded.
+fn start_load(state: &mut Scenery, ctx: &mut GlobalState) -> LoadProgress {
+    state.load_timer = Some(
+        ctx.replace_timer(
+            state.load_timer,
+            TimerDef::new()
+                .repeat_forever()
+                .timer(Duration::from_millis(100)),
+        ),
+    );
+    ctx.load.start()
+}
+
+/// Load the directory tree and files in the background.
+fn spawn_load_dir(
+    path: PathBuf,
+    state: &mut Scenery,
+    ctx: &mut GlobalState,
+) -> Result<(), SendError<()>> {
+    let cfg = ctx.cfg.globs.clone();
+    let progress = start_load(state, ctx);
+    ctx.spawn(move || {
+        let mut sys = FileSysStructure::new();
+        let r = sys.load_filesys_progress(&path, &progress).and_then(|_| {
+            if !progress.is_current() {
+                return Ok(());
+            }
+            if sys.is_mdbook() {
+                let src_path = path.join("src");
+                sys.load_current(&src_path, &cfg)
+            } else {
+                sys.load_current(&path, &cfg)
+            }
+        });
+        if !progress.is_current() {
+            return Ok(Control::Continue);
+        }
+
+        match r {
+            Ok(()) => Ok(Control::Event(MDEvent::FileSysChanged(
+                Box::new(AtomicCell::new(sys)), //
+            ))),
+            Err(e) => Ok(Control::Event(MDEvent::FileSysFailed(format!("{:#}", e)))),
+        }
+    })?;
+    Ok(())
+}
+
+/// Reload the current directory tree and files in the background.
+fn spawn_reload_dir(state: &mut Scenery, ctx: &mut GlobalState) -> Result<(), SendError<()>> {
+    let cfg = ctx.cfg.globs.clone();
+    let root = state.editor.file_list.root().to_path_buf();
+    let current = state.editor.file_list.current_dir().to_path_buf();
+    // keep what the user has expanded.
+    let expanded = state.editor.file_list.expanded();
+    let progress = start_load(state, ctx);
+    ctx.spawn(move || {
+        let mut sys = FileSysStructure::new();
+        let r = sys.load_filesys_progress(&root, &progress).and_then(|_| {
+            for dir in &expanded {
+                if !progress.is_current() {
+                    return Ok(());
+                }
+                // may be gone or unreadable by now.
+                _ = sys.expand(dir);
+            }
+            if !progress.is_current() {
+                return Ok(());
+            }
+            sys.load_current(&current, &cfg)
+        });
+        if !progress.is_current() {
+            return Ok(Control::Continue);
+        }
 
+        match r {
+            Ok(()) => Ok(Control::Event(MDEvent::FileSysReloaded(
+                Box::new(AtomicCell::new(sys)), //
+            ))),
+            Err(e) => Ok(Control::Event(MDEvent::FileSysFailed(format!("{:#}", e)))),
+        }
+    })?;
     Ok(())
 }
 
@@ -397,13 +843,42 @@ pub fn event(
                 ct_event!(keycode press CONTROL_SHIFT-F(4)) => Control::Event(MDEvent::CloseAll),
                 ct_event!(key press CONTROL-'n') => Control::Event(MDEvent::MenuNew),
                 ct_event!(key press CONTROL-'o') => Control::Event(MDEvent::MenuOpen),
+                ct_event!(key press CONTROL_SHIFT-'O') => Control::Event(MDEvent::MenuOpenGlob),
                 ct_event!(key press CONTROL-'s') => Control::Event(MDEvent::MenuSave),
+                ct_event!(key press CONTROL_SHIFT-'S') => Control::Event(MDEvent::MenuSaveAll),
+                ct_event!(keycode press Esc)
+                    if state
+                        .editor
+                        .split_tab
+                        .selected()
+                        .is_some_and(|(_, v)| v.completion.is_some()) =>
+                {
+                    if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                        sel.completion = None;
+                    }
+                    Control::Changed
+                }
                 ct_event!(keycode press Esc) => flip_esc_focus(state, ctx)?,
                 ct_event!(keycode press F(1)) => show_help(ctx)?,
                 ct_event!(keycode press F(2)) => show_cheat(ctx)?,
                 ct_event!(keycode press F(4)) => Control::Event(MDEvent::JumpToFiles),
                 ct_event!(keycode press F(5)) => Control::Event(MDEvent::JumpToTree),
                 ct_event!(keycode press F(6)) => Control::Event(MDEvent::HideFiles),
+                ct_event!(key press ALT-'h') => {
+                    ctx.hl_search = !ctx.hl_search;
+                    Control::Event(MDEvent::SearchHl)
+                }
+                ct_event!(keycode press F(9)) => {
+                    ctx.paste_mode = !ctx.paste_mode;
+                    Control::Event(MDEvent::Info(
+                        if ctx.paste_mode {
+                            "paste mode on"
+                        } else {
+                            "paste mode off"
+                        }
+                        .into(),
+                    ))
+                }
                 #[cfg(all(feature = "wgpu", not(feature = "term")))]
                 ct_event!(keycode press F(11)) => {
                     match ctx.window().fullscreen() {
@@ -442,25 +917,48 @@ pub fn event(
                     ctx.queue_event(MDEvent::CfgShowLinenr);
                     Control::Changed
                 }
+                ct_event!(key press ALT-'d') => {
+                    ctx.cfg.focus_mode = !ctx.cfg.focus_mode;
+                    ctx.queue_event(MDEvent::StoreConfig);
+                    Control::Changed
+                }
+                ct_event!(key press ALT-'m') => {
+                    ctx.cfg.hide_menu = !ctx.cfg.hide_menu;
+                    ctx.queue_event(MDEvent::StoreConfig);
+                    Control::Changed
+                }
                 ct_event!(key press CONTROL-'w') => {
                     state.window_cmd = true;
                     Control::Changed
                 }
+                Event::Paste(txt) if paste::dropped_file(txt).is_some() => {
+                    open_dropped_file(txt, ctx)?
+                }
                 ct_event!(focus_gained) => {
-                    let cfg = ctx.cfg.globs.clone();
-                    let root = state.editor.file_list.root().to_path_buf();
-                    let current = state.editor.file_list.current_dir().to_path_buf();
-                    ctx.spawn(move || {
-                        let mut sys = FileSysStructure::new();
-                        sys.load_filesys(&root)?;
-                        sys.load_current(&current, &cfg)?;
-                        Ok(Control::Event(MDEvent::FileSysReloaded(
-                            Box::new(AtomicCell::new(sys)), //
-                        )))
-                    })?;
+                    if ctx.paused {
+                        resume_timers(state, ctx);
+                    }
+                    state.reload_timer = Some(ctx.replace_timer(
+                        state.reload_timer,
+                        TimerDef::new().next(Instant::now() + Duration::from_millis(300)),
+                    ));
                     Control::Continue
                 }
-                ct_event!(focus_lost) => Control::Event(MDEvent::Save),
+                ct_event!(focus_lost) => {
+                    if let Some(reload_timer) = state.reload_timer.take() {
+                        ctx.remove_timer(reload_timer);
+                    }
+                    if ctx.cfg.pause_unfocused {
+                        pause_timers(state, ctx);
+                    }
+                    match ctx.cfg.on_focus_lost {
+                        OnFocusLost::Save => Control::Event(MDEvent::Save),
+                        OnFocusLost::Mark if state.editor.split_tab.is_changed() => {
+                            Control::Event(MDEvent::Info("unsaved changes".into()))
+                        }
+                        OnFocusLost::Mark | OnFocusLost::Nothing => Control::Continue,
+                    }
+                }
                 _ => Control::Continue,
             });
 
@@ -474,14 +972,88 @@ pub fn event(
                 // rebuild keyboard + mouse focus
                 ctx.set_focus(FocusBuilder::rebuild_for(state, ctx.take_focus()));
                 // ctx.focus().enable_log();
+                // graphics are written after the text
+                if ctx.preview != state.preview_shown {
+                    image_preview::clear(ctx.graphics)?;
+                    if let Some((path, area)) = &ctx.preview {
+                        image_preview::show(ctx.graphics, path, *area)?;
+                    }
+                    state.preview_shown = ctx.preview.clone();
+                }
                 Control::Continue
             });
         }
         MDEvent::Quit => {
             try_flow!({
-                _ = state.editor.save(ctx)?;
                 _ = store_config(state, ctx);
-                Control::Quit
+                if state.quit_confirmed {
+                    Control::Quit
+                } else {
+                    match ctx.cfg.on_quit {
+                        OnQuit::Save => {
+                            _ = state.editor.save(ctx)?;
+                            if state.editor.split_tab.is_changed() {
+                                // save failed, the prompt is queued.
+                                Control::Changed
+                            } else {
+                                Control::Quit
+                            }
+                        }
+                        OnQuit::Prompt => {
+                            let dirty = state.editor.split_tab.changed_files();
+                            if dirty.is_empty() {
+                                Control::Quit
+                            } else {
+                                ctx.dialogs.push(
+                                    dirty_dlg::render,
+                                    dirty_dlg::event,
+                                    DirtyDialogState::new_quit(dirty),
+                                );
+                                Control::Changed
+                            }
+                        }
+                        OnQuit::Discard => Control::Quit,
+                    }
+                }
+            });
+        }
+        MDEvent::SaveAndQuit(files) => {
+            try_flow!({
+                let mut failed = false;
+                for path in files {
+                    _ = state.editor.save_file(path, ctx)?;
+                    if let Some((_, md)) = state.editor.split_tab.for_path(path) {
+                        failed |= md.changed;
+                    }
+                }
+                if failed {
+                    Control::Changed
+                } else {
+                    state.quit_confirmed = true;
+                    Control::Quit
+                }
+            });
+        }
+        MDEvent::Status(s) => {
+            try_flow!({
+                state.status = s.clone();
+                Control::Changed
+            });
+        }
+        MDEvent::FileSysChanged(_) | MDEvent::FileSysReloaded(_) => {
+            // no try_flow, the editor needs this too.
+            if let Some(load_timer) = state.load_timer.take() {
+                ctx.remove_timer(load_timer);
+            }
+            ctx.queue_event(MDEvent::Status(String::new()));
+        }
+        MDEvent::FileSysFailed(msg) => {
+            try_flow!({
+                if let Some(load_timer) = state.load_timer.take() {
+                    ctx.remove_timer(load_timer);
+                }
+                ctx.queue_event(MDEvent::Status(String::new()));
+                show_message(msg, ctx)
             });
         }
         MDEvent::Info(s) => {
@@ -511,18 +1083,60 @@ pub fn event(
                 Control::Changed
             });
         }
+        MDEvent::MenuOpenGlob => {
+            try_flow!({
+                ctx.dialogs.push(
+                    input_dlg::render,
+                    input_dlg::event,
+                    InputDialogState::new("Open files", "Glob", |v| MDEvent::OpenGlob(v)),
+                );
+                Control::Changed
+            });
+        }
         MDEvent::MenuSave => {
             try_flow!(Control::Event(MDEvent::Save));
         }
         MDEvent::MenuSaveAs => {
             try_flow!({
+                let ext = match state.editor.split_tab.selected() {
+                    Some((_, t)) => t.extension(),
+                    None => "md".to_string(),
+                };
                 let mut state = FileDialogState::new();
-                state.save_dialog_ext(PathBuf::from("."), "", "pas")?;
+                state.save_dialog_ext(PathBuf::from("."), "", ext)?;
                 ctx.dialogs
                     .push(file_dlg::render, file_dlg::event_save_as, state);
                 Control::Changed
             });
         }
+        MDEvent::MenuExportSession => {
+            try_flow!({
+                let mut state = FileDialogState::new();
+                state.save_dialog_ext(PathBuf::from("."), "", session::EXTENSION)?;
+                ctx.dialogs
+                    .push(file_dlg::render, file_dlg::event_export_session, state);
+                Control::Changed
+            });
+        }
+        MDEvent::MenuImportSession => {
+            try_flow!({
+                let mut state = FileDialogState::new();
+                state.open_dialog(PathBuf::from("."))?;
+                ctx.dialogs
+                    .push(file_dlg::render, file_dlg::event_import_session, state);
+                Control::Changed
+            });
+        }
+        MDEvent::ExportSession(path) => {
+            try_flow!({
+                let layout = ctx.cfg.current_layout(&state.editor.split_tab);
+                session::export(&layout, path)?;
+                Control::Event(MDEvent::Info(format!("Exported {}", path.display())))
+            });
+        }
+        MDEvent::ImportSession(path) => {
+            try_flow!(import_session(path, state, ctx)?);
+        }
         MDEvent::StoreConfig => {
             try_flow!(store_config(state, ctx));
         }
@@ -530,6 +1144,38 @@ pub fn event(
             try_flow!(if t.handle == state.clear_status {
                 state.short = Default::default();
                 Control::Changed
+            } else if t.handle == state.watch_timer {
+                // follows the root of the file list
+                if let Err(e) = state.fs_watch.watch(state.editor.file_list.root()) {
+                    warn!("watch {:?}", e);
+                }
+                // saving a file syncs the file list already.
+                let changed = state.fs_watch.take_changed();
+                if changed.iter().any(|v| !is_open(state, v)) {
+                    spawn_reload_dir(state, ctx)?;
+                }
+                Control::Continue
+            } else if Some(t.handle) == state.ipc_timer {
+                handle_ipc(state, ctx)?
+            } else if Some(t.handle) == state.reload_timer {
+                state.reload_timer = None;
+                let dir_mtime = (
+                    dir_mtime(state.editor.file_list.root()),
+                    dir_mtime(state.editor.file_list.current_dir()),
+                );
+                if dir_mtime.0.is_none() || dir_mtime != state.dir_mtime {
+                    state.dir_mtime = dir_mtime;
+                    spawn_reload_dir(state, ctx)?;
+                }
+                Control::Continue
+            } else if Some(t.handle) == state.load_timer {
+                const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+                state.load_tick = state.load_tick.wrapping_add(1);
+                Control::Event(MDEvent::Status(format!(
+                    "{} loading {}",
+                    SPINNER[state.load_tick % SPINNER.len()],
+                    ctx.load.count()
+                )))
             } else {
                 Control::Continue
             });
@@ -603,6 +1249,17 @@ fn window_cmd(
             ctx.queue(Control::Changed);
             Control::Continue
         }
+        ct_event!(key press SHIFT-'X') => Control::Event(MDEvent::SwapSplit),
+        ct_event!(key press SHIFT-'S') => Control::Event(MDEvent::ScrollSync),
+        ct_event!(key press CONTROL-'p') | ct_event!(key press 'p') => {
+            Control::Event(MDEvent::TogglePin)
+        }
+        ct_event!(key press CONTROL-'l') | ct_event!(key press 'l') => {
+            Control::Event(MDEvent::PinFileList)
+        }
+        ct_event!(key press CONTROL-'w') | ct_event!(key press 'w') => {
+            Control::Event(MDEvent::SelectLastTab)
+        }
         ct_event!(key press CONTROL-'c')
         | ct_event!(key press 'c')
         | ct_event!(key press 'x')
@@ -648,14 +1305,38 @@ fn handle_menu(
         }
         MenuOutcome::MenuActivated(0, 2) => {
             _ = flip_esc_focus(state, ctx)?;
-            Control::Event(MDEvent::MenuSave)
+            Control::Event(MDEvent::MenuOpenGlob)
         }
         MenuOutcome::MenuActivated(0, 3) => {
             _ = flip_esc_focus(state, ctx)?;
-            Control::Event(MDEvent::MenuSaveAs)
+            Control::Event(MDEvent::MenuSave)
         }
         MenuOutcome::MenuActivated(0, 4) => {
             _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::MenuSaveAs)
+        }
+        MenuOutcome::MenuActivated(0, 5) => {
+            _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::MenuSaveAll)
+        }
+        MenuOutcome::MenuActivated(0, 6) => {
+            _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::MenuExportSession)
+        }
+        MenuOutcome::MenuActivated(0, 7) => {
+            _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::MenuImportSession)
+        }
+        MenuOutcome::MenuActivated(0, 8) => {
+            _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::Close)
+        }
+        MenuOutcome::MenuActivated(0, 9) => {
+            _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::CloseOutsideRoot)
+        }
+        MenuOutcome::MenuActivated(0, 10) => {
+            _ = flip_esc_focus(state, ctx)?;
 
             let state = ConfigDialogState::new(ctx)?;
             ctx.dialogs
@@ -677,7 +1358,268 @@ fn handle_menu(
 
             if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
                 ctx.focus().focus(sel);
-                Control::Event(MDEvent::MenuFormat)
+                Control::Event(MDEvent::MenuFormatEq)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 2) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::MenuFormatKeepBreaks)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 3) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::CopyCodeBlock)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 4) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::RunCodeBlock)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 5) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::NormalizeLinks(LinkStyle::Reference))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 6) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::NormalizeLinks(LinkStyle::Inline))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 7) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ConvertLinkPaths(LinkPaths::Relative))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 8) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ConvertLinkPaths(LinkPaths::RootRelative))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 9) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::AlignTable(TableAlign::Left))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 10) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::AlignTable(TableAlign::Center))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 11) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::AlignTable(TableAlign::Right))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 12) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::AlignTable(TableAlign::None))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 13) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::SmartPunct)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 14) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ConvertHeading)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 15) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::EscapeMd)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 16) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::UnescapeMd)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 17) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ToggleBlockquote(true))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 18) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ToggleBlockquote(false))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 19) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ToggleComment)
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 20) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ChangeCase(CaseMode::Upper))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 21) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ChangeCase(CaseMode::Lower))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 22) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::ChangeCase(CaseMode::Title))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 23) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::InsertDate(false))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 24) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::InsertDate(true))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 25) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::MenuCountTerm(false))
+            } else {
+                Control::Continue
+            }
+        }
+        MenuOutcome::MenuActivated(1, 26) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::MenuCountTerm(true))
+            } else {
+                Control::Continue
+            }
+        }
+        #[cfg(feature = "scripting")]
+        MenuOutcome::MenuActivated(1, 27) => {
+            _ = flip_esc_focus(state, ctx)?;
+
+            if let Some((_, sel)) = state.editor.split_tab.selected_mut() {
+                ctx.focus().focus(sel);
+                Control::Event(MDEvent::MenuRunScript)
             } else {
                 Control::Continue
             }
@@ -716,17 +1658,31 @@ fn handle_menu(
         }
         MenuOutcome::MenuActivated(2, 4) => {
             _ = flip_esc_focus(state, ctx)?;
-            Control::Event(MDEvent::Split)
+
+            ctx.cfg.focus_mode = !ctx.cfg.focus_mode;
+            ctx.queue_event(MDEvent::StoreConfig);
+            Control::Changed
         }
         MenuOutcome::MenuActivated(2, 5) => {
             _ = flip_esc_focus(state, ctx)?;
-            Control::Event(MDEvent::JumpToTree)
+
+            ctx.cfg.hide_menu = !ctx.cfg.hide_menu;
+            ctx.queue_event(MDEvent::StoreConfig);
+            Control::Changed
         }
         MenuOutcome::MenuActivated(2, 6) => {
             _ = flip_esc_focus(state, ctx)?;
-            Control::Event(MDEvent::JumpToFiles)
+            Control::Event(MDEvent::Split)
         }
         MenuOutcome::MenuActivated(2, 7) => {
+            _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::JumpToTree)
+        }
+        MenuOutcome::MenuActivated(2, 8) => {
+            _ = flip_esc_focus(state, ctx)?;
+            Control::Event(MDEvent::JumpToFiles)
+        }
+        MenuOutcome::MenuActivated(2, 9) => {
             _ = flip_esc_focus(state, ctx)?;
             Control::Event(MDEvent::HideFiles)
         }
@@ -758,6 +1714,21 @@ fn flip_esc_focus(state: &mut Scenery, ctx: &mut GlobalState) -> Result<Control<
     }
 }
 
+/// Ask whether a pasted file path should be opened or
+/// pasted as text.
+fn open_dropped_file(txt: &str, ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
+    let Some(path) = paste::dropped_file(txt) else {
+        return Ok(Control::Continue);
+    };
+    let state = ChoiceDialogState::new("Open file", path.display().to_string().as_str())
+        .choice("Open", MDEvent::Open(path))
+        .choice("Paste", MDEvent::PasteText(txt.to_string()))
+        .choice("Cancel", MDEvent::NoOp);
+    ctx.dialogs
+        .push(choice_dlg::render, choice_dlg::event, state);
+    Ok(Control::Changed)
+}
+
 fn show_help(ctx: &mut GlobalState) -> Result<Control<MDEvent>, Error> {
     let txt = from_utf8(HELP)?;
     let mut txt2 = String::new();
@@ -0,0 +1,122 @@
+//! List item indent and numbering.
+
+/// Start of a list item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemMarker {
+    /// Leading whitespace in bytes.
+    pub indent: usize,
+    /// End of the number/bullet in bytes.
+    pub marker_end: usize,
+    /// Number and delimiter of an ordered item.
+    pub ordered: Option<(u32, char)>,
+}
+
+/// Parse the list marker of the line.
+pub fn item_marker(line: &str) -> Option<ItemMarker> {
+    let body = line.trim_start_matches([' ', '\t']);
+    let indent = line.len() - body.len();
+
+    let mut chars = body.char_indices();
+    let (_, first) = chars.next()?;
+    if matches!(first, '-' | '*' | '+') {
+        return match body[1..].chars().next() {
+            None | Some(' ') | Some('\t') => Some(ItemMarker {
+                indent,
+                marker_end: indent + 1,
+                ordered: None,
+            }),
+            _ => None,
+        };
+    }
+
+    let digits = body.find(|c: char| !c.is_ascii_digit())?;
+    if digits == 0 || digits > 9 {
+        return None;
+    }
+    let delim = body[digits..].chars().next()?;
+    if !matches!(delim, '.' | ')') {
+        return None;
+    }
+    match body[digits + 1..].chars().next() {
+        None | Some(' ') | Some('\t') => Some(ItemMarker {
+            indent,
+            marker_end: indent + digits + 1,
+            ordered: Some((body[..digits].parse().ok()?, delim)),
+        }),
+        _ => None,
+    }
+}
+
+/// Add or remove up to width spaces of leading indent.
+/// Never removes anything but whitespace.
+pub fn shift_line(line: &str, width: usize, outdent: bool) -> String {
+    if outdent {
+        let n = line
+            .chars()
+            .take(width)
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .count();
+        line[n..].to_string()
+    } else if line.is_empty() {
+        String::new()
+    } else {
+        format!("{}{}", " ".repeat(width), line)
+    }
+}
+
+/// Renumber the ordered lists in the block of lines.
+/// Each list keeps the number of its first item, except
+/// the first item is one of the restart lines.
+pub fn renumber(lines: &mut [String], restart: &[usize]) {
+    // (indent, next number) per nesting level. None for bullet lists.
+    let mut levels: Vec<(usize, Option<u32>)> = Vec::new();
+
+    for (idx, line) in lines.iter_mut().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(marker) = item_marker(line) else {
+            // text with less indent ends the deeper lists
+            let indent = line.len() - line.trim_start().len();
+            while levels.last().is_some_and(|(v, _)| *v >= indent) {
+                levels.pop();
+            }
+            continue;
+        };
+
+        while levels.last().is_some_and(|(v, _)| *v > marker.indent) {
+            levels.pop();
+        }
+        let cont = levels.last().is_some_and(|(v, _)| *v == marker.indent);
+
+        match marker.ordered {
+            Some((num, delim)) => {
+                let next = match levels.last() {
+                    Some((_, Some(next))) if cont => *next,
+                    _ if restart.contains(&idx) => 1,
+                    _ => num,
+                };
+                if cont {
+                    levels.pop();
+                }
+                levels.push((marker.indent, Some(next + 1)));
+
+                if next != num {
+                    *line = format!(
+                        "{}{}{}{}",
+                        &line[..marker.indent],
+                        next,
+                        delim,
+                        &line[marker.marker_end..]
+                    );
+                }
+            }
+            None => {
+                if cont {
+                    levels.pop();
+                }
+                levels.push((marker.indent, None));
+            }
+        }
+    }
+}
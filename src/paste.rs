@@ -0,0 +1,282 @@
+//! Transformations for pasted text.
+
+use crate::md_list;
+use std::path::PathBuf;
+
+/// Looks like a single url.
+pub fn is_url(txt: &str) -> bool {
+    let txt = txt.trim();
+    (txt.starts_with("http://") || txt.starts_with("https://") || txt.starts_with("mailto:"))
+        && !txt.contains(char::is_whitespace)
+}
+
+/// Looks like a file dropped into the terminal.
+/// A single line with the absolute path of an existing file,
+/// maybe quoted or as file:// url.
+pub fn dropped_file(txt: &str) -> Option<PathBuf> {
+    let txt = txt.trim();
+    if txt.is_empty() || txt.contains('\n') {
+        return None;
+    }
+    let txt = txt.strip_prefix("file://").unwrap_or(txt);
+    let txt = txt
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .or_else(|| txt.strip_prefix('"').and_then(|v| v.strip_suffix('"')))
+        .unwrap_or(txt);
+    // some terminals escape spaces.
+    let path = PathBuf::from(txt.replace("\\ ", " "));
+    if path.is_absolute() && path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Looks like a html fragment.
+pub fn is_html(txt: &str) -> bool {
+    let txt = txt.trim_start();
+    if !txt.starts_with('<') {
+        return false;
+    }
+    let lower = txt.to_ascii_lowercase();
+    [
+        "<html", "<body", "<p>", "<p ", "<div", "<span", "<a ", "<b>", "<strong", "<em>", "<i>",
+        "<ul", "<ol", "<li", "<h1", "<h2", "<h3", "<br", "<table", "<meta",
+    ]
+    .iter()
+    .any(|v| lower.contains(v))
+}
+
+/// Convert the most common html tags to markdown.
+/// Everything else is stripped.
+pub fn html_to_md(html: &str) -> String {
+    let mut md = String::new();
+    let mut href = Vec::new();
+    let mut link_start = Vec::new();
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        if let Some(tag_start) = rest.find('<') {
+            md.push_str(&decode_entities(&rest[..tag_start]));
+            let Some(tag_end) = rest[tag_start..].find('>') else {
+                md.push_str(&decode_entities(&rest[tag_start..]));
+                break;
+            };
+            let tag = &rest[tag_start + 1..tag_start + tag_end];
+            rest = &rest[tag_start + tag_end + 1..];
+
+            let closing = tag.starts_with('/');
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+
+            match (name.as_str(), closing) {
+                // no text content, skip to the closing tag.
+                ("style" | "script" | "head" | "title", false) if !tag.ends_with('/') => {
+                    let close = format!("</{}", name);
+                    rest = match rest.to_ascii_lowercase().find(&close) {
+                        Some(idx) => match rest[idx..].find('>') {
+                            Some(end) => &rest[idx + end + 1..],
+                            None => "",
+                        },
+                        None => "",
+                    };
+                }
+                ("b" | "strong", _) => md.push_str("**"),
+                ("i" | "em", _) => md.push('_'),
+                ("code", _) => md.push('`'),
+                ("s" | "del" | "strike", _) => md.push_str("~~"),
+                ("br", _) => md.push('\n'),
+                ("p" | "div", false) => {}
+                ("p" | "div" | "ul" | "ol" | "table", true) => md.push_str("\n\n"),
+                ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                    let level = name[1..].parse::<usize>().unwrap_or(1);
+                    md.push_str(&"#".repeat(level));
+                    md.push(' ');
+                }
+                ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => md.push_str("\n\n"),
+                ("li", false) => md.push_str("* "),
+                ("li" | "tr", true) => md.push('\n'),
+                ("a", false) => {
+                    href.push(attr(tag, "href").unwrap_or_default());
+                    link_start.push(md.len());
+                }
+                ("a", true) => {
+                    if let (Some(href), Some(start)) = (href.pop(), link_start.pop()) {
+                        if !href.is_empty() {
+                            md.insert(start, '[');
+                            md.push_str("](");
+                            md.push_str(&href);
+                            md.push(')');
+                        }
+                    }
+                }
+                _ => {}
+            }
+        } else {
+            md.push_str(&decode_entities(rest));
+            break;
+        }
+    }
+
+    // collapse runs of empty lines
+    let mut result = String::new();
+    let mut empty = 0;
+    for line in md.trim().lines() {
+        if line.trim().is_empty() {
+            empty += 1;
+            if empty > 1 {
+                continue;
+            }
+        } else {
+            empty = 0;
+        }
+        result.push_str(line.trim_end());
+        result.push('\n');
+    }
+    result
+}
+
+/// Value of a html attribute.
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let pattern = format!("{}=", name);
+    // not the end of another attribute name like data-href.
+    let (start, _) = lower
+        .match_indices(&pattern)
+        .find(|(idx, _)| lower[..*idx].ends_with(char::is_whitespace))?;
+    let value = &tag[start + pattern.len()..];
+    let value = if let Some(v) = value.strip_prefix('"') {
+        &v[..v.find('"').unwrap_or(v.len())]
+    } else if let Some(v) = value.strip_prefix('\'') {
+        &v[..v.find('\'').unwrap_or(v.len())]
+    } else {
+        value.split_whitespace().next().unwrap_or_default()
+    };
+    Some(decode_entities(value))
+}
+
+/// Decode the common html entities.
+fn decode_entities(txt: &str) -> String {
+    txt.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Chars that are escaped anywhere.
+const ESCAPE: &[char] = &['\\', '`', '*', '_', '[', ']', '<', '>', '#', '|', '~'];
+
+/// Escape markdown special chars so they render as text.
+/// Existing escapes stay as they are.
+pub fn escape_md(txt: &str, at_line_start: bool) -> String {
+    let mut res = String::with_capacity(txt.len());
+    let mut line_start = at_line_start;
+    let mut chars = txt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.peek().copied().filter(|v| v.is_ascii_punctuation()) {
+                // already escaped
+                res.push(c);
+                res.push(next);
+                chars.next();
+                line_start = false;
+                continue;
+            }
+        }
+
+        if c == '\n' {
+            line_start = true;
+            res.push(c);
+            continue;
+        }
+        if line_start && (c == ' ' || c == '\t') {
+            res.push(c);
+            continue;
+        }
+
+        if ESCAPE.contains(&c) || (line_start && matches!(c, '-' | '+' | '=')) {
+            res.push('\\');
+            res.push(c);
+        } else if line_start && c.is_ascii_digit() {
+            // ordered list marker
+            res.push(c);
+            while let Some(d) = chars.next_if(|v| v.is_ascii_digit()) {
+                res.push(d);
+            }
+            if let Some(d) = chars.next_if(|v| *v == '.' || *v == ')') {
+                res.push('\\');
+                res.push(d);
+            }
+        } else {
+            res.push(c);
+        }
+        line_start = false;
+    }
+    res
+}
+
+/// Remove the backslash of escaped punctuation.
+pub fn unescape_md(txt: &str) -> String {
+    let mut res = String::with_capacity(txt.len());
+    let mut chars = txt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next_if(|v| v.is_ascii_punctuation()) {
+                res.push(next);
+                continue;
+            }
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Indent that continues the line: leading whitespace,
+/// blockquote markers and the width of a list marker.
+pub fn continuation_indent(line: &str) -> String {
+    let mut indent = String::new();
+    let mut rest = line;
+    loop {
+        let body = rest.trim_start_matches([' ', '\t']);
+        indent.push_str(&rest[..rest.len() - body.len()]);
+        rest = body;
+        if let Some(body) = rest.strip_prefix('>') {
+            indent.push('>');
+            rest = body;
+        } else {
+            break;
+        }
+    }
+    if let Some(marker) = md_list::item_marker(rest) {
+        let width = marker.marker_end + 1;
+        indent.push_str(&" ".repeat(width));
+    }
+    indent
+}
+
+/// Prefix all but the first line with the indent.
+pub fn indent_continuation(txt: &str, indent: &str) -> String {
+    if indent.is_empty() {
+        return txt.to_string();
+    }
+    let mut res = String::with_capacity(txt.len());
+    for (idx, line) in txt.split('\n').enumerate() {
+        if idx > 0 {
+            res.push('\n');
+            if line.trim().is_empty() {
+                res.push_str(indent.trim_end());
+            } else {
+                res.push_str(indent);
+            }
+        }
+        res.push_str(line);
+    }
+    res
+}
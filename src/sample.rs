@@ -0,0 +1,43 @@
+//! Synthetic markdown for reproducing parse/format performance.
+
+use std::fmt::Write;
+
+const LOREM: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, \
+    sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. \
+    Ut enim ad minim veniam, quis nostrud *exercitation* ullamco laboris \
+    nisi ut aliquip ex ea commodo `consequat`.";
+
+/// Generate a document with the given number of sections.
+/// Each section has a heading, some paragraphs, a list,
+/// a table and a code block.
+pub fn generate(sections: usize) -> String {
+    let mut buf = String::new();
+
+    _ = writeln!(buf, "# Sample document\n");
+    for n in 0..sections {
+        _ = writeln!(buf, "## Section {}\n", n + 1);
+        for _ in 0..3 {
+            _ = writeln!(buf, "{}\n", LOREM);
+        }
+
+        for i in 0..5 {
+            _ = writeln!(buf, "* item {} with a [link](https://example.com/{})", i, i);
+        }
+        _ = writeln!(buf);
+
+        _ = writeln!(buf, "| Name | Value | Comment |");
+        _ = writeln!(buf, "|:-----|------:|---------|");
+        for i in 0..8 {
+            _ = writeln!(buf, "| row {} | {} | some text |", i, i * n);
+        }
+        _ = writeln!(buf);
+
+        _ = writeln!(buf, "```rust");
+        _ = writeln!(buf, "fn section_{}() -> usize {{", n);
+        _ = writeln!(buf, "    {}", n);
+        _ = writeln!(buf, "}}");
+        _ = writeln!(buf, "```\n");
+    }
+
+    buf
+}
@@ -0,0 +1,67 @@
+//! Scripts that transform the text of the current buffer.
+//!
+//! A script is a [rhai](https://rhai.rs) file. It sees the
+//! buffer as `text`, the selected text as `selection` and
+//! the file as `path`. If it returns a string that replaces
+//! the buffer, anything else leaves it alone.
+//!
+//! ```rhai
+//! text.replace("\t", "    ");
+//! text
+//! ```
+//!
+//! Scripts are listed in the [scripts] section of mdedit.ini
+//! as `name = path`.
+
+use anyhow::{anyhow, Error};
+use rhai::{Dynamic, Engine, Scope};
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::path::Path;
+
+/// Script engine.
+pub struct Scripts {
+    engine: Engine,
+}
+
+impl Debug for Scripts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scripts").finish()
+    }
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        let mut engine = Engine::new();
+        // runaway scripts block the ui.
+        engine.set_max_operations(10_000_000);
+        Self { engine }
+    }
+}
+
+impl Scripts {
+    /// Run the script. Returns the new text, or None if
+    /// the script doesn't change it.
+    pub fn run(
+        &self,
+        script: &Path,
+        path: &Path,
+        text: &str,
+        selection: &str,
+    ) -> Result<Option<String>, Error> {
+        let src = fs::read_to_string(script)
+            .map_err(|e| anyhow!("Can't read {}: {}", script.display(), e))?;
+
+        let mut scope = Scope::new();
+        scope.push("text", text.to_string());
+        scope.push("selection", selection.to_string());
+        scope.push("path", path.to_string_lossy().to_string());
+
+        let result = self
+            .engine
+            .eval_with_scope::<Dynamic>(&mut scope, &src)
+            .map_err(|e| anyhow!("{}: {}", script.display(), e))?;
+
+        Ok(result.into_string().ok())
+    }
+}
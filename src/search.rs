@@ -0,0 +1,96 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Style id for highlighted search matches.
+/// Outside the range used by MDStyle.
+pub const SEARCH_MATCH: usize = 1000;
+
+/// Max length of the search history.
+const MAX_HISTORY: usize = 32;
+
+/// Add a term to the history, newest last.
+/// Repeating the last term doesn't add a new entry.
+pub fn push_history(history: &mut Vec<String>, term: &str) {
+    if term.is_empty() || history.last().is_some_and(|v| v == term) {
+        return;
+    }
+    history.push(term.to_string());
+    if history.len() > MAX_HISTORY {
+        history.drain(..history.len() - MAX_HISTORY);
+    }
+}
+
+/// Byte ranges of all matches of the term.
+pub fn find_all(text: &str, term: &str) -> Vec<Range<usize>> {
+    if term.is_empty() {
+        return Vec::new();
+    }
+    text.match_indices(term)
+        .map(|(pos, v)| pos..pos + v.len())
+        .collect()
+}
+
+/// Next match after pos, or the last one before pos.
+/// Wraps around at the end/start of the text.
+pub fn next_match(matches: &[Range<usize>], pos: usize, backward: bool) -> Option<Range<usize>> {
+    if backward {
+        matches
+            .iter()
+            .rev()
+            .find(|v| v.start < pos)
+            .or(matches.last())
+            .cloned()
+    } else {
+        matches
+            .iter()
+            .find(|v| v.start > pos)
+            .or(matches.first())
+            .cloned()
+    }
+}
+
+/// All files below root that match one of the globs.
+/// Honors .gitignore and friends.
+pub fn files(root: &Path, globs: &[String]) -> Vec<PathBuf> {
+    let patterns = globs
+        .iter()
+        .filter_map(|v| glob::Pattern::new(v).ok())
+        .collect::<Vec<_>>();
+
+    let mut files = Vec::new();
+    let walk = ignore::WalkBuilder::new(root)
+        .standard_filters(true)
+        .build();
+    for w in walk {
+        let Ok(w) = w else {
+            continue;
+        };
+        if !w.file_type().is_some_and(|v| v.is_file()) {
+            continue;
+        }
+        let name = w.file_name().to_string_lossy();
+        if !patterns.iter().any(|v| v.matches(name.as_ref())) {
+            continue;
+        }
+        files.push(w.into_path());
+    }
+    files.sort();
+    files
+}
+
+/// Count the term in all files below root that match one of the globs.
+/// Files without a match are left out.
+pub fn count_files(root: &Path, globs: &[String], term: &str) -> Vec<(PathBuf, usize)> {
+    let mut counts = Vec::new();
+    for path in files(root, globs) {
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let n = text.matches(term).count();
+        if n > 0 {
+            counts.push((path, n));
+        }
+    }
+    counts
+}
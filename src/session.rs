@@ -0,0 +1,182 @@
+//! Session files.
+//!
+//! The open files, splits and cursors as a standalone toml file.
+//!
+//! ```toml
+//! version = 1
+//! selected = [0, 1]
+//! split_widths = [60, 60]
+//!
+//! [[file]]
+//! split = 0
+//! tab = 0
+//! path = "chapter1.md"
+//! cursor = [4, 10]
+//! anchor = [0, 10]
+//! offset = [0, 2, 0]
+//! pinned = true
+//! ```
+//!
+//! Paths are relative to the session file if possible.
+//! 'anchor' is only written for a selection, 'pinned'
+//! only for pinned tabs.
+
+use crate::cfg::EditorLayout;
+use anyhow::{anyhow, Error};
+use rat_widget::text::upos_type;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Format version.
+const VERSION: i64 = 1;
+
+/// Default extension.
+pub const EXTENSION: &str = "mdsession";
+
+/// Write the layout as session file.
+pub fn export(layout: &EditorLayout, path: &Path) -> Result<(), Error> {
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    let mut files = Vec::new();
+    for (s, t, file) in &layout.tab_state {
+        let mut tab = Table::new();
+        tab.insert("split".into(), Value::Integer(*s as i64));
+        tab.insert("tab".into(), Value::Integer(*t as i64));
+        let file = file.strip_prefix(base).unwrap_or(file);
+        tab.insert(
+            "path".into(),
+            Value::String(file.to_string_lossy().to_string()),
+        );
+        if let Some((_, _, x, y)) = find(&layout.tab_cursor, *s, *t) {
+            tab.insert("cursor".into(), int_array(&[*x, *y]));
+        }
+        if let Some((_, _, x, y)) = find(&layout.tab_anchor, *s, *t) {
+            tab.insert("anchor".into(), int_array(&[*x, *y]));
+        }
+        if layout.tab_pinned.contains(&(*s, *t)) {
+            tab.insert("pinned".into(), Value::Boolean(true));
+        }
+        if let Some((_, _, ox, oy, os)) = layout
+            .tab_offset
+            .iter()
+            .find(|(vs, vt, _, _, _)| vs == s && vt == t)
+        {
+            tab.insert("offset".into(), int_array(&[*ox, *oy, *os]));
+        }
+        files.push(Value::Table(tab));
+    }
+
+    let mut session = Table::new();
+    session.insert("version".into(), Value::Integer(VERSION));
+    session.insert(
+        "selected".into(),
+        int_array(&[
+            layout.tab_selected.0 as upos_type,
+            layout.tab_selected.1 as upos_type,
+        ]),
+    );
+    session.insert(
+        "split_widths".into(),
+        Value::Array(
+            layout
+                .edit_split_at
+                .iter()
+                .map(|v| Value::Integer(*v as i64))
+                .collect(),
+        ),
+    );
+    session.insert("file".into(), Value::Array(files));
+
+    fs::write(path, session.to_string())?;
+    Ok(())
+}
+
+/// Read a session file.
+///
+/// Files that don't exist are kept in the layout,
+/// see [EditorLayout::existing].
+pub fn import(path: &Path) -> Result<EditorLayout, Error> {
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    let session = fs::read_to_string(path)?.parse::<Table>()?;
+    if session.get("version").and_then(|v| v.as_integer()) != Some(VERSION) {
+        return Err(anyhow!("{} is not a session file", path.display()));
+    }
+
+    let mut layout = EditorLayout::default();
+    if let Some([s, t]) = ints::<2>(session.get("selected")) {
+        layout.tab_selected = (s as usize, t as usize);
+    }
+    if let Some(widths) = session.get("split_widths").and_then(|v| v.as_array()) {
+        layout.edit_split_at = widths
+            .iter()
+            .filter_map(|v| v.as_integer())
+            .map(|v| v.clamp(0, u16::MAX as i64) as u16)
+            .collect();
+    }
+
+    let files = session
+        .get("file")
+        .and_then(|v| v.as_array())
+        .map(|v| v.as_slice())
+        .unwrap_or_default();
+    for tab in files {
+        let (Some(s), Some(t), Some(file)) = (
+            tab.get("split").and_then(|v| v.as_integer()),
+            tab.get("tab").and_then(|v| v.as_integer()),
+            tab.get("path").and_then(|v| v.as_str()),
+        ) else {
+            return Err(anyhow!("invalid file entry {} in {}", tab, path.display()));
+        };
+        let (s, t) = (s.max(0) as usize, t.max(0) as usize);
+
+        let file = PathBuf::from(file);
+        let file = if file.is_relative() {
+            base.join(file)
+        } else {
+            file
+        };
+        layout.tab_state.push((s, t, file));
+
+        if let Some([x, y]) = ints::<2>(tab.get("cursor")) {
+            layout.tab_cursor.push((s, t, x, y));
+        }
+        if let Some([x, y]) = ints::<2>(tab.get("anchor")) {
+            layout.tab_anchor.push((s, t, x, y));
+        }
+        if let Some([ox, oy, os]) = ints::<3>(tab.get("offset")) {
+            layout.tab_offset.push((s, t, ox, oy, os));
+        }
+        if tab.get("pinned").and_then(|v| v.as_bool()) == Some(true) {
+            layout.tab_pinned.push((s, t));
+        }
+    }
+
+    Ok(layout)
+}
+
+fn find(
+    v: &[(usize, usize, upos_type, upos_type)],
+    s: usize,
+    t: usize,
+) -> Option<&(usize, usize, upos_type, upos_type)> {
+    v.iter().find(|(vs, vt, _, _)| *vs == s && *vt == t)
+}
+
+fn int_array(v: &[upos_type]) -> Value {
+    Value::Array(v.iter().map(|v| Value::Integer(*v as i64)).collect())
+}
+
+/// Array of N positive integers.
+fn ints<const N: usize>(v: Option<&Value>) -> Option<[upos_type; N]> {
+    let v = v?.as_array()?;
+    if v.len() != N {
+        return None;
+    }
+    let mut res = [0; N];
+    for (r, v) in res.iter_mut().zip(v.iter()) {
+        *r = upos_type::try_from(v.as_integer()?).ok()?;
+    }
+    Some(res)
+}
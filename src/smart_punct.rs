@@ -0,0 +1,46 @@
+//! Typographic quotes, dashes and ellipsis.
+
+/// A quote after this char opens.
+fn opens(prev: Option<char>) -> bool {
+    match prev {
+        None => true,
+        Some(c) => c.is_whitespace() || matches!(c, '(' | '[' | '{' | '\u{2013}' | '\u{2014}'),
+    }
+}
+
+/// Replacement for the text before the cursor, after
+/// the char c has been typed. The text includes c.
+///
+/// Returns the number of bytes to replace and the replacement.
+pub fn typed(before: &str, c: char) -> Option<(usize, &'static str)> {
+    let prev = before[..before.len() - c.len_utf8()].chars().next_back();
+    match c {
+        '"' => Some((1, if opens(prev) { "\u{201C}" } else { "\u{201D}" })),
+        '\'' => Some((1, if opens(prev) { "\u{2018}" } else { "\u{2019}" })),
+        '.' if before.ends_with("...") => Some((3, "\u{2026}")),
+        '-' if is_rule(before) => None,
+        '-' if before.ends_with("\u{2013}-") => Some(("\u{2013}-".len(), "\u{2014}")),
+        '-' if before.ends_with("--") => Some((2, "\u{2013}")),
+        _ => None,
+    }
+}
+
+/// Dashes of a rule, setext heading or table delimiter row.
+fn is_rule(line: &str) -> bool {
+    let line = &line[line.rfind('\n').map(|v| v + 1).unwrap_or(0)..];
+    line.chars()
+        .all(|c| c.is_whitespace() || matches!(c, '-' | '|' | ':' | '+'))
+}
+
+/// Convert all quotes, dashes and ellipsis in the text.
+pub fn convert(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        res.push(c);
+        if let Some((n, new)) = typed(&res, c) {
+            res.truncate(res.len() - n);
+            res.push_str(new);
+        }
+    }
+    res
+}
@@ -1,3 +1,4 @@
+use crate::cfg::MDConfig;
 use crate::editor_file;
 use crate::editor_file::MDFileState;
 use crate::global::event::{MDEvent, MDImmediate};
@@ -5,6 +6,7 @@ use crate::global::theme::MDWidgets;
 use crate::global::GlobalState;
 use crate::rat_salsa::timer::TimerDef;
 use crate::rat_salsa::{Control, SalsaContext};
+use crate::text_width;
 use anyhow::Error;
 use log::error;
 use rat_theme4::WidgetStyle;
@@ -20,9 +22,13 @@ use ratatui::style::Style;
 use ratatui::text::Line;
 use ratatui::widgets::{Block, Borders, StatefulWidget};
 use std::cmp::max;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+// longer file names are cut with an ellipsis.
+const MAX_TAB_TITLE: u16 = 24;
+
 #[derive(Debug)]
 pub struct SplitTabState {
     pub container: FocusFlag,
@@ -33,6 +39,13 @@ pub struct SplitTabState {
     pub split: SplitState,
     pub split_tab: Vec<TabbedState>,
     pub split_tab_file: Vec<Vec<MDFileState>>,
+    /// Directory the file list keeps while the split
+    /// is selected, instead of following the files.
+    pub split_dir: Vec<Option<PathBuf>>,
+
+    /// Mirror the vertical offset of the selected editor
+    /// to all other splits.
+    pub scroll_sync: bool,
 }
 
 impl Default for SplitTabState {
@@ -44,6 +57,8 @@ impl Default for SplitTabState {
             split: SplitState::named("splitter"),
             split_tab: Default::default(),
             split_tab_file: Default::default(),
+            split_dir: Default::default(),
+            scroll_sync: false,
         }
     }
 }
@@ -62,6 +77,10 @@ pub fn render(
         .into_widgets();
     split_layout.render(area, buf, &mut state.split);
 
+    if state.scroll_sync {
+        state.sync_scroll();
+    }
+
     if state.split.widget_areas.is_empty() {
         buf.set_style(
             area,
@@ -71,37 +90,65 @@ pub fn render(
         );
     }
 
+    // files shown in more than one split.
+    let mut split_count = HashMap::<&Path, usize>::new();
+    for split in state.split_tab_file.iter() {
+        let paths = split
+            .iter()
+            .map(|v| v.path.as_path())
+            .collect::<HashSet<_>>();
+        for path in paths {
+            *split_count.entry(path).or_default() += 1;
+        }
+    }
+    let multi_view = split_count
+        .into_iter()
+        .filter(|(_, n)| *n > 1)
+        .map(|(path, _)| path.to_path_buf())
+        .collect::<HashSet<_>>();
+
     // let max_idx_split = state.split.widget_areas.len().saturating_sub(1);
     for (idx_split, edit_area) in state.split.widget_areas.iter().enumerate() {
-        Tabbed::new()
-            .tab_type(TabType::Attached)
-            .closeable(true)
-            .block(Block::bordered().borders(Borders::TOP))
-            .styles(ctx.theme.style(WidgetStyle::TABBED))
-            .tabs(state.split_tab_file[idx_split].iter().map(|v| {
-                let title = format!(
-                    "{}{}",
-                    v.path.file_name().unwrap_or_default().to_string_lossy(),
-                    if v.changed { " \u{1F5AB}" } else { "" }
-                );
-                Line::from(title)
-            }))
-            .render(*edit_area, buf, &mut state.split_tab[idx_split]);
+        // without tab bar the editor gets the whole area.
+        let edit_area = if state.tabs_hidden(idx_split, ctx) {
+            *edit_area
+        } else {
+            Tabbed::new()
+                .tab_type(TabType::Attached)
+                .closeable(true)
+                .block(Block::bordered().borders(Borders::TOP))
+                .styles(ctx.theme.style(WidgetStyle::TABBED))
+                .tabs(state.split_tab_file[idx_split].iter().map(|v| {
+                    let name = v.path.file_name().unwrap_or_default().to_string_lossy();
+                    let title = format!(
+                        "{}{}{}{}{}",
+                        if v.pinned { "\u{1F4CC} " } else { "" },
+                        text_width::truncate(&name, MAX_TAB_TITLE),
+                        if v.changed { " \u{1F5AB}" } else { "" },
+                        if v.read_only { " [ro]" } else { "" },
+                        if multi_view.contains(&v.path) {
+                            " \u{29C9}"
+                        } else {
+                            ""
+                        }
+                    );
+                    Line::from(title)
+                }))
+                .render(*edit_area, buf, &mut state.split_tab[idx_split]);
+            state.split_tab[idx_split].widget_area
+        };
 
         if let Some(idx_tab) = state.split_tab[idx_split].selected() {
             editor_file::render(
                 0, // if max_idx_split == idx_split { 0 } else { 1 },
-                state.split_tab[idx_split].widget_area,
+                edit_area,
                 buf,
                 &mut state.split_tab_file[idx_split][idx_tab],
                 ctx,
             )?;
         } else {
             // should not occur?
-            buf.set_style(
-                state.split_tab[idx_split].widget_area,
-                Style::new().on_red(),
-            );
+            buf.set_style(edit_area, Style::new().on_red());
         }
     }
 
@@ -200,8 +247,11 @@ pub fn event(
         }
 
         let (idx_split, r) = 'tab: {
-            for (idx_split, tabbed) in state.split_tab.iter_mut().enumerate() {
-                let r = tabbed.handle(event, Regular);
+            for idx_split in 0..state.split_tab.len() {
+                if state.tabs_hidden(idx_split, ctx) {
+                    continue;
+                }
+                let r = state.split_tab[idx_split].handle(event, Regular);
                 if r.is_consumed() {
                     break 'tab (idx_split, r);
                 }
@@ -251,6 +301,11 @@ pub fn event(
 }
 
 impl SplitTabState {
+    // The tab bar of the split is hidden.
+    fn tabs_hidden(&self, idx_split: usize, ctx: &GlobalState) -> bool {
+        ctx.cfg.hide_single_tab && self.split_tab_file[idx_split].len() == 1
+    }
+
     // Assert that focus and selection are in sync.
     pub fn assert_selection(&mut self) {
         // Find which split contains the current focus.
@@ -279,6 +334,7 @@ impl SplitTabState {
         }
         if pos.0 == self.split_tab_file.len() {
             self.split_tab_file.push(Vec::new());
+            self.split_dir.push(None);
             self.split_tab
                 .push(TabbedState::named(format!("tabbed-{}", pos.0).as_str()));
         }
@@ -298,52 +354,45 @@ impl SplitTabState {
     }
 
     // Close tab (split-idx, tab-idx).
-    pub fn close(&mut self, pos: (usize, usize), _ctx: &mut GlobalState) -> Result<(), Error> {
+    pub fn close(&mut self, pos: (usize, usize), ctx: &mut GlobalState) -> Result<(), Error> {
         if pos.0 < self.split_tab_file.len() {
             if pos.1 < self.split_tab_file[pos.0].len() {
-                self.split_tab_file[pos.0][pos.1].save()?;
+                let md = &mut self.split_tab_file[pos.0][pos.1];
+                if let Err(e) = md.save(&ctx.cfg) {
+                    // keep the tab
+                    ctx.queue_event(MDEvent::SaveFailed(md.path.clone(), format!("{:#}", e)));
+                    return Ok(());
+                }
 
                 // remove tab
                 self.split_tab_file[pos.0].remove(pos.1);
 
+                let len = self.split_tab_file[pos.0].len();
                 if let Some(sel_tab) = self.split_tab[pos.0].selected() {
-                    let new_tab = if sel_tab >= pos.1 {
-                        if sel_tab < self.split_tab_file[pos.0].len() {
-                            Some(sel_tab)
-                        } else if self.split_tab_file[pos.0].len() > 0 {
-                            Some(self.split_tab_file[pos.0].len() - 1)
-                        } else {
-                            None
-                        }
-                    } else {
-                        Some(sel_tab)
-                    };
-                    self.sel_tab = new_tab;
+                    let new_tab = index_after_remove(sel_tab, pos.1, len);
                     self.split_tab[pos.0].select(new_tab);
+                    if self.sel_split == Some(pos.0) {
+                        self.sel_tab = new_tab;
+                    }
                 }
 
                 // maybe remove split
-                if self.split_tab_file[pos.0].len() == 0 {
+                if len == 0 {
                     self.split_tab_file.remove(pos.0);
+                    self.split_dir.remove(pos.0);
                     self.split_tab.remove(pos.0);
 
                     if let Some(sel_split) = self.sel_split {
-                        let new_split = if sel_split >= pos.0 {
-                            if sel_split < self.split_tab_file.len() {
-                                Some(sel_split)
-                            } else if self.split_tab_file.len() > 0 {
-                                Some(self.split_tab_file.len() - 1)
-                            } else {
-                                None
-                            }
-                        } else {
-                            Some(sel_split)
-                        };
+                        // the neighbour takes over if the selected
+                        // split was removed.
+                        let new_split =
+                            index_after_remove(sel_split, pos.0, self.split_tab_file.len());
 
                         self.sel_split = new_split;
                         if let Some(new_split) = new_split {
-                            self.sel_tab = Some(0);
-                            self.split_tab[new_split].select(Some(0));
+                            let new_tab = self.split_tab[new_split].selected().unwrap_or_default();
+                            self.sel_tab = Some(new_tab);
+                            self.split_tab[new_split].select(Some(new_tab));
                         } else {
                             self.sel_tab = None;
                         }
@@ -361,8 +410,74 @@ impl SplitTabState {
                 self.sel_split = Some(pos.0);
                 self.sel_tab = Some(pos.1);
                 self.split_tab[pos.0].select(Some(pos.1));
+                self.split_tab_file[pos.0][pos.1].last_focus = Instant::now();
+            }
+        }
+    }
+
+    // Close the least recently selected tabs if there are
+    // more than max_open_buffers. Changed tabs are kept.
+    pub fn evict(&mut self, ctx: &mut GlobalState) -> Result<(), Error> {
+        let max_open = ctx.cfg.max_open_buffers;
+        if max_open == 0 {
+            return Ok(());
+        }
+
+        let sel_path = self.selected().map(|(_, v)| v.path.clone());
+        loop {
+            let open = self.split_tab_file.iter().map(|v| v.len()).sum::<usize>();
+            if open <= max_open {
+                break;
+            }
+
+            let mut lru: Option<((usize, usize), Instant)> = None;
+            for (idx_split, tabs) in self.split_tab_file.iter().enumerate() {
+                for (idx_tab, tab) in tabs.iter().enumerate() {
+                    if tab.changed || tab.pinned || Some(&tab.path) == sel_path.as_ref() {
+                        continue;
+                    }
+                    if lru.is_none_or(|(_, t)| tab.last_focus < t) {
+                        lru = Some(((idx_split, idx_tab), tab.last_focus));
+                    }
+                }
+            }
+            let Some((pos, _)) = lru else {
+                break;
+            };
+
+            let name = self.split_tab_file[pos.0][pos.1]
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            self.close(pos, ctx)?;
+            ctx.queue_event(MDEvent::Info(format!("closed {}", name)));
+        }
+        // closing may move the selection
+        if let Some(sel_path) = sel_path {
+            if let Some((pos, _)) = self.for_path(&sel_path) {
+                self.select(pos, ctx);
+            }
+        }
+        Ok(())
+    }
+
+    // Most recently selected tab apart from the current one.
+    pub fn last_selected(&self) -> Option<(usize, usize)> {
+        let sel = self.selected_pos();
+        let mut mru: Option<((usize, usize), Instant)> = None;
+        for (idx_split, split) in self.split_tab_file.iter().enumerate() {
+            for (idx_tab, tab) in split.iter().enumerate() {
+                if Some((idx_split, idx_tab)) == sel {
+                    continue;
+                }
+                if mru.is_none_or(|(_, t)| tab.last_focus > t) {
+                    mru = Some(((idx_split, idx_tab), tab.last_focus));
+                }
             }
         }
+        mru.map(|(pos, _)| pos)
     }
 
     // Rebuild focus and focus selected
@@ -403,6 +518,60 @@ impl SplitTabState {
         false
     }
 
+    // Apply the vertical offset of the selected editor
+    // to the visible editors of the other splits.
+    pub fn sync_scroll(&mut self) {
+        let Some(((sel_split, _), sel)) = self.selected() else {
+            return;
+        };
+        let offset = sel.edit.offset().1;
+        let sub_row_offset = sel.edit.sub_row_offset();
+
+        for (idx_split, tabbed) in self.split_tab.iter().enumerate() {
+            if idx_split == sel_split {
+                continue;
+            }
+            let Some(idx_tab) = tabbed.selected() else {
+                continue;
+            };
+            let edit = &mut self.split_tab_file[idx_split][idx_tab].edit;
+            let max_offset = (edit.len_lines() as usize).saturating_sub(1);
+            let (ox, _) = edit.offset();
+            if offset <= max_offset {
+                edit.set_offset((ox, offset));
+                edit.set_sub_row_offset(sub_row_offset);
+            } else {
+                edit.set_offset((ox, max_offset));
+                edit.set_sub_row_offset(0);
+            }
+        }
+    }
+
+    // Swap the selected split with its right neighbour,
+    // or with the left one for the last split.
+    pub fn swap_split(&mut self, ctx: &mut GlobalState) -> bool {
+        let Some(idx_split) = self.sel_split else {
+            return false;
+        };
+        if self.split_tab_file.len() < 2 {
+            return false;
+        }
+        let other = if idx_split + 1 < self.split_tab_file.len() {
+            idx_split + 1
+        } else {
+            idx_split - 1
+        };
+
+        self.split_tab_file.swap(idx_split, other);
+        self.split_dir.swap(idx_split, other);
+        self.split_tab.swap(idx_split, other);
+
+        let idx_tab = self.split_tab[other].selected().unwrap_or_default();
+        self.select((other, idx_tab), ctx);
+        self.focus_selected(ctx);
+        true
+    }
+
     // Position of the current focus.
     pub fn selected_pos(&self) -> Option<(usize, usize)> {
         if let Some(idx_split) = self.sel_split {
@@ -463,14 +632,63 @@ impl SplitTabState {
         None
     }
 
-    // Save all files.
-    pub fn save(&mut self) -> Result<(), Error> {
-        for (_idx_split, tabs) in self.split_tab_file.iter_mut().enumerate() {
-            for (_idx_tab, tab) in tabs.iter_mut().enumerate() {
-                tab.save()?
+    // Save one file. If this works all its clones are unchanged.
+    // None if the file is not open.
+    pub fn save_file(&mut self, path: &Path, cfg: &MDConfig) -> Option<Result<(), Error>> {
+        let mut clones = self
+            .split_tab_file
+            .iter_mut()
+            .flatten()
+            .filter(|v| v.path == path)
+            .collect::<Vec<_>>();
+        let idx = clones.iter().position(|v| v.changed).unwrap_or(0);
+        if let Err(e) = clones.get_mut(idx)?.save(cfg) {
+            return Some(Err(e));
+        }
+        for tab in clones {
+            tab.changed = false;
+        }
+        Some(Ok(()))
+    }
+
+    // Save all files. Returns the files that failed.
+    pub fn save(&mut self, cfg: &MDConfig) -> Vec<(PathBuf, Error)> {
+        let mut failed = Vec::new();
+        let mut saved = Vec::new();
+        for tab in self.split_tab_file.iter_mut().flatten() {
+            if !tab.changed {
+                continue;
+            }
+            // clones of a saved file only take the flag.
+            if saved.contains(&tab.path) {
+                tab.changed = false;
+                continue;
+            }
+            if failed.iter().any(|(v, _)| *v == tab.path) {
+                continue;
+            }
+            match tab.save(cfg) {
+                Ok(()) => saved.push(tab.path.clone()),
+                Err(e) => failed.push((tab.path.clone(), e)),
             }
         }
-        Ok(())
+        failed
+    }
+
+    // Files with unsaved changes.
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for tab in self.split_tab_file.iter().flatten() {
+            if tab.changed && !files.contains(&tab.path) {
+                files.push(tab.path.clone());
+            }
+        }
+        files
+    }
+
+    // Any unsaved changes?
+    pub fn is_changed(&self) -> bool {
+        self.split_tab_file.iter().flatten().any(|v| v.changed)
     }
 
     // Run the replay for the file at path.
@@ -495,3 +713,39 @@ impl SplitTabState {
         }
     }
 }
+
+/// Selected index after the item at `removed` is gone.
+/// `len` is the length after the removal. If the selected
+/// item was removed, the next one or the new last one is
+/// selected.
+fn index_after_remove(sel: usize, removed: usize, len: usize) -> Option<usize> {
+    if sel > removed {
+        Some(sel - 1)
+    } else if sel < len {
+        Some(sel)
+    } else {
+        len.checked_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::index_after_remove;
+
+    #[test]
+    fn test_close_middle_split() {
+        // three splits, the middle one is closed.
+        assert_eq!(index_after_remove(0, 1, 2), Some(0));
+        // the right neighbour takes over.
+        assert_eq!(index_after_remove(1, 1, 2), Some(1));
+        // sel_split moves down.
+        assert_eq!(index_after_remove(2, 1, 2), Some(1));
+    }
+
+    #[test]
+    fn test_close_last() {
+        assert_eq!(index_after_remove(2, 2, 2), Some(1));
+        assert_eq!(index_after_remove(0, 0, 0), None);
+        assert_eq!(index_after_remove(0, 2, 2), Some(0));
+    }
+}
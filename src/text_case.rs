@@ -0,0 +1,65 @@
+//! Upper/lower/title case.
+
+/// Case conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    Upper,
+    Lower,
+    Title,
+}
+
+/// Words that stay lower case in a title, except at the start or end.
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to",
+    "up", "via", "vs",
+];
+
+pub fn change_case(txt: &str, mode: CaseMode) -> String {
+    match mode {
+        CaseMode::Upper => txt.to_uppercase(),
+        CaseMode::Lower => txt.to_lowercase(),
+        CaseMode::Title => title_case(txt),
+    }
+}
+
+/// Capitalize each word. Small words and urls are left alone.
+fn title_case(txt: &str) -> String {
+    let n_words = txt.split_whitespace().count();
+
+    let mut res = String::with_capacity(txt.len());
+    let mut word_idx = 0;
+    let mut rest = txt;
+    while !rest.is_empty() {
+        let ws = rest.len() - rest.trim_start().len();
+        res.push_str(&rest[..ws]);
+        rest = &rest[ws..];
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        rest = &rest[end..];
+        if word.is_empty() {
+            continue;
+        }
+
+        // markup around the word doesn't count
+        let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+        let small = SMALL_WORDS.contains(&bare.to_lowercase().as_str());
+        let keep = word.contains("://") || (small && word_idx > 0 && word_idx + 1 < n_words);
+
+        if keep {
+            res.push_str(word);
+        } else {
+            let mut done = false;
+            for c in word.chars() {
+                if !done && c.is_alphabetic() {
+                    res.extend(c.to_uppercase());
+                    done = true;
+                } else {
+                    res.push(c);
+                }
+            }
+        }
+        word_idx += 1;
+    }
+    res
+}
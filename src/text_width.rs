@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+thread_local! {
+    static WIDTH_CACHE: RefCell<HashMap<String, u16>> = RefCell::new(HashMap::new());
+}
+
+/// Display width of the string.
+///
+/// Cached, this is meant for the tab titles and the status
+/// which are rendered each frame.
+pub fn width(s: &str) -> u16 {
+    WIDTH_CACHE.with_borrow_mut(|cache| {
+        if let Some(w) = cache.get(s) {
+            return *w;
+        }
+        if cache.len() > 1024 {
+            cache.clear();
+        }
+        let w = s.width().min(u16::MAX as usize) as u16;
+        cache.insert(s.to_string(), w);
+        w
+    })
+}
+
+/// Cut the string to the display width. Ends with an ellipsis if cut.
+pub fn truncate(s: &str, max: u16) -> Cow<'_, str> {
+    if width(s) <= max {
+        return Cow::Borrowed(s);
+    }
+    if max == 0 {
+        return Cow::Borrowed("");
+    }
+
+    let mut res = String::new();
+    let mut w = 0;
+    for c in s.chars() {
+        let cw = c.width().unwrap_or(0) as u16;
+        if w + cw + 1 > max {
+            break;
+        }
+        res.push(c);
+        w += cw;
+    }
+    res.push('\u{2026}');
+    Cow::Owned(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate, width};
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("a\u{1F600}b"), 4);
+        // combining accent
+        assert_eq!(width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abcdef", 4), "abc\u{2026}");
+        assert_eq!(truncate("abc", 0), "");
+        // a wide char doesn't fit in the last cell
+        assert_eq!(truncate("日本語", 4), "日\u{2026}");
+        assert_eq!(truncate("日本語", 5), "日本\u{2026}");
+        assert_eq!(
+            truncate("\u{1F600}\u{1F600}\u{1F600}", 4),
+            "\u{1F600}\u{2026}"
+        );
+        for max in 0..8 {
+            assert!(width(&truncate("日本語日本語", max)) <= max);
+        }
+    }
+}